@@ -0,0 +1,10 @@
+-- Minimum quantities used by the low-stock report (GET /api/reports/low-stock).
+-- An ITEM threshold wins over a CATEGORY threshold, which wins over the GLOBAL one.
+-- SCOPE_VALUE holds the ITEM_ID or ITEM_CAT, and '*' for the GLOBAL row.
+CREATE TABLE ODBC_JHC.REORDER_THRESHOLDS_JHC (
+    SCOPE_TYPE  VARCHAR2(10) NOT NULL,
+    SCOPE_VALUE VARCHAR2(50) DEFAULT '*' NOT NULL,
+    MIN_QTY     NUMBER NOT NULL,
+    CONSTRAINT REORDER_THRESHOLDS_JHC_PK PRIMARY KEY (SCOPE_TYPE, SCOPE_VALUE),
+    CONSTRAINT REORDER_THRESHOLDS_JHC_SCOPE CHECK (SCOPE_TYPE IN ('GLOBAL', 'CATEGORY', 'ITEM'))
+);
@@ -0,0 +1,39 @@
+-- Inter-store transfer requests (/api/transfers). Transfers only track the request,
+-- stock is still moved in the ERP.
+CREATE SEQUENCE ODBC_JHC.TRANSFERS_JHC_SEQ START WITH 1 INCREMENT BY 1 NOCACHE;
+
+CREATE TABLE ODBC_JHC.TRANSFERS_JHC (
+    TRANSFER_ID  NUMBER NOT NULL,
+    FROM_STORE   VARCHAR2(2) NOT NULL,
+    TO_STORE     VARCHAR2(2) NOT NULL,
+    STATUS       VARCHAR2(20) NOT NULL,
+    REQUESTED_BY VARCHAR2(50) NOT NULL,
+    NOTE         VARCHAR2(500),
+    CREATED_AT   DATE DEFAULT SYSDATE NOT NULL,
+    UPDATED_AT   DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT TRANSFERS_JHC_PK PRIMARY KEY (TRANSFER_ID)
+);
+
+CREATE TABLE ODBC_JHC.TRANSFER_LINES_JHC (
+    TRANSFER_ID   NUMBER NOT NULL,
+    ITEM_ID       VARCHAR2(50) NOT NULL,
+    QTY_REQUESTED NUMBER NOT NULL,
+    QTY_APPROVED  NUMBER,
+    QTY_SHIPPED   NUMBER,
+    QTY_RECEIVED  NUMBER,
+    CONSTRAINT TRANSFER_LINES_JHC_PK PRIMARY KEY (TRANSFER_ID, ITEM_ID),
+    CONSTRAINT TRANSFER_LINES_JHC_FK FOREIGN KEY (TRANSFER_ID) REFERENCES ODBC_JHC.TRANSFERS_JHC (TRANSFER_ID)
+);
+
+CREATE TABLE ODBC_JHC.TRANSFER_AUDIT_JHC (
+    AUDIT_ID    NUMBER GENERATED ALWAYS AS IDENTITY,
+    TRANSFER_ID NUMBER NOT NULL,
+    ACTION      VARCHAR2(20) NOT NULL,
+    USERNAME    VARCHAR2(50) NOT NULL,
+    FROM_STATUS VARCHAR2(20),
+    TO_STATUS   VARCHAR2(20) NOT NULL,
+    NOTE        VARCHAR2(500),
+    CREATED_AT  DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT TRANSFER_AUDIT_JHC_PK PRIMARY KEY (AUDIT_ID),
+    CONSTRAINT TRANSFER_AUDIT_JHC_FK FOREIGN KEY (TRANSFER_ID) REFERENCES ODBC_JHC.TRANSFERS_JHC (TRANSFER_ID)
+);
@@ -0,0 +1,40 @@
+-- Stock-taking sessions (/api/counts). Lines hold every scan, results are the
+-- variance frozen when the session is closed.
+CREATE SEQUENCE ODBC_JHC.COUNT_SESSIONS_JHC_SEQ START WITH 1 INCREMENT BY 1 NOCACHE;
+
+CREATE TABLE ODBC_JHC.COUNT_SESSIONS_JHC (
+    SESSION_ID NUMBER NOT NULL,
+    STORE_ID   VARCHAR2(2) NOT NULL,
+    ITEM_CAT   VARCHAR2(50),
+    STATUS     VARCHAR2(10) NOT NULL,
+    CREATED_BY VARCHAR2(50) NOT NULL,
+    CREATED_AT DATE DEFAULT SYSDATE NOT NULL,
+    CLOSED_BY  VARCHAR2(50),
+    CLOSED_AT  DATE,
+    NOTE       VARCHAR2(500),
+    CONSTRAINT COUNT_SESSIONS_JHC_PK PRIMARY KEY (SESSION_ID)
+);
+
+CREATE TABLE ODBC_JHC.COUNT_LINES_JHC (
+    LINE_ID      NUMBER GENERATED ALWAYS AS IDENTITY,
+    SESSION_ID   NUMBER NOT NULL,
+    ITEM_ID      VARCHAR2(50) NOT NULL,
+    SCANNED_CODE VARCHAR2(50),
+    QTY          NUMBER NOT NULL,
+    USERNAME     VARCHAR2(50) NOT NULL,
+    CREATED_AT   DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT COUNT_LINES_JHC_PK PRIMARY KEY (LINE_ID),
+    CONSTRAINT COUNT_LINES_JHC_FK FOREIGN KEY (SESSION_ID) REFERENCES ODBC_JHC.COUNT_SESSIONS_JHC (SESSION_ID)
+);
+
+CREATE INDEX ODBC_JHC.COUNT_LINES_JHC_SESSION ON ODBC_JHC.COUNT_LINES_JHC (SESSION_ID, ITEM_ID);
+
+CREATE TABLE ODBC_JHC.COUNT_RESULTS_JHC (
+    SESSION_ID  NUMBER NOT NULL,
+    ITEM_ID     VARCHAR2(50) NOT NULL,
+    ITEM_DESC   VARCHAR2(500),
+    SYSTEM_QTY  NUMBER NOT NULL,
+    COUNTED_QTY NUMBER NOT NULL,
+    CONSTRAINT COUNT_RESULTS_JHC_PK PRIMARY KEY (SESSION_ID, ITEM_ID),
+    CONSTRAINT COUNT_RESULTS_JHC_FK FOREIGN KEY (SESSION_ID) REFERENCES ODBC_JHC.COUNT_SESSIONS_JHC (SESSION_ID)
+);
@@ -0,0 +1,26 @@
+-- Change tracking for JHC_INVDATA (/api/changes). INV_STATE_JHC keeps the last
+-- captured values per item and store, INV_CHANGES_JHC one row per changed field.
+CREATE TABLE ODBC_JHC.INV_STATE_JHC (
+    ITEM_ID          VARCHAR2(50) NOT NULL,
+    STORE_ID         VARCHAR2(2) NOT NULL,
+    QTY              NUMBER,
+    SALE_PRICE_NOTAX NUMBER,
+    FIRST_DISC_PER   NUMBER,
+    SECOND_DISC_PER  NUMBER,
+    CAPTURED_AT      DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT INV_STATE_JHC_PK PRIMARY KEY (ITEM_ID, STORE_ID)
+);
+
+CREATE TABLE ODBC_JHC.INV_CHANGES_JHC (
+    CHANGE_ID  NUMBER GENERATED ALWAYS AS IDENTITY,
+    ITEM_ID    VARCHAR2(50) NOT NULL,
+    STORE_ID   VARCHAR2(2) NOT NULL,
+    FIELD      VARCHAR2(20) NOT NULL,
+    OLD_VALUE  NUMBER,
+    NEW_VALUE  NUMBER,
+    CHANGED_AT DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT INV_CHANGES_JHC_PK PRIMARY KEY (CHANGE_ID)
+);
+
+CREATE INDEX ODBC_JHC.INV_CHANGES_JHC_DATE ON ODBC_JHC.INV_CHANGES_JHC (CHANGED_AT, STORE_ID);
+CREATE INDEX ODBC_JHC.INV_CHANGES_JHC_ITEM ON ODBC_JHC.INV_CHANGES_JHC (ITEM_ID, CHANGED_AT);
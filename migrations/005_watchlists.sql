@@ -0,0 +1,29 @@
+-- Per-user watchlists (/api/me/watchlist) and the in-app notification inbox.
+-- A NULL STORE_ID watches the item in every store the user can access.
+CREATE TABLE ODBC_JHC.WATCHLIST_JHC (
+    WATCH_ID    NUMBER GENERATED ALWAYS AS IDENTITY,
+    USERNAME    VARCHAR2(50) NOT NULL,
+    ITEM_ID     VARCHAR2(50) NOT NULL,
+    STORE_ID    VARCHAR2(2),
+    ON_STOCKOUT NUMBER(1) DEFAULT 1 NOT NULL,
+    ON_PRICE    NUMBER(1) DEFAULT 1 NOT NULL,
+    CREATED_AT  DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT WATCHLIST_JHC_PK PRIMARY KEY (WATCH_ID),
+    CONSTRAINT WATCHLIST_JHC_UK UNIQUE (USERNAME, ITEM_ID, STORE_ID)
+);
+
+CREATE INDEX ODBC_JHC.WATCHLIST_JHC_ITEM ON ODBC_JHC.WATCHLIST_JHC (ITEM_ID);
+
+CREATE TABLE ODBC_JHC.NOTIFICATIONS_JHC (
+    NOTIFICATION_ID NUMBER GENERATED ALWAYS AS IDENTITY,
+    USERNAME        VARCHAR2(50) NOT NULL,
+    ITEM_ID         VARCHAR2(50) NOT NULL,
+    STORE_ID        VARCHAR2(2) NOT NULL,
+    KIND            VARCHAR2(20) NOT NULL,
+    MESSAGE         VARCHAR2(400) NOT NULL,
+    CREATED_AT      DATE DEFAULT SYSDATE NOT NULL,
+    READ_AT         DATE,
+    CONSTRAINT NOTIFICATIONS_JHC_PK PRIMARY KEY (NOTIFICATION_ID)
+);
+
+CREATE INDEX ODBC_JHC.NOTIFICATIONS_JHC_USER ON ODBC_JHC.NOTIFICATIONS_JHC (USERNAME, CREATED_AT);
@@ -0,0 +1,14 @@
+-- Several photos per product (/api/products/<item>/images).
+-- Each image is stored as <ITEM_ID>@<IMAGE_ID>.jpg, the primary one is also copied
+-- to <ITEM_ID>.jpg, which /api/images/<item> and the ERP forms read.
+CREATE TABLE ODBC_JHC.PRODUCT_IMAGES_JHC (
+    IMAGE_ID    NUMBER GENERATED ALWAYS AS IDENTITY,
+    ITEM_ID     VARCHAR2(50) NOT NULL,
+    POSITION    NUMBER DEFAULT 0 NOT NULL,
+    IS_PRIMARY  NUMBER(1) DEFAULT 0 NOT NULL,
+    UPLOADED_BY VARCHAR2(50),
+    UPLOADED_AT DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT PRODUCT_IMAGES_JHC_PK PRIMARY KEY (IMAGE_ID)
+);
+
+CREATE INDEX ODBC_JHC.PRODUCT_IMAGES_JHC_ITEM ON ODBC_JHC.PRODUCT_IMAGES_JHC (ITEM_ID, POSITION);
@@ -0,0 +1,17 @@
+-- Changes to product images with the user who made them. Replaced and deleted images
+-- are kept as <ITEM_ID>@<IMAGE_ID>@r<REVISION_ID>.jpg in the image store and can be
+-- restored until the retention period ends (IMAGE_REVISION_RETENTION_DAYS), after
+-- which the file is removed and HAS_FILE cleared. The rows themselves are kept.
+CREATE TABLE ODBC_JHC.IMAGE_REVISIONS_JHC (
+    REVISION_ID NUMBER GENERATED ALWAYS AS IDENTITY,
+    ITEM_ID     VARCHAR2(50) NOT NULL,
+    IMAGE_ID    NUMBER NOT NULL,
+    ACTION      VARCHAR2(20) NOT NULL,
+    HAS_FILE    NUMBER(1) DEFAULT 0 NOT NULL,
+    CHANGED_BY  VARCHAR2(50),
+    CHANGED_AT  DATE DEFAULT SYSDATE NOT NULL,
+    CONSTRAINT IMAGE_REVISIONS_JHC_PK PRIMARY KEY (REVISION_ID)
+);
+
+CREATE INDEX ODBC_JHC.IMAGE_REVISIONS_JHC_ITEM ON ODBC_JHC.IMAGE_REVISIONS_JHC (ITEM_ID);
+CREATE INDEX ODBC_JHC.IMAGE_REVISIONS_JHC_FILE ON ODBC_JHC.IMAGE_REVISIONS_JHC (HAS_FILE, CHANGED_AT);
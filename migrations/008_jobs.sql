@@ -0,0 +1,14 @@
+-- Long running work started through the API (/api/jobs/<id>), with its result as JSON.
+-- STATUS is RUNNING, DONE or FAILED.
+CREATE TABLE ODBC_JHC.JOBS_JHC (
+    JOB_ID      NUMBER GENERATED ALWAYS AS IDENTITY,
+    KIND        VARCHAR2(30) NOT NULL,
+    STATUS      VARCHAR2(20) NOT NULL,
+    CREATED_BY  VARCHAR2(50) NOT NULL,
+    CREATED_AT  DATE DEFAULT SYSDATE NOT NULL,
+    FINISHED_AT DATE,
+    RESULT      CLOB,
+    CONSTRAINT JOBS_JHC_PK PRIMARY KEY (JOB_ID)
+);
+
+CREATE INDEX ODBC_JHC.JOBS_JHC_USER ON ODBC_JHC.JOBS_JHC (CREATED_BY, CREATED_AT);
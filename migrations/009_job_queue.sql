@@ -0,0 +1,13 @@
+-- Jobs become a persistent work queue. A job is QUEUED until a worker claims it
+-- (RUNNING), then DONE or FAILED. Failed attempts go back to QUEUED with RUN_AFTER
+-- pushed out until JOB_MAX_ATTEMPTS is reached. Uploaded files wait in JOB_SPOOL_DIR
+-- as <JOB_ID>.input, PAYLOAD holds the rest of the request as JSON.
+ALTER TABLE ODBC_JHC.JOBS_JHC ADD (
+    PAYLOAD    CLOB,
+    ATTEMPTS   NUMBER DEFAULT 0 NOT NULL,
+    RUN_AFTER  DATE DEFAULT SYSDATE NOT NULL,
+    STARTED_AT DATE,
+    LAST_ERROR VARCHAR2(400)
+);
+
+CREATE INDEX ODBC_JHC.JOBS_JHC_QUEUE ON ODBC_JHC.JOBS_JHC (STATUS, RUN_AFTER);
@@ -10,6 +10,7 @@ use serde::{Deserialize, Serialize};
 use crate::functions::authentication::structs::LoginParams;
 use crate::functions::authentication::structs::User;
 
+use crate::server::request_guard::api_key::ApiKey;
 use crate::utils::sql::SQLManager;
 use crate::utils::structs::APIErrors;
 
@@ -170,6 +171,14 @@ pub fn validate_token(token: &str) -> bool {
     }
 }
 
+/// Username of the token's owner
+pub fn get_username(key: &ApiKey<'_>) -> Result<String, APIErrors> {
+    match decode_token_data(key.0) {
+        Some(data) => data.USER_ID.ok_or(APIErrors::InvalidToken),
+        None => Err(APIErrors::InvalidToken),
+    }
+}
+
 pub fn decode_token_data(token: &str) -> Option<User> {
     let secret: String = std::env::var("SECRET_KEY").expect("SECRET_KEY must be set.");
     let decoded_token = decode::<Claims>(
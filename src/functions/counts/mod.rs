@@ -0,0 +1,486 @@
+pub mod structs;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use oracle::pool::Pool;
+use oracle::sql_type::ToSql;
+use oracle::{Connection, Row};
+use rust_decimal::Decimal;
+
+use crate::functions::authentication::get_username;
+use crate::functions::counts::structs::{
+    AddCountLinesParams, CountLinesResult, CountListParams, CountSession, CreateCountParams,
+    RejectedCountLine, VarianceRow,
+};
+use crate::functions::products::barcode::{self, parse_barcodes};
+use crate::functions::products::structs::{Product, QTY_STORES};
+use crate::functions::products::{get_decimal, get_user_store_ids};
+use crate::functions::reports::query_report_products;
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::db::{db_error, in_transaction, NUMBER};
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+const OPEN: &str = "OPEN";
+const CLOSED: &str = "CLOSED";
+
+/// An item returned by `find_items_by_code`
+struct ScanCandidate {
+    item_id: String,
+    item_cat: Option<String>,
+    main_barcode: Option<String>,
+    barcodes: Vec<String>,
+}
+
+/// The item a scanned code belongs to. The main barcode wins over the item code,
+/// which wins over the other listed barcodes. Two items on the same level is ambiguous.
+/// Barcodes compare in all the forms given by `barcode::variants`.
+fn match_scan<'a>(code: &str, candidates: &'a [ScanCandidate]) -> Result<&'a ScanCandidate, &'static str> {
+    let variants = barcode::variants(code);
+    let levels: [&dyn Fn(&ScanCandidate) -> bool; 3] = [
+        &|c| barcode::is_main_barcode(&variants, c.main_barcode.as_deref()),
+        &|c| c.item_id == code,
+        &|c| c.barcodes.iter().any(|b| variants.contains(b)),
+    ];
+    for level in levels {
+        let matches: Vec<&ScanCandidate> = candidates.iter().filter(|c| level(c)).collect();
+        match matches.len() {
+            0 => continue,
+            1 => return Ok(matches[0]),
+            _ => return Err("AMBIGUOUS"),
+        }
+    }
+    Err("NOT_FOUND")
+}
+
+fn find_candidates(conn: &Connection, sql_manager: &SQLManager, code: &str) -> Result<Vec<ScanCandidate>, APIErrors> {
+    let mut sql = sql_manager.get_sql("find_items_by_code")?;
+    let mut binds: Vec<(&str, &dyn ToSql)> = vec![("code", &code)];
+    let variants = barcode::variants(code);
+    let (condition, barcode_binds) = barcode::sql_condition(&variants);
+    if !variants.is_empty() {
+        sql.push_str(&format!(" OR {}", condition));
+        for (bind_name, value) in &barcode_binds {
+            binds.push((bind_name.as_str(), value as &dyn ToSql));
+        }
+    }
+    let mut stmt = conn.statement(&sql).build().map_err(db_error)?;
+    let rows = stmt.query_named(&binds).map_err(db_error)?;
+    let mut candidates: Vec<ScanCandidate> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        let listed: Option<String> = row.get("BARCODE_LISTED").unwrap_or(None);
+        candidates.push(ScanCandidate {
+            item_id: row.get("ITEM_ID").map_err(db_error)?,
+            item_cat: row.get("ITEM_CAT").unwrap_or(None),
+            main_barcode: row.get("ITEM_MAIN_BARCODE").unwrap_or(None),
+            barcodes: parse_barcodes(listed.as_deref().unwrap_or("")),
+        });
+    }
+    Ok(candidates)
+}
+
+/// Item a code resolves to, matched the way count scans are, or `NOT_FOUND` / `AMBIGUOUS`
+pub(crate) fn resolve_item_code(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    code: &str,
+) -> Result<Result<String, &'static str>, APIErrors> {
+    let candidates = find_candidates(conn, sql_manager, code)?;
+    Ok(match_scan(code, &candidates).map(|candidate| candidate.item_id.clone()))
+}
+
+/// Counted against system quantities. Items in scope that weren't counted but have
+/// stock are included with a count of zero, as they are missing from the shelves.
+fn compute_variance(products: &[Product], totals: &HashMap<String, Decimal>, store_id: &str) -> Vec<VarianceRow> {
+    let mut rows: BTreeMap<String, VarianceRow> = BTreeMap::new();
+    for product in products {
+        let item_id = match &product.ITEM_ID {
+            Some(item_id) => item_id.clone(),
+            None => continue,
+        };
+        let system_qty = product.store_qty(store_id).unwrap_or(Decimal::ZERO);
+        let counted_qty = totals.get(&item_id).copied();
+        if counted_qty.is_none() && system_qty.is_zero() {
+            continue;
+        }
+        let counted_qty = counted_qty.unwrap_or(Decimal::ZERO);
+        rows.insert(
+            item_id.clone(),
+            VarianceRow {
+                ITEM_ID: item_id,
+                ITEM_DESC: product.ITEM_DESC.clone(),
+                SYSTEM_QTY: system_qty,
+                COUNTED_QTY: counted_qty,
+                VARIANCE: counted_qty - system_qty,
+            },
+        );
+    }
+    // Counted items no longer in JHC_INVDATA
+    for (item_id, counted_qty) in totals {
+        rows.entry(item_id.clone()).or_insert_with(|| VarianceRow {
+            ITEM_ID: item_id.clone(),
+            ITEM_DESC: None,
+            SYSTEM_QTY: Decimal::ZERO,
+            COUNTED_QTY: *counted_qty,
+            VARIANCE: *counted_qty,
+        });
+    }
+    rows.into_values().collect()
+}
+
+fn session_from_row(row: &Row) -> Result<CountSession, APIErrors> {
+    Ok(CountSession {
+        SESSION_ID: row.get("SESSION_ID").map_err(db_error)?,
+        STORE_ID: row.get("STORE_ID").map_err(db_error)?,
+        ITEM_CAT: row.get("ITEM_CAT").unwrap_or(None),
+        STATUS: row.get("STATUS").map_err(db_error)?,
+        CREATED_BY: row.get("CREATED_BY").map_err(db_error)?,
+        CREATED_AT: row.get("CREATED_AT").unwrap_or(None),
+        CLOSED_BY: row.get("CLOSED_BY").unwrap_or(None),
+        CLOSED_AT: row.get("CLOSED_AT").unwrap_or(None),
+        NOTE: row.get("NOTE").unwrap_or(None),
+    })
+}
+
+/// Session by id, locked until commit with `for_update`. The user needs access to its store.
+fn read_session(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    session_id: i64,
+    store_ids: &HashSet<String>,
+    for_update: bool,
+) -> Result<CountSession, APIErrors> {
+    let sql = if for_update {
+        sql_manager.get_sql("get_count_session_for_update")?
+    } else {
+        format!("{} AND SESSION_ID = :1", sql_manager.get_sql("get_count_sessions")?)
+    };
+    let row = match conn.query_row(&sql, &[&session_id]) {
+        Ok(row) => row,
+        Err(e) => {
+            info!("Count session {} not found: {}", session_id, e);
+            return Err(APIErrors::NoData);
+        }
+    };
+    let session = session_from_row(&row)?;
+    if !store_ids.contains(&session.STORE_ID) {
+        return Err(APIErrors::NoStoreAccess);
+    }
+    Ok(session)
+}
+
+fn get_count_totals(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    session_id: i64,
+) -> Result<HashMap<String, Decimal>, APIErrors> {
+    let rows = conn
+        .query(sql_manager.get_sql("get_count_totals")?.as_str(), &[&session_id])
+        .map_err(db_error)?;
+    let mut totals: HashMap<String, Decimal> = HashMap::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        let item_id: String = row.get("ITEM_ID").map_err(db_error)?;
+        totals.insert(item_id, get_decimal(&row, "QTY").unwrap_or(Decimal::ZERO));
+    }
+    Ok(totals)
+}
+
+/// Live variance of an open session, read on the caller's connection which may hold the session lock
+fn live_variance(
+    conn: &Connection,
+    session: &CountSession,
+    sql_manager: &SQLManager,
+) -> Result<Vec<VarianceRow>, APIErrors> {
+    let totals = get_count_totals(conn, sql_manager, session.SESSION_ID)?;
+    let store_ids: HashSet<String> = HashSet::from([session.STORE_ID.clone()]);
+    let filters = [("ITEM_CAT", session.ITEM_CAT.as_ref())];
+    let products = query_report_products(conn, &filters, &store_ids, false, sql_manager)?;
+    Ok(compute_variance(&products, &totals, &session.STORE_ID))
+}
+
+pub async fn create_count_session(
+    params: &CreateCountParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<CountSession, APIErrors> {
+    let username = get_username(key)?;
+    if !QTY_STORES.contains(&params.p_store.as_str()) {
+        error!("Unknown store: {}", params.p_store);
+        return Err(APIErrors::InvalidData);
+    }
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    if !store_ids.contains(&params.p_store) {
+        return Err(APIErrors::NoStoreAccess);
+    }
+
+    let conn = pool.get().map_err(db_error)?;
+    let session_id = in_transaction(&conn, || {
+        let session_id: i64 = conn
+            .query_row_as(sql_manager.get_sql("next_count_session_id")?.as_str(), &[])
+            .map_err(db_error)?;
+        conn.execute(
+            sql_manager.get_sql("insert_count_session")?.as_str(),
+            &[&session_id, &params.p_store, &params.p_category, &username, &params.p_note],
+        )
+        .map_err(db_error)?;
+        Ok(session_id)
+    })?;
+
+    info!("Count session {} opened by {}", session_id, username);
+    read_session(&conn, sql_manager, session_id, &store_ids, false)
+}
+
+/// Sessions of the user's stores, newest first
+pub async fn get_count_sessions(
+    params: &CountListParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Vec<CountSession>, APIErrors> {
+    let mut store_ids: Vec<String> = get_user_store_ids(key, pool, sql_manager).await?.into_iter().collect();
+    if let Some(store) = &params.store {
+        store_ids.retain(|store_id| store_id == store);
+    }
+    if store_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    store_ids.sort();
+    let status = params.status.as_ref().map(|s| s.to_uppercase());
+    if let Some(status) = &status {
+        if status != OPEN && status != CLOSED {
+            return Err(APIErrors::InvalidData);
+        }
+    }
+
+    let bind_names: Vec<String> = (0..store_ids.len()).map(|i| format!("store_{}", i)).collect();
+    let placeholders: Vec<String> = bind_names.iter().map(|name| format!(":{}", name)).collect();
+    let mut sql = sql_manager.get_sql("get_count_sessions")?;
+    sql.push_str(&format!(" AND STORE_ID IN ({})", placeholders.join(", ")));
+    let mut binds: Vec<(&str, &dyn ToSql)> = Vec::new();
+    for (name, store_id) in bind_names.iter().zip(store_ids.iter()) {
+        binds.push((name.as_str(), store_id as &dyn ToSql));
+    }
+    if let Some(status) = &status {
+        sql.push_str(" AND STATUS = :status");
+        binds.push(("status", status as &dyn ToSql));
+    }
+    sql.push_str(" ORDER BY SESSION_ID DESC FETCH FIRST 500 ROWS ONLY");
+
+    let conn = pool.get().map_err(db_error)?;
+    let mut stmt = conn.statement(&sql).build().map_err(db_error)?;
+    let rows = stmt.query_named(&binds).map_err(db_error)?;
+    let mut sessions: Vec<CountSession> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        sessions.push(session_from_row(&row)?);
+    }
+    Ok(sessions)
+}
+
+pub async fn get_count_session(
+    session_id: i64,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<CountSession, APIErrors> {
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    let conn = pool.get().map_err(db_error)?;
+    read_session(&conn, sql_manager, session_id, &store_ids, false)
+}
+
+/// Record scanned lines. Quantities add up when an item is scanned more than once.
+pub async fn add_count_lines(
+    session_id: i64,
+    params: &AddCountLinesParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<CountLinesResult, APIErrors> {
+    let username = get_username(key)?;
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+
+    let conn = pool.get().map_err(db_error)?;
+    in_transaction(&conn, || {
+        let session = read_session(&conn, sql_manager, session_id, &store_ids, true)?;
+        if session.STATUS != OPEN {
+            return Err(APIErrors::InvalidState);
+        }
+
+        let insert_line = sql_manager.get_sql("insert_count_line")?;
+        let mut result = CountLinesResult {
+            ACCEPTED: 0,
+            REJECTED: Vec::new(),
+        };
+        for line in params.p_lines.iter() {
+            let code = match (&line.p_barcode, &line.p_item_id) {
+                (Some(barcode), _) => barcode.trim().to_string(),
+                (None, Some(item_id)) => item_id.trim().to_string(),
+                (None, None) => return Err(APIErrors::InvalidData),
+            };
+            let mut reject = |reason: &str| {
+                result.REJECTED.push(RejectedCountLine {
+                    CODE: code.clone(),
+                    QTY: line.p_qty,
+                    REASON: reason.to_string(),
+                });
+            };
+            if line.p_qty < Decimal::ZERO || code.is_empty() {
+                reject("INVALID_QTY");
+                continue;
+            }
+
+            let candidates = find_candidates(&conn, sql_manager, &code)?;
+            let candidate = match match_scan(&code, &candidates) {
+                Ok(candidate) => candidate,
+                Err(reason) => {
+                    reject(reason);
+                    continue;
+                }
+            };
+            if session.ITEM_CAT.is_some() && candidate.item_cat != session.ITEM_CAT {
+                reject("OUT_OF_SCOPE");
+                continue;
+            }
+
+            conn.execute(
+                &insert_line,
+                &[&session_id, &candidate.item_id, &code, &(&line.p_qty.to_string(), &NUMBER), &username],
+            )
+            .map_err(db_error)?;
+            result.ACCEPTED += 1;
+        }
+        Ok(result)
+    })
+}
+
+/// Live variance while the session is open, the frozen one once it is closed
+pub async fn get_count_variance(
+    session_id: i64,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Vec<VarianceRow>, APIErrors> {
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    let conn = pool.get().map_err(db_error)?;
+    let session = read_session(&conn, sql_manager, session_id, &store_ids, false)?;
+    if session.STATUS == OPEN {
+        return live_variance(&conn, &session, sql_manager);
+    }
+
+    let rows = conn
+        .query(sql_manager.get_sql("get_count_results")?.as_str(), &[&session_id])
+        .map_err(db_error)?;
+    let mut variance: Vec<VarianceRow> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        let system_qty = get_decimal(&row, "SYSTEM_QTY").unwrap_or(Decimal::ZERO);
+        let counted_qty = get_decimal(&row, "COUNTED_QTY").unwrap_or(Decimal::ZERO);
+        variance.push(VarianceRow {
+            ITEM_ID: row.get("ITEM_ID").map_err(db_error)?,
+            ITEM_DESC: row.get("ITEM_DESC").unwrap_or(None),
+            SYSTEM_QTY: system_qty,
+            COUNTED_QTY: counted_qty,
+            VARIANCE: counted_qty - system_qty,
+        });
+    }
+    Ok(variance)
+}
+
+/// Close the session and freeze its variance against the current system quantities
+pub async fn close_count_session(
+    session_id: i64,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<CountSession, APIErrors> {
+    let username = get_username(key)?;
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+
+    let conn = pool.get().map_err(db_error)?;
+    in_transaction(&conn, || {
+        let session = read_session(&conn, sql_manager, session_id, &store_ids, true)?;
+        if session.STATUS != OPEN {
+            return Err(APIErrors::InvalidState);
+        }
+        let variance = live_variance(&conn, &session, sql_manager)?;
+        let insert_result = sql_manager.get_sql("insert_count_result")?;
+        for row in variance.iter() {
+            conn.execute(
+                &insert_result,
+                &[
+                    &session_id,
+                    &row.ITEM_ID,
+                    &row.ITEM_DESC,
+                    &(&row.SYSTEM_QTY.to_string(), &NUMBER),
+                    &(&row.COUNTED_QTY.to_string(), &NUMBER),
+                ],
+            )
+            .map_err(db_error)?;
+        }
+        conn.execute(
+            sql_manager.get_sql("close_count_session")?.as_str(),
+            &[&username, &session_id],
+        )
+        .map_err(db_error)?;
+        info!("Count session {} closed by {} with {} lines", session_id, username, variance.len());
+        Ok(())
+    })?;
+
+    read_session(&conn, sql_manager, session_id, &store_ids, false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn candidate(item_id: &str, main_barcode: &str, listed: &str) -> ScanCandidate {
+        ScanCandidate {
+            item_id: item_id.to_string(),
+            item_cat: None,
+            main_barcode: Some(main_barcode.to_string()),
+            barcodes: parse_barcodes(listed),
+        }
+    }
+
+    #[test]
+    fn test_match_scan() {
+        let candidates = vec![
+            candidate("100", "6251234567892", "6251234567892,012345678905"),
+            candidate("200", "012345678905", "012345678905"),
+            candidate("300", "4006381333931", "99887766,12345"),
+            candidate("400", "5012345678900", "99887766"),
+        ];
+        // Listed on item 100 too, but it's item 200's main barcode
+        assert_eq!(match_scan("012345678905", &candidates).unwrap().item_id, "200");
+        assert_eq!(match_scan("300", &candidates).unwrap().item_id, "300");
+        assert_eq!(match_scan("12345", &candidates).unwrap().item_id, "300");
+        // Substring of a listed barcode isn't a match
+        assert_eq!(match_scan("1234", &candidates).err(), Some("NOT_FOUND"));
+        assert_eq!(match_scan("99887766", &candidates).err(), Some("AMBIGUOUS"));
+    }
+
+    #[test]
+    fn test_compute_variance() {
+        let mut counted = Product::default();
+        counted.ITEM_ID = Some("1".to_string());
+        counted.QTY_STORE_05 = Some(dec("10"));
+        let mut missing = Product::default();
+        missing.ITEM_ID = Some("2".to_string());
+        missing.QTY_STORE_05 = Some(dec("3"));
+        let mut empty = Product::default();
+        empty.ITEM_ID = Some("3".to_string());
+
+        let totals = HashMap::from([("1".to_string(), dec("8")), ("9".to_string(), dec("1"))]);
+        let variance = compute_variance(&[counted, missing, empty], &totals, "05");
+        let items: Vec<(&str, Decimal)> = variance.iter().map(|r| (r.ITEM_ID.as_str(), r.VARIANCE)).collect();
+        assert_eq!(items, vec![("1", dec("-2")), ("2", dec("-3")), ("9", dec("1"))]);
+    }
+}
@@ -0,0 +1,84 @@
+#![allow(non_snake_case)]
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::export::CsvRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCountParams {
+    pub p_store: String,
+    pub p_category: Option<String>,
+    pub p_note: Option<String>,
+}
+
+/// A scanned line, identified by `p_barcode` or directly by `p_item_id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountLineParams {
+    pub p_barcode: Option<String>,
+    pub p_item_id: Option<String>,
+    pub p_qty: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddCountLinesParams {
+    pub p_lines: Vec<CountLineParams>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct CountListParams {
+    pub store: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountSession {
+    pub SESSION_ID: i64,
+    pub STORE_ID: String,
+    pub ITEM_CAT: Option<String>,
+    pub STATUS: String,
+    pub CREATED_BY: String,
+    pub CREATED_AT: Option<String>,
+    pub CLOSED_BY: Option<String>,
+    pub CLOSED_AT: Option<String>,
+    pub NOTE: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RejectedCountLine {
+    pub CODE: String,
+    pub QTY: Decimal,
+    /// NOT_FOUND, AMBIGUOUS, OUT_OF_SCOPE or INVALID_QTY
+    pub REASON: String,
+}
+
+/// Lines that couldn't be matched to an item of the session are returned, not stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountLinesResult {
+    pub ACCEPTED: u32,
+    pub REJECTED: Vec<RejectedCountLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VarianceRow {
+    pub ITEM_ID: String,
+    pub ITEM_DESC: Option<String>,
+    pub SYSTEM_QTY: Decimal,
+    pub COUNTED_QTY: Decimal,
+    pub VARIANCE: Decimal,
+}
+
+impl CsvRow for VarianceRow {
+    fn headers() -> &'static [&'static str] {
+        &["ITEM_ID", "ITEM_DESC", "SYSTEM_QTY", "COUNTED_QTY", "VARIANCE"]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.ITEM_ID.clone(),
+            self.ITEM_DESC.clone().unwrap_or_default(),
+            self.SYSTEM_QTY.to_string(),
+            self.COUNTED_QTY.to_string(),
+            self.VARIANCE.to_string(),
+        ]
+    }
+}
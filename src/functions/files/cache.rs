@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+
+use crate::functions::files::store::temp_name;
+use crate::server::responders::etag_for;
+use crate::utils::env::env_number;
+
+/// An image as served, with its validators
+#[derive(Debug, Clone)]
+pub struct CachedImage {
+    pub data: Vec<u8>,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+}
+
+struct Entry {
+    /// Item code the entry belongs to, all of an item's entries go on upload
+    item: String,
+    size: u64,
+    etag: String,
+    last_modified: DateTime<Utc>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, Entry>,
+    total: u64,
+    clock: u64,
+    /// Bumped on each `invalidate` of an item, items never invalidated are at 0
+    generations: HashMap<String, u64>,
+}
+
+/// Marks a directory as the cache's own, only such a directory is cleared
+const MARKER: &str = ".jhapi-image-cache";
+
+/// Entries (`<name>@<width>.<ext>`) and the temp files they are written through
+fn is_cache_file(name: &str) -> bool {
+    let entry = [".jpg", ".png", ".webp"].iter().any(|extension| name.ends_with(extension));
+    let temp = name.starts_with('.') && name.ends_with(".tmp");
+    name.contains('@') && (entry || temp)
+}
+
+/// Remove the entries of a previous run. A directory without the marker is only taken
+/// when it's new or empty, anything else is refused (false) and left alone.
+fn prepare(dir: &Path) -> std::io::Result<bool> {
+    std::fs::create_dir_all(dir)?;
+    if !dir.join(MARKER).exists() {
+        if std::fs::read_dir(dir)?.next().is_some() {
+            return Ok(false);
+        }
+        std::fs::write(dir.join(MARKER), b"")?;
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_entry = entry.file_name().to_str().map(is_cache_file).unwrap_or(false);
+        if is_entry && entry.file_type()?.is_file() {
+            std::fs::remove_file(entry.path()).ok();
+        }
+    }
+    Ok(true)
+}
+
+/// Images kept on local disk, least recently used ones evicted past `max_bytes`.
+/// The directory only holds this process' entries, they are removed at startup.
+/// Blocking, only use it from blocking threads.
+pub struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// `max-age` sent to clients, in seconds
+    pub max_age: u64,
+    index: Mutex<CacheIndex>,
+}
+
+impl ImageCache {
+    /// Caching is disabled when `dir` isn't a cache directory, see `prepare`
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64, max_age: u64) -> ImageCache {
+        let dir = dir.into();
+        let max_bytes = match max_bytes > 0 {
+            true => match prepare(&dir) {
+                Ok(true) => max_bytes,
+                Ok(false) => {
+                    error!("{:?} holds other files, image cache disabled. Use a new or empty IMAGE_CACHE_DIR", dir);
+                    0
+                }
+                Err(e) => {
+                    error!("Error preparing image cache {:?}: {}", dir, e);
+                    0
+                }
+            },
+            false => 0,
+        };
+        ImageCache {
+            dir,
+            max_bytes,
+            max_age,
+            index: Mutex::new(CacheIndex::default()),
+        }
+    }
+
+    /// `IMAGE_CACHE_DIR` (`cache/images`), `IMAGE_CACHE_MAX_MB` (256, 0 disables the cache)
+    /// and `IMAGE_CACHE_MAX_AGE_SECONDS` (3600)
+    pub fn from_env() -> ImageCache {
+        ImageCache::new(
+            std::env::var("IMAGE_CACHE_DIR").unwrap_or("cache/images".to_string()),
+            env_number("IMAGE_CACHE_MAX_MB", 256u64) * 1024 * 1024,
+            env_number("IMAGE_CACHE_MAX_AGE_SECONDS", 3600),
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedImage> {
+        let mut index = self.index.lock().unwrap();
+        index.clock += 1;
+        let clock = index.clock;
+        let entry = index.entries.get_mut(key)?;
+        entry.last_used = clock;
+        let (etag, last_modified) = (entry.etag.clone(), entry.last_modified);
+        drop(index);
+
+        match std::fs::read(self.dir.join(key)) {
+            Ok(data) => Some(CachedImage {
+                data,
+                etag,
+                last_modified,
+            }),
+            Err(e) => {
+                error!("Error reading cached image {}: {}", key, e);
+                self.remove(key);
+                None
+            }
+        }
+    }
+
+    /// Read before fetching an item's image, and handed back to `insert`
+    pub fn generation(&self, item: &str) -> u64 {
+        let index = self.index.lock().unwrap();
+        index.generations.get(item).copied().unwrap_or(0)
+    }
+
+    /// Cache `data` under `key` and evict down to the size cap. Data read before the
+    /// item was invalidated (an older `generation`) isn't kept.
+    /// The image is returned even when it couldn't be cached.
+    pub fn insert(&self, item: &str, key: &str, data: Vec<u8>, generation: u64) -> CachedImage {
+        let image = CachedImage {
+            etag: etag_for(&data),
+            last_modified: Utc::now(),
+            data,
+        };
+        let size = image.data.len() as u64;
+        if size > self.max_bytes || self.generation(item) != generation {
+            return image;
+        }
+        if let Err(e) = self.write(key, &image.data) {
+            error!("Error caching image {}: {}", key, e);
+            return image;
+        }
+
+        let mut index = self.index.lock().unwrap();
+        if index.generations.get(item).copied().unwrap_or(0) != generation {
+            // Invalidated while the file was written
+            drop(index);
+            std::fs::remove_file(self.dir.join(key)).ok();
+            return image;
+        }
+        index.clock += 1;
+        let entry = Entry {
+            item: item.to_string(),
+            size,
+            etag: image.etag.clone(),
+            last_modified: image.last_modified,
+            last_used: index.clock,
+        };
+        if let Some(previous) = index.entries.insert(key.to_string(), entry) {
+            index.total -= previous.size;
+        }
+        index.total += size;
+
+        let mut evicted: Vec<String> = Vec::new();
+        while index.total > self.max_bytes {
+            let oldest = index
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            let entry = index.entries.remove(&oldest).unwrap();
+            index.total -= entry.size;
+            evicted.push(oldest);
+        }
+        drop(index);
+        for key in evicted {
+            std::fs::remove_file(self.dir.join(key)).ok();
+        }
+        image
+    }
+
+    /// Drop every entry of an item, reads already under way won't be cached
+    pub fn invalidate(&self, item: &str) {
+        let keys: Vec<String> = {
+            let mut index = self.index.lock().unwrap();
+            *index.generations.entry(item.to_string()).or_insert(0) += 1;
+            index
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.item == item)
+                .map(|(key, _)| key.clone())
+                .collect()
+        };
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let mut index = self.index.lock().unwrap();
+        if let Some(entry) = index.entries.remove(key) {
+            index.total -= entry.size;
+            std::fs::remove_file(self.dir.join(key)).ok();
+        }
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temp = self.dir.join(temp_name(key));
+        std::fs::write(&temp, data)?;
+        std::fs::rename(&temp, self.dir.join(key)).inspect_err(|_| {
+            std::fs::remove_file(&temp).ok();
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_image_cache() {
+        let dir = std::env::temp_dir().join(format!("jhapi-cache-{}", std::process::id()));
+        let cache = ImageCache::new(&dir, 10, 60);
+        assert!(cache.get("1.jpg").is_none());
+
+        let inserted = cache.insert("1", "1.jpg", b"aaaa".to_vec(), 0);
+        assert_eq!(inserted.etag, etag_for(b"aaaa"));
+        cache.insert("2", "2.jpg", b"bbbb".to_vec(), 0);
+        // 1.jpg is now the most recently used, 2.jpg goes when 3.jpg doesn't fit
+        assert_eq!(cache.get("1.jpg").unwrap().data, b"aaaa");
+        cache.insert("3", "3.jpg", b"cccc".to_vec(), 0);
+        assert!(cache.get("2.jpg").is_none());
+        assert!(!dir.join("2.jpg").exists());
+        assert!(cache.get("3.jpg").is_some());
+
+        // Larger than the whole cache, served but not kept
+        assert_eq!(cache.insert("4", "4.jpg", vec![0; 11], 0).data.len(), 11);
+        assert!(cache.get("4.jpg").is_none());
+
+        cache.invalidate("1");
+        assert!(cache.get("1.jpg").is_none());
+        assert!(!dir.join("1.jpg").exists());
+        // Read before the invalidation, not cached
+        cache.insert("1", "1.jpg", b"aaaa".to_vec(), 0);
+        assert!(cache.get("1.jpg").is_none());
+        cache.insert("1", "1.jpg", b"dddd".to_vec(), cache.generation("1"));
+        assert_eq!(cache.get("1.jpg").unwrap().data, b"dddd");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cache_dir() {
+        let dir = std::env::temp_dir().join(format!("jhapi-cache-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("1001.jpg"), b"product image").unwrap();
+        // Not a cache directory, nothing is removed and nothing is cached
+        let cache = ImageCache::new(&dir, 100, 60);
+        cache.insert("1", "1@640.jpg", b"aaaa".to_vec(), 0);
+        assert!(cache.get("1@640.jpg").is_none());
+        assert!(dir.join("1001.jpg").exists());
+
+        std::fs::remove_file(dir.join("1001.jpg")).unwrap();
+        ImageCache::new(&dir, 100, 60).insert("1", "1@640.jpg", b"aaaa".to_vec(), 0);
+        std::fs::write(dir.join("notes.txt"), b"kept").unwrap();
+        // Entries of the previous run go, other files stay
+        ImageCache::new(&dir, 100, 60);
+        assert!(!dir.join("1@640.jpg").exists());
+        assert!(dir.join("notes.txt").exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
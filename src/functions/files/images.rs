@@ -0,0 +1,468 @@
+use std::sync::Arc;
+
+use oracle::pool::Pool;
+use oracle::sql_type::OracleType;
+use oracle::{Connection, Row};
+
+use crate::functions::files::cache::ImageCache;
+use crate::functions::files::store::{validate_item_code, ImageStore};
+use crate::functions::files::revisions::record_change;
+use crate::functions::files::structs::{ProductImage, RevisionAction};
+use crate::functions::files::{image_key, with_store};
+use crate::utils::db::{db_error, in_transaction};
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Store key of one of the item's images, `<item>.jpg` holds a copy of the primary one
+pub fn product_image_key(item_code: &str, image_id: i64) -> String {
+    format!("{}@{}.jpg", item_code, image_id)
+}
+
+fn image_from_row(row: &Row) -> Result<ProductImage, APIErrors> {
+    Ok(ProductImage {
+        IMAGE_ID: row.get("IMAGE_ID").map_err(db_error)?,
+        ITEM_ID: row.get("ITEM_ID").map_err(db_error)?,
+        POSITION: row.get("POSITION").map_err(db_error)?,
+        IS_PRIMARY: row.get::<_, i64>("IS_PRIMARY").map_err(db_error)? != 0,
+        UPLOADED_BY: row.get("UPLOADED_BY").unwrap_or(None),
+        UPLOADED_AT: row.get("UPLOADED_AT").unwrap_or(None),
+    })
+}
+
+pub(crate) fn read_images(conn: &Connection, item_code: &str, sql_manager: &SQLManager) -> Result<Vec<ProductImage>, APIErrors> {
+    let rows = conn
+        .query(sql_manager.get_sql("get_product_images")?.as_str(), &[&item_code])
+        .map_err(db_error)?;
+    let mut images: Vec<ProductImage> = Vec::new();
+    for row_result in rows {
+        images.push(image_from_row(&row_result.map_err(db_error)?)?);
+    }
+    Ok(images)
+}
+
+/// Images can only be added to items in `JHC_INVDATA`
+fn ensure_item_exists(conn: &Connection, sql_manager: &SQLManager, item_code: &str) -> Result<(), APIErrors> {
+    validate_item_code(item_code)?;
+    let count: i64 = conn
+        .query_row_as(sql_manager.get_sql("count_item")?.as_str(), &[&item_code])
+        .map_err(db_error)?;
+    if count == 0 {
+        return Err(APIErrors::NoData);
+    }
+    Ok(())
+}
+
+/// `ensure_item_exists` before work for the item is queued
+pub async fn check_item(item_code: &str, pool: &Pool, sql_manager: &SQLManager) -> Result<(), APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    ensure_item_exists(&conn, sql_manager, item_code)
+}
+
+/// Insert a row, left uncommitted
+pub(crate) fn insert_image(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    item_code: &str,
+    position: i64,
+    primary: bool,
+    username: Option<&str>,
+) -> Result<i64, APIErrors> {
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("insert_product_image")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    stmt.execute(&[&item_code, &position, &(primary as i32), &username, &OracleType::Number(0, 0)])
+        .map_err(db_error)?;
+    let ids: Vec<i64> = stmt.returned_values(5).map_err(db_error)?;
+    ids.first().copied().ok_or(APIErrors::DBError)
+}
+
+/// Number the images 0.. in the given order, left uncommitted
+fn write_positions(conn: &Connection, sql_manager: &SQLManager, item_code: &str, image_ids: &[i64]) -> Result<(), APIErrors> {
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("update_image_position")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    for (position, image_id) in image_ids.iter().enumerate() {
+        stmt.execute(&[&(position as i64), image_id, &item_code]).map_err(db_error)?;
+    }
+    Ok(())
+}
+
+/// Flag one image as primary and clear the others, left uncommitted
+fn write_primary(conn: &Connection, sql_manager: &SQLManager, item_code: &str, image_id: i64) -> Result<(), APIErrors> {
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("set_primary_image")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    stmt.execute_named(&[("image_id", &image_id), ("item_id", &item_code)])
+        .map_err(db_error)?;
+    Ok(())
+}
+
+/// `image_ids` with `image_id` moved to `index` (clamped)
+fn reordered(image_ids: &[i64], image_id: i64, index: usize) -> Vec<i64> {
+    let mut ordered: Vec<i64> = image_ids.iter().copied().filter(|id| *id != image_id).collect();
+    ordered.insert(index.min(ordered.len()), image_id);
+    ordered
+}
+
+/// Copy an image over `<item>.jpg`. Only once the change is committed, a rollback
+/// can't bring back the previous file.
+async fn publish_primary(store: &Arc<dyn ImageStore>, item_code: &str, image_id: i64) -> Result<(), APIErrors> {
+    let (source, target) = (product_image_key(item_code, image_id), image_key(item_code));
+    with_store(store, move |store| store.put(&target, &store.get(&source)?)).await
+}
+
+/// Remove files nothing points at anymore, after the commit. A failure only leaves
+/// an unused file behind.
+async fn remove_files(store: &Arc<dyn ImageStore>, keys: Vec<String>) {
+    let removed = with_store(store, move |store| {
+        for key in &keys {
+            store.delete(key)?;
+        }
+        Ok(())
+    })
+    .await;
+    if let Err(e) = removed {
+        error!("Error removing replaced images: {}", e);
+    }
+}
+
+/// `IMAGE_ID` a `<item>.jpg` older than the images table is listed with. The image
+/// routes accept it until a change to the item's images registers the file.
+pub const LEGACY_IMAGE_ID: i64 = 0;
+
+/// Lock the item so changes to its images run one at a time, then read them. An
+/// `<item>.jpg` older than the images table is registered as the primary image first.
+/// Left uncommitted, `NoData` when the item doesn't exist.
+pub(crate) async fn lock_images(
+    conn: &Connection,
+    item_code: &str,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+) -> Result<Vec<ProductImage>, APIErrors> {
+    validate_item_code(item_code)?;
+    let mut locked = conn
+        .query(sql_manager.get_sql("lock_item")?.as_str(), &[&item_code])
+        .map_err(db_error)?;
+    match locked.next() {
+        Some(row_result) => {
+            row_result.map_err(db_error)?;
+        }
+        None => return Err(APIErrors::NoData),
+    }
+    let images = read_images(conn, item_code, sql_manager)?;
+    if !images.is_empty() {
+        return Ok(images);
+    }
+    let legacy_key = image_key(item_code);
+    let data = match with_store(store, move |store| store.get(&legacy_key)).await {
+        Ok(data) => data,
+        Err(APIErrors::FileNotFound) => return Ok(images),
+        Err(e) => return Err(e),
+    };
+
+    info!("Registering existing image of {}", item_code);
+    let image_id = insert_image(conn, sql_manager, item_code, 0, true, None)?;
+    let key = product_image_key(item_code, image_id);
+    with_store(store, move |store| store.put(&key, &data)).await?;
+    read_images(conn, item_code, sql_manager)
+}
+
+/// `LEGACY_IMAGE_ID` finds the registered legacy image, the only one without an uploader
+fn find_image(images: &[ProductImage], image_id: i64) -> Option<&ProductImage> {
+    images.iter().find(|image| {
+        image.IMAGE_ID == image_id || (image_id == LEGACY_IMAGE_ID && image.IS_PRIMARY && image.UPLOADED_BY.is_none())
+    })
+}
+
+/// Images of the item. Nothing is written, a `<item>.jpg` older than the images table
+/// is listed as the primary image with `LEGACY_IMAGE_ID`.
+pub async fn list_images(
+    item_code: &str,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+) -> Result<Vec<ProductImage>, APIErrors> {
+    validate_item_code(item_code)?;
+    let conn = pool.get().map_err(db_error)?;
+    let images = read_images(&conn, item_code, sql_manager)?;
+    if !images.is_empty() {
+        return Ok(images);
+    }
+    let legacy_key = image_key(item_code);
+    if !with_store(store, move |store| store.exists(&legacy_key)).await? {
+        return Ok(images);
+    }
+    Ok(vec![ProductImage {
+        IMAGE_ID: LEGACY_IMAGE_ID,
+        ITEM_ID: item_code.to_string(),
+        POSITION: 0,
+        IS_PRIMARY: true,
+        UPLOADED_BY: None,
+        UPLOADED_AT: None,
+    }])
+}
+
+/// Insert a new image and store its file under its own key, left uncommitted.
+/// Nothing serves the key before the commit.
+#[allow(clippy::too_many_arguments)]
+async fn insert_new_image(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    item_code: &str,
+    images: &[ProductImage],
+    data: Vec<u8>,
+    index: usize,
+    primary: bool,
+    username: &str,
+) -> Result<i64, APIErrors> {
+    let image_id = insert_image(conn, sql_manager, item_code, images.len() as i64, false, Some(username))?;
+    let image_ids: Vec<i64> = images.iter().map(|image| image.IMAGE_ID).collect();
+    write_positions(conn, sql_manager, item_code, &reordered(&image_ids, image_id, index))?;
+    if primary {
+        write_primary(conn, sql_manager, item_code, image_id)?;
+    }
+    record_change(conn, sql_manager, store, item_code, image_id, RevisionAction::Upload, username, false).await?;
+    let key = product_image_key(item_code, image_id);
+    with_store(store, move |store| store.put(&key, &data)).await?;
+    Ok(image_id)
+}
+
+/// Store a new image at `position` (the end by default). The first image of an item
+/// is always primary. `NoData` when the item doesn't exist.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_image(
+    item_code: &str,
+    data: Vec<u8>,
+    position: Option<i64>,
+    primary: bool,
+    username: &str,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<ProductImage, APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    let result = async {
+        let images = lock_images(&conn, item_code, sql_manager, store).await?;
+        let primary = primary || images.is_empty();
+        let index = position.map(|p| p.max(0) as usize).unwrap_or(images.len());
+        let image_id =
+            insert_new_image(&conn, sql_manager, store, item_code, &images, data, index, primary, username).await?;
+        Ok((image_id, primary))
+    }
+    .await;
+    let (image_id, primary) = in_transaction(&conn, || result)?;
+    let published = match primary {
+        true => publish_primary(store, item_code, image_id).await,
+        false => Ok(()),
+    };
+    cache.invalidate(item_code);
+    published?;
+
+    read_images(&conn, item_code, sql_manager)?
+        .into_iter()
+        .find(|image| image.IMAGE_ID == image_id)
+        .ok_or(APIErrors::NoData)
+}
+
+/// Overwrite the primary image, or add a first one. Backs the single image `/api/upload` route.
+/// The previous file is kept as a revision.
+#[allow(clippy::too_many_arguments)]
+pub async fn replace_primary_image(
+    item_code: &str,
+    data: Vec<u8>,
+    username: &str,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<ProductImage, APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    let result = async {
+        let images = lock_images(&conn, item_code, sql_manager, store).await?;
+        let primary = match images.iter().find(|image| image.IS_PRIMARY) {
+            Some(primary) => primary.IMAGE_ID,
+            None => {
+                let image_id =
+                    insert_new_image(&conn, sql_manager, store, item_code, &images, data.clone(), 0, true, username)
+                        .await?;
+                return Ok((image_id, false));
+            }
+        };
+        record_change(&conn, sql_manager, store, item_code, primary, RevisionAction::Replace, username, true).await?;
+        conn.execute(sql_manager.get_sql("touch_product_image")?.as_str(), &[&username, &primary])
+            .map_err(db_error)?;
+        Ok((primary, true))
+    }
+    .await;
+    let (image_id, replaced) = in_transaction(&conn, || result)?;
+
+    // The previous file is safe in the revision, overwrite it now that the change is committed
+    let key = product_image_key(item_code, image_id);
+    let legacy_key = image_key(item_code);
+    let stored = with_store(store, move |store| {
+        if replaced {
+            store.put(&key, &data)?;
+        }
+        store.put(&legacy_key, &data)
+    })
+    .await;
+    cache.invalidate(item_code);
+    stored?;
+
+    read_images(&conn, item_code, sql_manager)?
+        .into_iter()
+        .find(|image| image.IMAGE_ID == image_id)
+        .ok_or(APIErrors::NoData)
+}
+
+/// `image_ids` must list every image of the item exactly once
+pub async fn reorder_images(
+    item_code: &str,
+    image_ids: &[i64],
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+) -> Result<Vec<ProductImage>, APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    let result = async {
+        let images = lock_images(&conn, item_code, sql_manager, store).await?;
+        // `LEGACY_IMAGE_ID` from a listing taken before the legacy image was registered
+        let ordered: Vec<i64> = image_ids
+            .iter()
+            .map(|image_id| find_image(&images, *image_id).map(|image| image.IMAGE_ID))
+            .collect::<Option<Vec<i64>>>()
+            .ok_or(APIErrors::InvalidData)?;
+        let mut current: Vec<i64> = images.iter().map(|image| image.IMAGE_ID).collect();
+        let mut requested: Vec<i64> = ordered.clone();
+        current.sort();
+        requested.sort();
+        if current != requested {
+            return Err(APIErrors::InvalidData);
+        }
+        write_positions(&conn, sql_manager, item_code, &ordered)
+    }
+    .await;
+    in_transaction(&conn, || result)?;
+    read_images(&conn, item_code, sql_manager)
+}
+
+pub async fn set_primary_image(
+    item_code: &str,
+    image_id: i64,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<Vec<ProductImage>, APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    let result = async {
+        let images = lock_images(&conn, item_code, sql_manager, store).await?;
+        let image_id = find_image(&images, image_id).ok_or(APIErrors::NoData)?.IMAGE_ID;
+        write_primary(&conn, sql_manager, item_code, image_id)?;
+        Ok(image_id)
+    }
+    .await;
+    let image_id = in_transaction(&conn, || result)?;
+    let published = publish_primary(store, item_code, image_id).await;
+    cache.invalidate(item_code);
+    published?;
+    read_images(&conn, item_code, sql_manager)
+}
+
+/// Remove an image, the primary one when `image_id` is `None`. Its file is kept as a
+/// revision. When it was the primary one the next image in order takes over,
+/// `<item>.jpg` goes with the last image.
+#[allow(clippy::too_many_arguments)]
+async fn remove_image(
+    item_code: &str,
+    image_id: Option<i64>,
+    username: &str,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<(), APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    let result = async {
+        let images = lock_images(&conn, item_code, sql_manager, store).await?;
+        let image = match image_id {
+            Some(image_id) => find_image(&images, image_id),
+            None => images.iter().find(|image| image.IS_PRIMARY),
+        }
+        .ok_or(APIErrors::NoData)?;
+        let next_primary = match image.IS_PRIMARY {
+            true => images.iter().find(|other| other.IMAGE_ID != image.IMAGE_ID).map(|other| other.IMAGE_ID),
+            false => None,
+        };
+
+        record_change(&conn, sql_manager, store, item_code, image.IMAGE_ID, RevisionAction::Delete, username, true).await?;
+        conn.execute(sql_manager.get_sql("delete_product_image")?.as_str(), &[&image.IMAGE_ID, &item_code])
+            .map_err(db_error)?;
+        if let Some(next_id) = next_primary {
+            write_primary(&conn, sql_manager, item_code, next_id)?;
+        }
+        Ok((image.IMAGE_ID, image.IS_PRIMARY, next_primary))
+    }
+    .await;
+    let (image_id, was_primary, next_primary) = in_transaction(&conn, || result)?;
+
+    // The file is kept by the revision, the store follows the committed rows
+    let published = match (was_primary, next_primary) {
+        (true, Some(next_id)) => publish_primary(store, item_code, next_id).await,
+        (true, None) => {
+            let legacy_key = image_key(item_code);
+            with_store(store, move |store| store.delete(&legacy_key)).await
+        }
+        (false, _) => Ok(()),
+    };
+    cache.invalidate(item_code);
+    published?;
+    remove_files(store, vec![product_image_key(item_code, image_id)]).await;
+    Ok(())
+}
+
+pub async fn delete_image(
+    item_code: &str,
+    image_id: i64,
+    username: &str,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<(), APIErrors> {
+    remove_image(item_code, Some(image_id), username, pool, sql_manager, store, cache).await
+}
+
+/// Remove the primary image, backs `DELETE /api/images/<item>`
+pub async fn delete_primary_image(
+    item_code: &str,
+    username: &str,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<(), APIErrors> {
+    remove_image(item_code, None, username, pool, sql_manager, store, cache).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reordered() {
+        assert_eq!(reordered(&[1, 2, 3], 4, 0), vec![4, 1, 2, 3]);
+        assert_eq!(reordered(&[1, 2, 3], 4, 10), vec![1, 2, 3, 4]);
+        assert_eq!(reordered(&[1, 2, 3], 3, 1), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_product_image_key() {
+        assert_eq!(product_image_key("123", 7), "123@7.jpg");
+    }
+}
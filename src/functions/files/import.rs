@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+
+use oracle::pool::Pool;
+
+use crate::functions::counts::resolve_item_code;
+use crate::functions::files::cache::ImageCache;
+use crate::functions::files::images::replace_primary_image;
+use crate::functions::files::resized;
+use crate::functions::files::store::ImageStore;
+use crate::functions::files::structs::{ImportReport, ImportedFile};
+use crate::functions::files::validation::UploadLimits;
+use crate::utils::db::db_error;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Job kind of a ZIP import
+pub const IMPORT_JOB: &str = "IMAGE_IMPORT";
+
+const STORED: &str = "STORED";
+const FAILED: &str = "FAILED";
+
+/// Archives with more entries are refused as a whole
+const MAX_ENTRIES: usize = 2000;
+/// Uncompressed size an entry may inflate to, larger ones are reported as TOO_LARGE
+const MAX_ENTRY_BYTES: u64 = 25 * 1024 * 1024;
+
+/// An entry read from the archive, resized or with the reason it can't be
+struct PreparedEntry {
+    file_name: String,
+    code: String,
+    image: Result<Vec<u8>, &'static str>,
+}
+
+/// Item code or barcode an entry is named after: the file name without folders or extension.
+/// `None` for folders and files left by archivers (`__MACOSX/`, `.DS_Store`, ...).
+fn entry_code(path: &str) -> Option<String> {
+    if path.ends_with('/') || path.starts_with("__MACOSX/") {
+        return None;
+    }
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    if file_name.starts_with('.') {
+        return None;
+    }
+    let stem = match file_name.rsplit_once('.') {
+        Some((stem, _)) => stem,
+        None => file_name,
+    };
+    Some(stem.trim().to_string())
+}
+
+fn read_entry(entry: &mut zip::read::ZipFile<'_>, limits: &UploadLimits) -> Result<Vec<u8>, &'static str> {
+    if entry.size() > MAX_ENTRY_BYTES {
+        return Err("TOO_LARGE");
+    }
+    // The declared size can't be trusted, stop reading past the cap
+    let mut data: Vec<u8> = Vec::new();
+    entry
+        .take(MAX_ENTRY_BYTES + 1)
+        .read_to_end(&mut data)
+        .map_err(|_| "CORRUPT")?;
+    if data.len() as u64 > MAX_ENTRY_BYTES {
+        return Err("TOO_LARGE");
+    }
+    limits.check(&data).map_err(|e| e.code())?;
+    resized(&data).map_err(|_| "CORRUPT")
+}
+
+/// Unpack, check and resize every image of the archive. Blocking.
+fn prepare_entries(archive: &[u8], limits: &UploadLimits) -> Result<Vec<PreparedEntry>, APIErrors> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive)).map_err(|e| {
+        error!("Error opening import archive: {}", e);
+        APIErrors::InvalidData
+    })?;
+    if archive.len() > MAX_ENTRIES {
+        error!("Import archive has {} entries", archive.len());
+        return Err(APIErrors::InvalidData);
+    }
+
+    let mut entries: Vec<PreparedEntry> = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = match archive.by_index(index) {
+            Ok(entry) => entry,
+            Err(e) => {
+                error!("Error reading import entry {}: {}", index, e);
+                entries.push(PreparedEntry {
+                    file_name: format!("#{}", index),
+                    code: String::new(),
+                    image: Err("CORRUPT"),
+                });
+                continue;
+            }
+        };
+        let file_name = entry.name().to_string();
+        let code = match entry_code(&file_name) {
+            Some(code) => code,
+            None => continue,
+        };
+        let image = match code.is_empty() {
+            true => Err("NOT_FOUND"),
+            false => read_entry(&mut entry, limits),
+        };
+        entries.push(PreparedEntry { file_name, code, image });
+    }
+    Ok(entries)
+}
+
+/// Store the images of a ZIP whose entries are named by item code or barcode, each
+/// replacing the primary image of its item. One bad entry doesn't stop the others,
+/// the report says what happened to each file.
+#[allow(clippy::too_many_arguments)]
+pub async fn import_images(
+    job_id: i64,
+    archive: Vec<u8>,
+    username: &str,
+    limits: &UploadLimits,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<ImportReport, APIErrors> {
+    let limits = limits.clone();
+    let entries = tokio::task::spawn_blocking(move || prepare_entries(&archive, &limits))
+        .await
+        .map_err(|e| {
+            error!("Import archive processing panicked: {:?}", e);
+            APIErrors::InternalServerError
+        })??;
+
+    let mut report = ImportReport {
+        JOB_ID: job_id,
+        STORED: 0,
+        FAILED: 0,
+        FILES: Vec::new(),
+    };
+    // Item codes are looked up first and the connection released before storing,
+    // `replace_primary_image` takes its own
+    let items: Vec<Result<String, &'static str>> = {
+        let conn = pool.get().map_err(db_error)?;
+        entries
+            .iter()
+            .map(|entry| match &entry.image {
+                Err(reason) => Err(*reason),
+                Ok(_) => match resolve_item_code(&conn, sql_manager, &entry.code) {
+                    Err(e) => {
+                        error!("Error looking up {}: {}", entry.file_name, e);
+                        Err("LOOKUP_ERROR")
+                    }
+                    Ok(item) => item,
+                },
+            })
+            .collect()
+    };
+
+    let mut imported: HashSet<String> = HashSet::new();
+    for (entry, item) in entries.into_iter().zip(items) {
+        let mut file = ImportedFile {
+            FILE_NAME: entry.file_name,
+            CODE: entry.code,
+            ITEM_ID: None,
+            STATUS: FAILED.to_string(),
+            REASON: None,
+        };
+        // Failures past this point are per entry, a failed import has stored nothing
+        let outcome = match (entry.image, item) {
+            (Err(reason), _) | (_, Err(reason)) => Err(reason),
+            (Ok(_), Ok(item_id)) if !imported.insert(item_id.clone()) => {
+                file.ITEM_ID = Some(item_id);
+                Err("DUPLICATE")
+            }
+            (Ok(data), Ok(item_id)) => {
+                let stored = replace_primary_image(&item_id, data, username, pool, sql_manager, store, cache).await;
+                file.ITEM_ID = Some(item_id);
+                stored.map(|_| ()).map_err(|e| {
+                    error!("Error importing {}: {}", file.FILE_NAME, e);
+                    "STORE_ERROR"
+                })
+            }
+        };
+        match outcome {
+            Ok(()) => {
+                file.STATUS = STORED.to_string();
+                report.STORED += 1;
+            }
+            Err(reason) => {
+                file.REASON = Some(reason.to_string());
+                report.FAILED += 1;
+            }
+        }
+        report.FILES.push(file);
+    }
+    info!("Image import {}: {} stored, {} failed", job_id, report.STORED, report.FAILED);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_entry_code() {
+        assert_eq!(entry_code("1001.jpg"), Some("1001".to_string()));
+        assert_eq!(entry_code("supplier/7501234567890.PNG"), Some("7501234567890".to_string()));
+        assert_eq!(entry_code("photos\\ 1002 .jpeg"), Some("1002".to_string()));
+        assert_eq!(entry_code("1003"), Some("1003".to_string()));
+        assert_eq!(entry_code("supplier/"), None);
+        assert_eq!(entry_code("__MACOSX/._1001.jpg"), None);
+        assert_eq!(entry_code("supplier/.DS_Store"), None);
+    }
+
+    #[test]
+    fn test_prepare_entries() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.add_directory("supplier/", options).unwrap();
+        writer.start_file("supplier/1001.jpg", options).unwrap();
+        writer.write_all(b"not an image").unwrap();
+        writer.start_file("__MACOSX/supplier/._1001.jpg", options).unwrap();
+        writer.write_all(b"metadata").unwrap();
+        writer.start_file(".jpg", options).unwrap();
+        writer.write_all(b"").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+
+        let limits = UploadLimits {
+            min_side: 100,
+            max_side: 10000,
+            max_pixels: 50_000_000,
+        };
+        let entries = prepare_entries(&archive, &limits).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file_name, "supplier/1001.jpg");
+        assert_eq!(entries[0].code, "1001");
+        assert_eq!(entries[0].image, Err("UNSUPPORTED_FORMAT"));
+
+        assert!(prepare_entries(b"not a zip", &limits).is_err());
+    }
+}
@@ -1,55 +1,68 @@
 use magick_rust::GravityType;
-use rocket::tokio::io::AsyncReadExt;
-use ssh2::Session;
-use std::io::prelude::*;
-use std::io::Read;
-use std::net::TcpStream;
-use std::path::Path;
-use tokio::fs::File;
+use oracle::pool::Pool;
+use std::sync::Arc;
 
 use magick_rust::{magick_wand_genesis, MagickWand, PixelWand};
 use std::sync::Once;
 
+use crate::utils::sql::SQLManager;
 use crate::utils::structs::APIErrors;
 
+pub mod cache;
+pub mod images;
+pub mod import;
+pub mod revisions;
+pub mod s3;
+pub mod sftp;
+pub mod store;
+pub mod structs;
+pub mod tasks;
+pub mod validation;
+pub mod variants;
+
+use cache::{CachedImage, ImageCache};
+use images::{product_image_key, replace_primary_image, LEGACY_IMAGE_ID};
+use store::{validate_item_code, ImageStore};
+use structs::ProductImage;
+use variants::{render_variant, ImageVariant};
+
 // Used to make sure MagickWand is initialized exactly once. Note that we
 // do not bother shutting down, we simply exit when we're done.
 static START: Once = Once::new();
 
-// Testing image resize function
-fn resize(filename: &str, name: &str) -> Result<bool, String> {
+pub(crate) fn init_magick() {
     START.call_once(|| {
         magick_wand_genesis();
     });
+}
 
-    let mut wand = MagickWand::new();
+// Image resize function, returns the 640x640 JPEG
+fn resize(data: &[u8]) -> Result<Vec<u8>, String> {
+    init_magick();
 
-    match wand.read_image(&filename) {
-        Ok(_) => (),
-        Err(e) => {
-            println!("Error reading image: {:?}", e);
-            return Err(format!("Error reading image: {:?}", e));
-        }
-    };
+    let wand = MagickWand::new();
+    wand.read_image_blob(data).map_err(|e| format!("Error reading image: {:?}", e))?;
 
     // Set background color
     let mut pixelwand = PixelWand::new();
-    pixelwand.set_color("white").unwrap();
-    wand.set_background_color(&pixelwand).unwrap();
-    wand.set_format("jpg").unwrap();
-
-    let temp = wand.write_image_blob("jpg");
-    if temp.is_err() {
-        println!("Error writing image blob");
-        return Err(format!("Error writing image blob: {}", temp.err().unwrap()));
-    }
-    let temp = temp.unwrap();
-
-    // Too many unwraps here, but magickwand is not very good at error handling
-    let mut magickwand = MagickWand::new();
-    magickwand.read_image_blob(&temp).unwrap();
-    magickwand.set_image_gravity(GravityType::Center).unwrap();
-    magickwand.set_gravity(GravityType::Center).unwrap();
+    pixelwand.set_color("white").map_err(|e| format!("Error setting background: {}", e))?;
+    wand.set_background_color(&pixelwand).map_err(|e| format!("Error setting background: {}", e))?;
+    wand.set_format("jpg").map_err(|e| format!("Error setting format: {}", e))?;
+
+    let temp = wand
+        .write_image_blob("jpg")
+        .map_err(|e| format!("Error writing image blob: {}", e))?;
+
+    let magickwand = MagickWand::new();
+    magickwand
+        .read_image_blob(&temp)
+        .map_err(|e| format!("Error reading image blob: {}", e))?;
+    magickwand
+        .set_image_gravity(GravityType::Center)
+        .map_err(|e| format!("Error setting gravity: {}", e))?;
+    magickwand
+        .set_gravity(GravityType::Center)
+        .map_err(|e| format!("Error setting gravity: {}", e))?;
     magickwand.fit(640, 640);
 
     let width = magickwand.get_image_width() as isize;
@@ -60,161 +73,100 @@ fn resize(filename: &str, name: &str) -> Result<bool, String> {
 
     magickwand
         .extend_image(640, 640, x_offset, y_offset)
-        .unwrap();
+        .map_err(|e| format!("Error extending image: {}", e))?;
 
-    let res_file = "tmp/".to_string() + name + ".jpg";
+    magickwand
+        .write_image_blob("jpg")
+        .map_err(|e| format!("Error writing image blob: {}", e))
+}
 
-    magickwand.write_image(&res_file).unwrap();
+/// `resize` for an upload that passed `UploadLimits::check`. ImageMagick failing on
+/// it means the file is damaged past its header.
+pub fn resized(data: &[u8]) -> Result<Vec<u8>, APIErrors> {
+    resize(data).map_err(|e| {
+        error!("Error resizing image: {:?}", e);
+        APIErrors::InvalidData
+    })
+}
 
-    Ok(true)
+/// Run a blocking store call off the async runtime threads
+pub(crate) async fn with_store<T: Send + 'static>(
+    store: &Arc<dyn ImageStore>,
+    call: impl FnOnce(&dyn ImageStore) -> Result<T, APIErrors> + Send + 'static,
+) -> Result<T, APIErrors> {
+    let store = store.clone();
+    tokio::task::spawn_blocking(move || call(store.as_ref()))
+        .await
+        .map_err(|e| {
+            error!("Image store call panicked: {:?}", e);
+            APIErrors::InternalServerError
+        })?
 }
 
-pub async fn download_file(file_name: &String) -> Result<(), APIErrors> {
-    // No caching, will always download file
-
-    // Delete temporary file
-    // first check if file exists
-    let file_path = Path::new("tmp/tmpdownload.jpg");
-    if file_path.exists() {
-        std::fs::remove_file("tmp/tmpdownload.jpg").unwrap();
-    }
-
-    // Connect to the local SSH server
-    let tcp_stream =
-        TcpStream::connect(std::env::var("SFTP_HOST").expect("SFTP_HOST must be set.")).unwrap();
-    let mut sess = Session::new().unwrap();
-    sess.set_tcp_stream(tcp_stream);
-    match sess.handshake() {
-        Ok(_) => println!("SFTP Handshake successful"),
-        Err(e) => {
-            error!("SFTP Handshake failed: {:?}", e);
-            return Err(APIErrors::SFTPError);
-        }
-    };
+/// Store key of a product's image
+pub fn image_key(item_code: &str) -> String {
+    format!("{}.jpg", item_code)
+}
 
-    // Error if username or password is not set, but should not panic
-    let username = std::env::var("SFTP_USERNAME");
-    let password = std::env::var("SFTP_PASSWORD");
-    if username.is_err() || password.is_err() {
-        println!("SFTP_USERNAME or SFTP_PASSWORD not set");
-        return Err(APIErrors::InvalidCredentials);
-    }
-    let username = username.unwrap();
-    let password = password.unwrap();
-
-    match sess.userauth_password(&username, &password) {
-        Ok(_) => println!("Authentication successful"),
-        Err(e) => {
-            error!("Authentication failed: {:?}", e);
-            return Err(APIErrors::InvalidCredentials);
-        }
+/// Product image from the local cache: the primary one (`<item>.jpg`) or a given image.
+/// On a miss the variant is rendered from the master, which comes from the cache or the store.
+pub async fn download_file(
+    store: &Arc<dyn ImageStore>,
+    cache: &Arc<ImageCache>,
+    item_code: &str,
+    image_id: Option<i64>,
+    variant: ImageVariant,
+) -> Result<CachedImage, APIErrors> {
+    validate_item_code(item_code)?;
+    let (key, name) = match image_id {
+        // A legacy image is the primary one, served from `<item>.jpg` before and after it's registered
+        None | Some(LEGACY_IMAGE_ID) => (image_key(item_code), item_code.to_string()),
+        Some(image_id) => (product_image_key(item_code, image_id), format!("{}@{}", item_code, image_id)),
     };
-
-    let filetarget = "/u02/forms/erp/images/".to_string() + file_name + ".jpg";
-
-    if let Ok((mut remote_file, stat)) = sess.scp_recv(Path::new(&filetarget)) {
-        println!("File exists");
-        println!("remote file size: {}", stat.size());
-        let mut contents = Vec::new();
-        match remote_file.read_to_end(&mut contents) {
-            Ok(_) => (),
-            Err(e) => {
-                error!("File read failed: {:?}", e);
-                return Err(APIErrors::SFTPError);
-            }
+    let item = item_code.to_string();
+    let cache = cache.clone();
+    with_store(store, move |store| {
+        // Taken before anything is read, an upload in between keeps the old image out of the cache
+        let generation = cache.generation(&item);
+        if let Some(image) = cache.get(&variant.cache_key(&name)) {
+            return Ok(image);
+        }
+        let master_key = ImageVariant::MASTER.cache_key(&name);
+        let master = match cache.get(&master_key) {
+            Some(master) => master,
+            None => cache.insert(&item, &master_key, store.get(&key)?, generation),
         };
-        // Close the channel and wait for the whole content to be tranferred
-        // Too many unwraps here, should be handled better
-        remote_file.send_eof().unwrap();
-        remote_file.wait_eof().unwrap();
-        remote_file.close().unwrap();
-        remote_file.wait_close().unwrap();
-
-        //Save file
-
-        let local_file = std::fs::File::create("tmp/tmpdownload.jpg");
-        if local_file.is_err() {
-            error!("File create failed: {:?}", local_file.err().unwrap());
-            return Err(APIErrors::InternalServerError);
+        if variant == ImageVariant::MASTER {
+            return Ok(master);
         }
-        let mut local_file = local_file.unwrap();
-        local_file.write_all(&contents).unwrap();
-        Ok(())
-    } else {
-        println!("File does not exist");
-        return Err(APIErrors::FileNotFound);
-    }
+        let data = render_variant(&master.data, variant)?;
+        Ok(cache.insert(&item, &variant.cache_key(&name), data, generation))
+    })
+    .await
 }
 
-pub async fn upload_file(item_code: &String, filepath: &String) -> Result<(), APIErrors> {
-    match resize(&filepath, &item_code) {
-        Ok(_) => (),
-        Err(e) => {
-            error!("Error resizing image: {:?}", e);
-            return Err(APIErrors::InternalServerError);
-        }
-    }
-    let temp_file = "tmp/".to_string() + item_code + ".jpg";
-
-    let mut f = File::open(&temp_file).await.unwrap();
-    let mut buffer = Vec::new();
-    let file_size = f.metadata().await.unwrap().len();
-    f.read_to_end(&mut buffer).await.ok();
-
-    let tcp =
-        TcpStream::connect(std::env::var("SFTP_HOST").expect("SFTP_HOST must be set.")).unwrap();
-    if let Ok(mut sess) = Session::new() {
-        println!("Session created");
-
-        sess.set_tcp_stream(tcp);
-        match sess.handshake() {
-            Ok(_) => println!("SFTP Handshake successful"),
-            Err(e) => {
-                error!("SFTP Handshake failed: {:?}", e);
-                return Err(APIErrors::SFTPError);
-            }
-        };
-
-        // Error if username or password is not set, but should not panic
-        let username = std::env::var("SFTP_USERNAME");
-        let password = std::env::var("SFTP_PASSWORD");
-        if username.is_err() || password.is_err() {
-            println!("SFTP_USERNAME or SFTP_PASSWORD not set");
-            return Err(APIErrors::InvalidCredentials);
-        }
-        let username = username.unwrap();
-        let password = password.unwrap();
-
-        match sess.userauth_password(&username, &password) {
-            Ok(_) => println!("SFTP Authentication successful"),
-            Err(e) => {
-                error!("SFTP Authentication failed: {:?}", e);
-                return Err(APIErrors::InvalidCredentials);
-            }
-        };
+/// Check if a product image exists without downloading it
+pub async fn image_exists(store: &Arc<dyn ImageStore>, item_code: &str) -> Result<bool, APIErrors> {
+    let key = image_key(item_code);
+    with_store(store, move |store| store.exists(&key)).await
+}
 
-        // Default static path for image files
-        let file_name = "/u02/forms/erp/images/".to_string() + item_code + ".jpg";
-
-        if let Ok(mut remote_file) = sess.scp_send(Path::new(&file_name), 0o644, file_size, None) {
-            println!("File Send successful");
-            println!("remote file size: {}", file_size);
-            remote_file.write_all(&buffer).unwrap();
-            // Close the channel and wait for the whole content to be tranferred
-            remote_file.send_eof().unwrap();
-            remote_file.wait_eof().unwrap();
-            remote_file.close().unwrap();
-            remote_file.wait_close().unwrap();
-            std::fs::remove_file(temp_file).unwrap();
-            return Ok(());
-        } else {
-            println!("File Send Unsuccessful");
-            std::fs::remove_file(temp_file).unwrap();
-            return Err(APIErrors::SFTPError);
-        }
-    } else {
-        println!("Session not created");
-        std::fs::remove_file(temp_file).unwrap();
-        return Err(APIErrors::SFTPError);
-    }
+/// Replace the item's primary image with the resized upload
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_file(
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    item_code: &str,
+    data: Vec<u8>,
+    username: &str,
+) -> Result<ProductImage, APIErrors> {
+    let buffer = tokio::task::spawn_blocking(move || resized(&data)).await.map_err(|e| {
+        error!("Image resize panicked: {:?}", e);
+        APIErrors::InternalServerError
+    })??;
+    let image = replace_primary_image(item_code, buffer, username, pool, sql_manager, store, cache).await?;
+    info!("Image {} stored", item_code);
+    Ok(image)
 }
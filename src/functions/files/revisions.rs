@@ -0,0 +1,233 @@
+use std::sync::Arc;
+
+use oracle::pool::Pool;
+use oracle::sql_type::OracleType;
+use oracle::{Connection, Row};
+
+use crate::functions::files::cache::ImageCache;
+use crate::functions::files::images::{insert_image, lock_images, product_image_key, read_images};
+use crate::functions::files::store::{validate_item_code, ImageStore};
+use crate::functions::files::structs::{ImageRevision, ProductImage, RevisionAction};
+use crate::functions::files::{image_key, with_store};
+use crate::utils::db::{db_error, in_transaction};
+use crate::utils::env::env_number;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Days replaced and deleted images stay restorable, `IMAGE_REVISION_RETENTION_DAYS` (30)
+pub fn retention_days() -> i64 {
+    env_number("IMAGE_REVISION_RETENTION_DAYS", 30i64).max(0)
+}
+
+/// Store key of the file kept by a revision
+pub fn revision_key(item_code: &str, image_id: i64, revision_id: i64) -> String {
+    format!("{}@{}@r{}.jpg", item_code, image_id, revision_id)
+}
+
+fn revision_from_row(row: &Row) -> Result<ImageRevision, APIErrors> {
+    Ok(ImageRevision {
+        REVISION_ID: row.get("REVISION_ID").map_err(db_error)?,
+        ITEM_ID: row.get("ITEM_ID").map_err(db_error)?,
+        IMAGE_ID: row.get("IMAGE_ID").map_err(db_error)?,
+        ACTION: row.get("ACTION").map_err(db_error)?,
+        RESTORABLE: row.get::<_, i64>("HAS_FILE").map_err(db_error)? != 0,
+        CHANGED_BY: row.get("CHANGED_BY").unwrap_or(None),
+        CHANGED_AT: row.get("CHANGED_AT").unwrap_or(None),
+        EXPIRES_AT: row.get("EXPIRES_AT").unwrap_or(None),
+    })
+}
+
+fn read_revisions(conn: &Connection, item_code: &str, sql_manager: &SQLManager) -> Result<Vec<ImageRevision>, APIErrors> {
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("get_image_revisions")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    let rows = stmt
+        .query_named(&[("item_id", &item_code), ("retention_days", &retention_days())])
+        .map_err(db_error)?;
+    let mut revisions: Vec<ImageRevision> = Vec::new();
+    for row_result in rows {
+        revisions.push(revision_from_row(&row_result.map_err(db_error)?)?);
+    }
+    Ok(revisions)
+}
+
+/// Record a change to an image, left uncommitted. With `archive` the image's current
+/// file is copied aside first so the change can be undone.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn record_change(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    item_code: &str,
+    image_id: i64,
+    action: RevisionAction,
+    username: &str,
+    archive: bool,
+) -> Result<i64, APIErrors> {
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("insert_image_revision")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    stmt.execute(&[
+        &item_code,
+        &image_id,
+        &action.as_str(),
+        &(archive as i32),
+        &username,
+        &OracleType::Number(0, 0),
+    ])
+    .map_err(db_error)?;
+    let ids: Vec<i64> = stmt.returned_values(6).map_err(db_error)?;
+    let revision_id = ids.first().copied().ok_or(APIErrors::DBError)?;
+    if !archive {
+        return Ok(revision_id);
+    }
+
+    let (source, target) = (product_image_key(item_code, image_id), revision_key(item_code, image_id, revision_id));
+    let archived = with_store(store, move |store| match store.get(&source) {
+        Ok(data) => store.put(&target, &data).map(|_| true),
+        Err(APIErrors::FileNotFound) => Ok(false),
+        Err(e) => Err(e),
+    })
+    .await?;
+    if !archived {
+        warn!("Image {} of {} has no file to keep", image_id, item_code);
+        conn.execute(sql_manager.get_sql("expire_image_revision")?.as_str(), &[&revision_id])
+            .map_err(db_error)?;
+    }
+    Ok(revision_id)
+}
+
+/// Changes to the item's images, newest first
+pub async fn list_revisions(item_code: &str, pool: &Pool, sql_manager: &SQLManager) -> Result<Vec<ImageRevision>, APIErrors> {
+    validate_item_code(item_code)?;
+    let conn = pool.get().map_err(db_error)?;
+    read_revisions(&conn, item_code, sql_manager)
+}
+
+/// Put a revision's file back. An image that still exists is overwritten (keeping its
+/// current file as a new revision), a deleted one is added back at the end.
+#[allow(clippy::too_many_arguments)]
+pub async fn restore_revision(
+    item_code: &str,
+    revision_id: i64,
+    username: &str,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    store: &Arc<dyn ImageStore>,
+    cache: &ImageCache,
+) -> Result<ProductImage, APIErrors> {
+    validate_item_code(item_code)?;
+    let conn = pool.get().map_err(db_error)?;
+    let revision = read_revisions(&conn, item_code, sql_manager)?
+        .into_iter()
+        .find(|revision| revision.REVISION_ID == revision_id)
+        .ok_or(APIErrors::NoData)?;
+    if !revision.RESTORABLE {
+        return Err(APIErrors::InvalidState);
+    }
+    let key = revision_key(item_code, revision.IMAGE_ID, revision_id);
+    let data = match with_store(store, move |store| store.get(&key)).await {
+        Err(APIErrors::FileNotFound) => return Err(APIErrors::InvalidState),
+        result => result?,
+    };
+
+    let result = async {
+        let images = lock_images(&conn, item_code, sql_manager, store).await?;
+        let existing = images.iter().find(|image| image.IMAGE_ID == revision.IMAGE_ID);
+        let (image_id, primary) = match existing {
+            Some(image) => {
+                record_change(&conn, sql_manager, store, item_code, image.IMAGE_ID, RevisionAction::Replace, username, true)
+                    .await?;
+                conn.execute(
+                    sql_manager.get_sql("touch_product_image")?.as_str(),
+                    &[&username, &image.IMAGE_ID],
+                )
+                .map_err(db_error)?;
+                (image.IMAGE_ID, image.IS_PRIMARY)
+            }
+            None => {
+                let primary = images.is_empty();
+                let image_id = insert_image(&conn, sql_manager, item_code, images.len() as i64, primary, Some(username))?;
+                (image_id, primary)
+            }
+        };
+        record_change(&conn, sql_manager, store, item_code, image_id, RevisionAction::Restore, username, false).await?;
+        if existing.is_none() {
+            // A new image gets a key of its own, nothing serves it before the commit
+            let key = product_image_key(item_code, image_id);
+            let data = data.clone();
+            with_store(store, move |store| store.put(&key, &data)).await?;
+        }
+        Ok((image_id, primary, existing.is_some()))
+    }
+    .await;
+    let (image_id, primary, overwrite) = in_transaction(&conn, || result)?;
+
+    // Overwriting the current file and `<item>.jpg` waits for the commit
+    let key = product_image_key(item_code, image_id);
+    let legacy_key = image_key(item_code);
+    let stored = with_store(store, move |store| {
+        if overwrite {
+            store.put(&key, &data)?;
+        }
+        if primary {
+            store.put(&legacy_key, &data)?;
+        }
+        Ok(())
+    })
+    .await;
+    cache.invalidate(item_code);
+    stored?;
+
+    read_images(&conn, item_code, sql_manager)?
+        .into_iter()
+        .find(|image| image.IMAGE_ID == image_id)
+        .ok_or(APIErrors::NoData)
+}
+
+/// Remove the files of revisions past the retention period, their history stays.
+/// Blocking, run from the scheduler.
+pub fn purge_expired_revisions(pool: &Pool, sql_manager: &SQLManager, store: &dyn ImageStore) -> Result<(), APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    let rows = conn
+        .query(sql_manager.get_sql("get_expired_image_revisions")?.as_str(), &[&retention_days()])
+        .map_err(db_error)?;
+    let mut expired: Vec<(i64, String, i64)> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        expired.push((
+            row.get("REVISION_ID").map_err(db_error)?,
+            row.get("ITEM_ID").map_err(db_error)?,
+            row.get("IMAGE_ID").map_err(db_error)?,
+        ));
+    }
+
+    // A file that can't be removed is tried again on the next run, the others still go
+    let expire_sql = sql_manager.get_sql("expire_image_revision")?;
+    let mut removed = 0;
+    for (revision_id, item_code, image_id) in &expired {
+        if let Err(e) = store.delete(&revision_key(item_code, *image_id, *revision_id)) {
+            error!("Error removing expired image revision {}: {}", revision_id, e);
+            continue;
+        }
+        conn.execute(expire_sql.as_str(), &[revision_id]).map_err(db_error)?;
+        conn.commit().map_err(db_error)?;
+        removed += 1;
+    }
+    if !expired.is_empty() {
+        info!("Removed {} of {} expired image revisions", removed, expired.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_revision_key() {
+        assert_eq!(revision_key("123", 7, 42), "123@7@r42.jpg");
+    }
+}
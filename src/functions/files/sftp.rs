@@ -0,0 +1,582 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use ssh2::{ErrorCode, RenameFlags, Session, Sftp};
+
+use crate::functions::files::store::{temp_name, validate_key, ImageStore};
+use crate::utils::env::{env_number, env_value};
+use crate::utils::structs::APIErrors;
+
+/// Opens and checks the connections held by a `SessionPool`
+pub trait Connector: Send + Sync {
+    type Connection: Send;
+    fn connect(&self) -> Result<Self::Connection, APIErrors>;
+    fn is_healthy(&self, connection: &Self::Connection) -> bool;
+}
+
+/// Connections idle for less than this are handed out without a health check,
+/// a failure on them is still caught by the retry in `SftpImageStore::run`
+const HEALTH_CHECK_AFTER: Duration = Duration::from_secs(10);
+
+struct Idle<T> {
+    connection: T,
+    since: Instant,
+}
+
+struct PoolState<T> {
+    idle: Vec<Idle<T>>,
+    /// Idle plus checked out
+    open: usize,
+}
+
+/// Bounded pool of connections. Blocking, only use it from blocking threads.
+pub struct SessionPool<C: Connector> {
+    connector: C,
+    max_size: usize,
+    idle_timeout: Duration,
+    wait_timeout: Duration,
+    health_check_after: Duration,
+    state: Mutex<PoolState<C::Connection>>,
+    released: Condvar,
+}
+
+/// A checked out connection, back in the pool when dropped unless discarded
+pub struct Pooled<'a, C: Connector> {
+    pool: &'a SessionPool<C>,
+    connection: Option<C::Connection>,
+}
+
+impl<C: Connector> Pooled<'_, C> {
+    pub fn connection(&self) -> &C::Connection {
+        self.connection.as_ref().unwrap()
+    }
+
+    /// Close the connection instead of returning it, after a connection level failure
+    pub fn discard(mut self) {
+        self.connection.take();
+        self.pool.release(None);
+    }
+}
+
+impl<C: Connector> Drop for Pooled<'_, C> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.release(Some(connection));
+        }
+    }
+}
+
+impl<C: Connector> SessionPool<C> {
+    pub fn new(connector: C, max_size: usize, idle_timeout: Duration, wait_timeout: Duration) -> SessionPool<C> {
+        SessionPool {
+            connector,
+            max_size: max_size.max(1),
+            idle_timeout,
+            wait_timeout,
+            health_check_after: HEALTH_CHECK_AFTER,
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                open: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Most recently used healthy connection, a new one while under `max_size`,
+    /// otherwise wait up to `wait_timeout` for one to be released
+    pub fn get(&self) -> Result<Pooled<'_, C>, APIErrors> {
+        let deadline = Instant::now() + self.wait_timeout;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let expired = self.take_expired(&mut state);
+            if let Some(idle) = state.idle.pop() {
+                drop(state);
+                drop(expired);
+                if idle.since.elapsed() < self.health_check_after || self.connector.is_healthy(&idle.connection) {
+                    return Ok(self.pooled(idle.connection));
+                }
+                info!("Dropping unhealthy pooled connection");
+                drop(idle);
+                state = self.state.lock().unwrap();
+                state.open -= 1;
+                continue;
+            }
+            if state.open < self.max_size {
+                state.open += 1;
+                drop(state);
+                drop(expired);
+                return match self.connector.connect() {
+                    Ok(connection) => Ok(self.pooled(connection)),
+                    Err(e) => {
+                        self.release(None);
+                        Err(e)
+                    }
+                };
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                error!("Timed out waiting for a pooled connection");
+                return Err(APIErrors::SFTPError);
+            }
+            state = self.released.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    fn pooled(&self, connection: C::Connection) -> Pooled<'_, C> {
+        Pooled {
+            pool: self,
+            connection: Some(connection),
+        }
+    }
+
+    /// Idle connections past `idle_timeout`, closed by the caller once the lock is released
+    fn take_expired(&self, state: &mut PoolState<C::Connection>) -> Vec<Idle<C::Connection>> {
+        let (expired, idle) = std::mem::take(&mut state.idle)
+            .into_iter()
+            .partition(|idle| idle.since.elapsed() >= self.idle_timeout);
+        state.idle = idle;
+        state.open -= expired.len();
+        expired
+    }
+
+    fn release(&self, connection: Option<C::Connection>) {
+        let mut state = self.state.lock().unwrap();
+        match connection {
+            Some(connection) => state.idle.push(Idle {
+                connection,
+                since: Instant::now(),
+            }),
+            None => state.open -= 1,
+        }
+        let expired = self.take_expired(&mut state);
+        drop(state);
+        drop(expired);
+        self.released.notify_one();
+    }
+}
+
+pub enum SftpAuth {
+    Password(String),
+    /// Private key file, with an optional public key file and passphrase
+    Key {
+        private_key: PathBuf,
+        public_key: Option<PathBuf>,
+        passphrase: Option<String>,
+    },
+}
+
+pub struct SftpConnector {
+    host: String,
+    username: String,
+    auth: SftpAuth,
+    timeout: Duration,
+}
+
+impl Connector for SftpConnector {
+    type Connection = Sftp;
+
+    fn connect(&self) -> Result<Sftp, APIErrors> {
+        let address = self
+            .host
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| {
+                error!("SFTP_HOST {} could not be resolved", self.host);
+                APIErrors::SFTPError
+            })?;
+        let tcp_stream = TcpStream::connect_timeout(&address, self.timeout).map_err(|e| {
+            error!("SFTP Connection failed: {:?}", e);
+            APIErrors::SFTPError
+        })?;
+        let mut sess = Session::new().map_err(|e| {
+            error!("SFTP Session failed: {:?}", e);
+            APIErrors::SFTPError
+        })?;
+        sess.set_tcp_stream(tcp_stream);
+        sess.set_timeout(self.timeout.as_millis() as u32);
+        sess.handshake().map_err(|e| {
+            error!("SFTP Handshake failed: {:?}", e);
+            APIErrors::SFTPError
+        })?;
+
+        let authenticated = match &self.auth {
+            SftpAuth::Password(password) => sess.userauth_password(&self.username, password),
+            SftpAuth::Key {
+                private_key,
+                public_key,
+                passphrase,
+            } => sess.userauth_pubkey_file(&self.username, public_key.as_deref(), private_key, passphrase.as_deref()),
+        };
+        authenticated.map_err(|e| {
+            error!("SFTP Authentication failed: {:?}", e);
+            APIErrors::InvalidCredentials
+        })?;
+
+        info!("SFTP session opened to {}", self.host);
+        sess.sftp().map_err(|e| {
+            error!("SFTP Subsystem failed: {:?}", e);
+            APIErrors::SFTPError
+        })
+    }
+
+    fn is_healthy(&self, sftp: &Sftp) -> bool {
+        sftp.realpath(Path::new(".")).is_ok()
+    }
+}
+
+/// How an operation on a pooled session failed
+enum SftpFailure {
+    Missing,
+    /// The file operation failed, the session is still usable
+    Operation(String),
+    /// The session is unusable and gets replaced
+    Connection(String),
+}
+
+impl From<ssh2::Error> for SftpFailure {
+    fn from(e: ssh2::Error) -> SftpFailure {
+        match e.code() {
+            // LIBSSH2_FX_NO_SUCH_FILE, LIBSSH2_FX_NO_SUCH_PATH
+            ErrorCode::SFTP(2) | ErrorCode::SFTP(10) => SftpFailure::Missing,
+            ErrorCode::SFTP(_) => SftpFailure::Operation(e.to_string()),
+            ErrorCode::Session(_) => SftpFailure::Connection(e.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for SftpFailure {
+    fn from(e: std::io::Error) -> SftpFailure {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => SftpFailure::Missing,
+            _ => SftpFailure::Connection(e.to_string()),
+        }
+    }
+}
+
+/// The file operations behind `put`
+trait RemoteFiles {
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), SftpFailure>;
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), SftpFailure>;
+    fn unlink(&self, path: &Path) -> Result<(), SftpFailure>;
+}
+
+impl RemoteFiles for Sftp {
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), SftpFailure> {
+        Ok(self.create(path)?.write_all(data)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), SftpFailure> {
+        Ok(Sftp::rename(self, from, to, Some(RenameFlags::OVERWRITE | RenameFlags::ATOMIC))?)
+    }
+
+    fn unlink(&self, path: &Path) -> Result<(), SftpFailure> {
+        Ok(Sftp::unlink(self, path)?)
+    }
+}
+
+/// Write `temp`, then move it to `path`. libssh2 speaks SFTP v3, which drops the rename
+/// flags, and OpenSSH refuses to rename onto an existing file: when the rename fails
+/// the existing file is removed and the rename tried again.
+fn write_replacing(files: &impl RemoteFiles, temp: &Path, path: &Path, data: &[u8]) -> Result<(), SftpFailure> {
+    let written = files.write(temp, data).and_then(|_| match files.rename(temp, path) {
+        Err(SftpFailure::Operation(e)) => match files.unlink(path) {
+            Ok(()) => files.rename(temp, path),
+            // Nothing was in the way, the rename failed for another reason
+            Err(SftpFailure::Missing) => Err(SftpFailure::Operation(e)),
+            Err(unlink_error) => Err(unlink_error),
+        },
+        renamed => renamed,
+    });
+    if written.is_err() {
+        files.unlink(temp).ok();
+    }
+    written
+}
+
+/// Images on the ERP server, reached over pooled SFTP sessions
+pub struct SftpImageStore {
+    pool: Option<SessionPool<SftpConnector>>,
+    root: PathBuf,
+}
+
+impl SftpImageStore {
+    /// `SFTP_HOST`, `SFTP_USERNAME` and either `SFTP_PRIVATE_KEY` (with optional `SFTP_PUBLIC_KEY`
+    /// and `SFTP_KEY_PASSPHRASE`) or `SFTP_PASSWORD`. Images are under `SFTP_IMAGE_DIR`.
+    /// `SFTP_POOL_SIZE` (4) sessions at most, closed after `SFTP_IDLE_TIMEOUT_SECONDS` (300) unused,
+    /// `SFTP_TIMEOUT_SECONDS` (30) for connecting, waiting for a session and each operation.
+    /// Missing settings are reported when an image is requested, not at startup.
+    pub fn from_env() -> SftpImageStore {
+        let auth = match (env_value("SFTP_PRIVATE_KEY"), env_value("SFTP_PASSWORD")) {
+            (Some(private_key), _) => Some(SftpAuth::Key {
+                private_key: PathBuf::from(private_key),
+                public_key: env_value("SFTP_PUBLIC_KEY").map(PathBuf::from),
+                passphrase: env_value("SFTP_KEY_PASSPHRASE"),
+            }),
+            (None, Some(password)) => Some(SftpAuth::Password(password)),
+            (None, None) => None,
+        };
+        let timeout = Duration::from_secs(env_number("SFTP_TIMEOUT_SECONDS", 30u64));
+        let pool = match (env_value("SFTP_HOST"), env_value("SFTP_USERNAME"), auth) {
+            (Some(host), Some(username), Some(auth)) => Some(SessionPool::new(
+                SftpConnector {
+                    host,
+                    username,
+                    auth,
+                    timeout,
+                },
+                env_number("SFTP_POOL_SIZE", 4usize),
+                Duration::from_secs(env_number("SFTP_IDLE_TIMEOUT_SECONDS", 300u64)),
+                timeout,
+            )),
+            _ => None,
+        };
+        SftpImageStore {
+            pool,
+            root: PathBuf::from(std::env::var("SFTP_IMAGE_DIR").unwrap_or("/u02/forms/erp/images/".to_string())),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    /// Run `operation` on a pooled session. A connection failure discards the session
+    /// and the operation is tried once more on a fresh one.
+    fn run<T>(&self, operation: impl Fn(&Sftp) -> Result<T, SftpFailure>) -> Result<T, APIErrors> {
+        let pool = self.pool.as_ref().ok_or_else(|| {
+            error!("SFTP_HOST, SFTP_USERNAME and SFTP_PASSWORD or SFTP_PRIVATE_KEY must be set");
+            APIErrors::InvalidCredentials
+        })?;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let session = pool.get()?;
+            match operation(session.connection()) {
+                Ok(value) => return Ok(value),
+                Err(SftpFailure::Missing) => return Err(APIErrors::FileNotFound),
+                // Refused by the server (permissions, disk, ...), `SFTPError` is kept for
+                // sessions that can't be opened or kept
+                Err(SftpFailure::Operation(e)) => {
+                    error!("SFTP Error: {}", e);
+                    return Err(APIErrors::InternalServerError);
+                }
+                Err(SftpFailure::Connection(e)) => {
+                    session.discard();
+                    if attempts > 1 {
+                        error!("SFTP Connection Error: {}", e);
+                        return Err(APIErrors::SFTPError);
+                    }
+                    info!("SFTP session failed ({}), reconnecting", e);
+                }
+            }
+        }
+    }
+}
+
+impl ImageStore for SftpImageStore {
+    fn name(&self) -> &'static str {
+        "sftp"
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, APIErrors> {
+        validate_key(key)?;
+        let path = self.path(key);
+        self.run(|sftp| {
+            let mut contents = Vec::new();
+            sftp.open(&path)?.read_to_end(&mut contents)?;
+            Ok(contents)
+        })
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), APIErrors> {
+        validate_key(key)?;
+        let path = self.path(key);
+        // Written under a temporary name so a half-written image is never served
+        let temp = self.path(&temp_name(key));
+        self.run(|sftp| write_replacing(sftp, &temp, &path, data))
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, APIErrors> {
+        validate_key(key)?;
+        let path = self.path(key);
+        match self.run(|sftp| Ok(sftp.stat(&path)?)) {
+            Ok(_) => Ok(true),
+            Err(APIErrors::FileNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<(), APIErrors> {
+        validate_key(key)?;
+        let path = self.path(key);
+        match self.run(|sftp| Ok(sftp.unlink(&path)?)) {
+            Err(APIErrors::FileNotFound) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct TestConnector {
+        opened: AtomicUsize,
+        healthy: AtomicBool,
+    }
+
+    impl Connector for TestConnector {
+        type Connection = usize;
+
+        fn connect(&self) -> Result<usize, APIErrors> {
+            Ok(self.opened.fetch_add(1, Ordering::SeqCst))
+        }
+
+        fn is_healthy(&self, _connection: &usize) -> bool {
+            self.healthy.load(Ordering::SeqCst)
+        }
+    }
+
+    impl<C: Connector> SessionPool<C> {
+        /// (open, idle)
+        fn size(&self) -> (usize, usize) {
+            let state = self.state.lock().unwrap();
+            (state.open, state.idle.len())
+        }
+    }
+
+    fn pool(max_size: usize, idle_timeout: Duration) -> SessionPool<TestConnector> {
+        let connector = TestConnector {
+            opened: AtomicUsize::new(0),
+            healthy: AtomicBool::new(true),
+        };
+        SessionPool::new(connector, max_size, idle_timeout, Duration::from_millis(50))
+    }
+
+    #[test]
+    fn test_pool_reuse_and_bound() {
+        let pool = pool(2, Duration::from_secs(60));
+        {
+            let first = pool.get().unwrap();
+            let second = pool.get().unwrap();
+            assert_eq!((*first.connection(), *second.connection()), (0, 1));
+            // Both sessions checked out, the third caller times out
+            assert!(matches!(pool.get(), Err(APIErrors::SFTPError)));
+        }
+        assert_eq!(pool.size(), (2, 2));
+        // Most recently returned first, `second` is dropped before `first`
+        assert_eq!(*pool.get().unwrap().connection(), 0);
+        assert_eq!(pool.connector.opened.load(Ordering::SeqCst), 2);
+
+        // A discarded session frees its slot and is replaced
+        pool.get().unwrap().discard();
+        assert_eq!(pool.size(), (1, 1));
+    }
+
+    #[test]
+    fn test_pool_idle_timeout() {
+        let pool = pool(2, Duration::ZERO);
+        drop(pool.get().unwrap());
+        assert_eq!(pool.size(), (0, 0));
+        assert_eq!(*pool.get().unwrap().connection(), 1);
+    }
+
+    #[test]
+    fn test_pool_health_check() {
+        let mut pool = pool(2, Duration::from_secs(60));
+        pool.health_check_after = Duration::ZERO;
+        drop(pool.get().unwrap());
+        pool.connector.healthy.store(false, Ordering::SeqCst);
+        // The idle session fails its check and is replaced
+        assert_eq!(*pool.get().unwrap().connection(), 1);
+        assert_eq!(pool.size(), (1, 1));
+    }
+
+    #[test]
+    fn test_pool_wakes_waiter() {
+        let pool = std::sync::Arc::new(SessionPool::new(
+            TestConnector {
+                opened: AtomicUsize::new(0),
+                healthy: AtomicBool::new(true),
+            },
+            1,
+            Duration::from_secs(60),
+            Duration::from_secs(5),
+        ));
+        let session = pool.get().unwrap();
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || *pool.get().unwrap().connection())
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        drop(session);
+        assert_eq!(waiter.join().unwrap(), 0);
+    }
+
+    /// An SFTP v3 server as OpenSSH implements it, renaming onto an existing file fails
+    #[derive(Default)]
+    struct TestFiles {
+        files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    }
+
+    impl RemoteFiles for TestFiles {
+        fn write(&self, path: &Path, data: &[u8]) -> Result<(), SftpFailure> {
+            self.files.lock().unwrap().insert(path.to_path_buf(), data.to_vec());
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<(), SftpFailure> {
+            let mut files = self.files.lock().unwrap();
+            if files.contains_key(to) {
+                return Err(SftpFailure::Operation("failure".to_string()));
+            }
+            let data = files.remove(from).ok_or(SftpFailure::Missing)?;
+            files.insert(to.to_path_buf(), data);
+            Ok(())
+        }
+
+        fn unlink(&self, path: &Path) -> Result<(), SftpFailure> {
+            self.files.lock().unwrap().remove(path).map(|_| ()).ok_or(SftpFailure::Missing)
+        }
+    }
+
+    #[test]
+    fn test_put_replaces_existing_file() {
+        let files = TestFiles::default();
+        let path = Path::new("/images/ABC.jpg");
+        for data in [b"first".as_slice(), b"second".as_slice()] {
+            let temp = PathBuf::from("/images").join(temp_name("ABC.jpg"));
+            assert!(write_replacing(&files, &temp, path, data).is_ok());
+        }
+        let files = files.files.lock().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files.get(path).map(Vec::as_slice), Some(b"second".as_slice()));
+    }
+
+    #[test]
+    fn test_put_failure_removes_temp() {
+        struct RefusingFiles(TestFiles);
+        impl RemoteFiles for RefusingFiles {
+            fn write(&self, path: &Path, data: &[u8]) -> Result<(), SftpFailure> {
+                self.0.write(path, data)
+            }
+            fn rename(&self, _from: &Path, _to: &Path) -> Result<(), SftpFailure> {
+                Err(SftpFailure::Operation("permission denied".to_string()))
+            }
+            fn unlink(&self, path: &Path) -> Result<(), SftpFailure> {
+                self.0.unlink(path)
+            }
+        }
+        let files = RefusingFiles(TestFiles::default());
+        let written = write_replacing(&files, Path::new("/images/.tmp"), Path::new("/images/ABC.jpg"), b"data");
+        assert!(matches!(written, Err(SftpFailure::Operation(_))));
+        assert!(files.0.files.lock().unwrap().is_empty());
+    }
+}
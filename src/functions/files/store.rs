@@ -0,0 +1,165 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::functions::files::s3::S3ImageStore;
+use crate::functions::files::sftp::SftpImageStore;
+use crate::utils::structs::APIErrors;
+
+/// Where product images live. Calls are blocking, async callers go through `spawn_blocking`.
+pub trait ImageStore: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Object bytes, `APIErrors::FileNotFound` when the key doesn't exist
+    fn get(&self, key: &str) -> Result<Vec<u8>, APIErrors>;
+    /// Create or replace the object
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), APIErrors>;
+    fn exists(&self, key: &str) -> Result<bool, APIErrors>;
+    /// Deleting a missing key is not an error
+    fn delete(&self, key: &str) -> Result<(), APIErrors>;
+}
+
+fn is_flat_name(name: &str, extra: &[char]) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' || extra.contains(&c))
+}
+
+/// Keys are flat file names, anything that could leave the store's root is rejected
+pub fn validate_key(key: &str) -> Result<(), APIErrors> {
+    if is_flat_name(key, &['@']) {
+        Ok(())
+    } else {
+        Err(APIErrors::InvalidData)
+    }
+}
+
+/// Item codes become part of keys, `@` is kept for the image id in `<item>@<image_id>.jpg`
+pub fn validate_item_code(item_code: &str) -> Result<(), APIErrors> {
+    if is_flat_name(item_code, &[]) {
+        Ok(())
+    } else {
+        Err(APIErrors::InvalidData)
+    }
+}
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Hidden name next to the target, so a half-written image is never served
+pub fn temp_name(key: &str) -> String {
+    format!(
+        ".{}.{}.{}.tmp",
+        key,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Images in a directory on the API server, for development and tests
+pub struct LocalImageStore {
+    root: PathBuf,
+}
+
+impl LocalImageStore {
+    pub fn new(root: impl Into<PathBuf>) -> LocalImageStore {
+        LocalImageStore { root: root.into() }
+    }
+
+    fn io_error(e: std::io::Error) -> APIErrors {
+        error!("Image Store Error: {}", e);
+        APIErrors::InternalServerError
+    }
+}
+
+impl ImageStore for LocalImageStore {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, APIErrors> {
+        validate_key(key)?;
+        match std::fs::read(self.root.join(key)) {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(APIErrors::FileNotFound),
+            Err(e) => Err(Self::io_error(e)),
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), APIErrors> {
+        validate_key(key)?;
+        std::fs::create_dir_all(&self.root).map_err(Self::io_error)?;
+        let temp = self.root.join(temp_name(key));
+        std::fs::write(&temp, data).map_err(Self::io_error)?;
+        std::fs::rename(&temp, self.root.join(key)).map_err(|e| {
+            std::fs::remove_file(&temp).ok();
+            Self::io_error(e)
+        })
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, APIErrors> {
+        validate_key(key)?;
+        Ok(self.root.join(key).is_file())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), APIErrors> {
+        validate_key(key)?;
+        match std::fs::remove_file(self.root.join(key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(Self::io_error(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Backend picked by `IMAGE_STORE`: `sftp` (default), `local` (`IMAGE_STORE_PATH`, `images` by default) or `s3`
+pub fn image_store_from_env() -> Arc<dyn ImageStore> {
+    let name = std::env::var("IMAGE_STORE").unwrap_or("sftp".to_string());
+    let store: Arc<dyn ImageStore> = match name.trim().to_lowercase().as_str() {
+        "sftp" => Arc::new(SftpImageStore::from_env()),
+        "local" => Arc::new(LocalImageStore::new(
+            std::env::var("IMAGE_STORE_PATH").unwrap_or("images".to_string()),
+        )),
+        "s3" => Arc::new(
+            S3ImageStore::from_env().expect("S3_ENDPOINT, S3_BUCKET, S3_ACCESS_KEY and S3_SECRET_KEY must be set."),
+        ),
+        other => panic!("Unknown IMAGE_STORE: {}", other),
+    };
+    info!("Image store: {}", store.name());
+    store
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("12345.jpg").is_ok());
+        assert!(validate_key("AB-12_3.jpg").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("../secret").is_err());
+        assert!(validate_key("a/b.jpg").is_err());
+        assert!(validate_key(".hidden").is_err());
+        assert!(validate_key("123@4.jpg").is_ok());
+        assert!(validate_item_code("123@4").is_err());
+        assert!(validate_item_code("AB-12").is_ok());
+    }
+
+    #[test]
+    fn test_local_store() {
+        let root = std::env::temp_dir().join(format!("jhapi-store-{}", std::process::id()));
+        let store = LocalImageStore::new(&root);
+        assert!(matches!(store.get("1.jpg"), Err(APIErrors::FileNotFound)));
+        assert!(!store.exists("1.jpg").unwrap());
+        store.put("1.jpg", b"first").unwrap();
+        store.put("1.jpg", b"second").unwrap();
+        assert!(store.exists("1.jpg").unwrap());
+        assert_eq!(store.get("1.jpg").unwrap(), b"second");
+        store.delete("1.jpg").unwrap();
+        store.delete("1.jpg").unwrap();
+        assert!(!store.exists("1.jpg").unwrap());
+        // No temp files left behind
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
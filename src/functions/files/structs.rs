@@ -0,0 +1,74 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProductImage {
+    pub IMAGE_ID: i64,
+    pub ITEM_ID: String,
+    pub POSITION: i64,
+    pub IS_PRIMARY: bool,
+    pub UPLOADED_BY: Option<String>,
+    pub UPLOADED_AT: Option<String>,
+}
+
+/// Every image of the item, in display order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageOrder {
+    pub p_image_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RevisionAction {
+    Upload,
+    Replace,
+    Delete,
+    Restore,
+}
+
+impl RevisionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionAction::Upload => "UPLOAD",
+            RevisionAction::Replace => "REPLACE",
+            RevisionAction::Delete => "DELETE",
+            RevisionAction::Restore => "RESTORE",
+        }
+    }
+}
+
+/// A change to one of the item's images. Replaced and deleted images can be restored
+/// until `EXPIRES_AT`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageRevision {
+    pub REVISION_ID: i64,
+    pub ITEM_ID: String,
+    pub IMAGE_ID: i64,
+    pub ACTION: String,
+    pub RESTORABLE: bool,
+    pub CHANGED_BY: Option<String>,
+    pub CHANGED_AT: Option<String>,
+    pub EXPIRES_AT: Option<String>,
+}
+
+/// Outcome of one entry of an import archive
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImportedFile {
+    pub FILE_NAME: String,
+    /// Item code or barcode taken from the file name
+    pub CODE: String,
+    pub ITEM_ID: Option<String>,
+    /// STORED or FAILED
+    pub STATUS: String,
+    /// EMPTY, UNSUPPORTED_FORMAT, CORRUPT, TOO_SMALL, TOO_LARGE, NOT_FOUND, AMBIGUOUS,
+    /// DUPLICATE, LOOKUP_ERROR or STORE_ERROR
+    pub REASON: Option<String>,
+}
+
+/// Kept as the result of the import job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub JOB_ID: i64,
+    pub STORED: u32,
+    pub FAILED: u32,
+    pub FILES: Vec<ImportedFile>,
+}
@@ -0,0 +1,91 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+
+use crate::functions::files::images::add_image;
+use crate::functions::files::import::{import_images, IMPORT_JOB};
+use crate::functions::files::{resized, upload_file};
+use crate::functions::jobs::queue::JobContext;
+use crate::functions::jobs::structs::Job;
+use crate::utils::structs::APIErrors;
+
+/// Replace an item's primary image, `/api/upload`
+pub const UPLOAD_JOB: &str = "IMAGE_UPLOAD";
+/// Add one more image to an item, `POST /api/products/<item>/images`
+pub const ADD_JOB: &str = "IMAGE_ADD";
+
+/// Payload of upload and add jobs, the image itself is the job's input file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageJobParams {
+    pub ITEM_ID: String,
+    #[serde(default)]
+    pub POSITION: Option<i64>,
+    #[serde(default)]
+    pub PRIMARY: bool,
+}
+
+fn to_value<T: Serialize>(value: Result<T, APIErrors>) -> Result<serde_json::Value, APIErrors> {
+    serde_json::to_value(value?).map_err(|_| APIErrors::InternalServerError)
+}
+
+/// Whether a job that failed on an unreachable database or store can run again. An
+/// import stores entries as it goes, running it twice would replace them twice.
+pub fn can_retry(kind: &str) -> bool {
+    kind != IMPORT_JOB
+}
+
+/// Run an image job, the returned value is kept as the job's result
+pub async fn run_image_job(job: &Job, input: Vec<u8>, context: &JobContext) -> Result<serde_json::Value, APIErrors> {
+    let params = || serde_json::from_value::<ImageJobParams>(job.PAYLOAD.clone()).map_err(|_| APIErrors::InvalidData);
+    match job.KIND.as_str() {
+        UPLOAD_JOB => to_value(
+            upload_file(
+                &context.image_store,
+                &context.image_cache,
+                &context.pool,
+                &context.sql_manager,
+                &params()?.ITEM_ID,
+                input,
+                &job.CREATED_BY,
+            )
+            .await,
+        ),
+        ADD_JOB => {
+            let params = params()?;
+            let data = tokio::task::spawn_blocking(move || resized(&input)).await.map_err(|e| {
+                error!("Image resize panicked: {:?}", e);
+                APIErrors::InternalServerError
+            })??;
+            to_value(
+                add_image(
+                    &params.ITEM_ID,
+                    data,
+                    params.POSITION,
+                    params.PRIMARY,
+                    &job.CREATED_BY,
+                    &context.pool,
+                    &context.sql_manager,
+                    &context.image_store,
+                    &context.image_cache,
+                )
+                .await,
+            )
+        }
+        IMPORT_JOB => to_value(
+            import_images(
+                job.JOB_ID,
+                input,
+                &job.CREATED_BY,
+                &context.upload_limits,
+                &context.pool,
+                &context.sql_manager,
+                &context.image_store,
+                &context.image_cache,
+            )
+            .await,
+        ),
+        kind => {
+            error!("Unknown job kind {}", kind);
+            Err(APIErrors::InvalidData)
+        }
+    }
+}
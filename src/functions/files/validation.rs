@@ -0,0 +1,222 @@
+use std::fmt;
+
+use crate::functions::files::variants::ImageFormat;
+use crate::utils::env::env_number;
+
+/// Why an upload was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum UploadError {
+    /// Empty upload
+    Empty,
+    /// Content isn't JPEG, PNG or WebP, whatever the file name says
+    UnsupportedFormat,
+    /// Recognised format but no dimensions could be read from the header
+    Corrupt,
+    TooSmall { width: u32, height: u32 },
+    /// Larger than the side or pixel limit, this is what stops decompression bombs
+    TooLarge { width: u32, height: u32 },
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UploadError::Empty => write!(f, "Empty file"),
+            UploadError::UnsupportedFormat => write!(f, "Unsupported image format, use JPEG, PNG or WebP"),
+            UploadError::Corrupt => write!(f, "Unreadable image"),
+            UploadError::TooSmall { width, height } => write!(f, "Image too small ({}x{})", width, height),
+            UploadError::TooLarge { width, height } => write!(f, "Image too large ({}x{})", width, height),
+        }
+    }
+}
+
+impl UploadError {
+    /// Reason code used in import reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            UploadError::Empty => "EMPTY",
+            UploadError::UnsupportedFormat => "UNSUPPORTED_FORMAT",
+            UploadError::Corrupt => "CORRUPT",
+            UploadError::TooSmall { .. } => "TOO_SMALL",
+            UploadError::TooLarge { .. } => "TOO_LARGE",
+        }
+    }
+}
+
+/// Dimensions accepted for uploads
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    pub min_side: u32,
+    pub max_side: u32,
+    pub max_pixels: u64,
+}
+
+impl UploadLimits {
+    /// `IMAGE_MIN_SIDE` (100), `IMAGE_MAX_SIDE` (10000) and `IMAGE_MAX_MEGAPIXELS` (50)
+    pub fn from_env() -> UploadLimits {
+        UploadLimits {
+            min_side: env_number("IMAGE_MIN_SIDE", 100),
+            max_side: env_number("IMAGE_MAX_SIDE", 10000),
+            max_pixels: env_number("IMAGE_MAX_MEGAPIXELS", 50u64) * 1_000_000,
+        }
+    }
+
+    /// Check an upload before it's handed to ImageMagick. Only the header is read,
+    /// so an oversized image is refused without being decoded.
+    pub fn check(&self, data: &[u8]) -> Result<ImageFormat, UploadError> {
+        if data.is_empty() {
+            return Err(UploadError::Empty);
+        }
+        let format = sniff_format(data).ok_or(UploadError::UnsupportedFormat)?;
+        let (width, height) = dimensions(format, data).ok_or(UploadError::Corrupt)?;
+        if width.max(height) > self.max_side || width as u64 * height as u64 > self.max_pixels {
+            return Err(UploadError::TooLarge { width, height });
+        }
+        if width.min(height) < self.min_side {
+            return Err(UploadError::TooSmall { width, height });
+        }
+        Ok(format)
+    }
+}
+
+/// Format from the magic bytes
+pub fn sniff_format(data: &[u8]) -> Option<ImageFormat> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some(ImageFormat::Webp)
+    } else {
+        None
+    }
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16)
+}
+
+/// Width and height from the image header
+fn dimensions(format: ImageFormat, data: &[u8]) -> Option<(u32, u32)> {
+    match format {
+        ImageFormat::Png => {
+            // IHDR is always the first chunk
+            if data.get(12..16)? != b"IHDR" {
+                return None;
+            }
+            Some((be32(data, 16)?, be32(data, 20)?))
+        }
+        ImageFormat::Jpeg => {
+            let mut at = 2;
+            loop {
+                if *data.get(at)? != 0xFF {
+                    return None;
+                }
+                let marker = *data.get(at + 1)?;
+                match marker {
+                    // Fill byte
+                    0xFF => at += 1,
+                    // Markers without a length
+                    0x01 | 0xD0..=0xD7 => at += 2,
+                    // Start of frame, except DHT, JPG and DAC which share the range
+                    0xC0..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                        return Some((be16(data, at + 7)?, be16(data, at + 5)?));
+                    }
+                    // Image data or end of image before any frame
+                    0xDA | 0xD9 => return None,
+                    _ => at += 2 + be16(data, at + 2)? as usize,
+                }
+            }
+        }
+        ImageFormat::Webp => match data.get(12..16)? {
+            b"VP8 " => {
+                if data.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                    return None;
+                }
+                let width = u16::from_le_bytes(data.get(26..28)?.try_into().ok()?) & 0x3FFF;
+                let height = u16::from_le_bytes(data.get(28..30)?.try_into().ok()?) & 0x3FFF;
+                Some((width as u32, height as u32))
+            }
+            b"VP8L" => {
+                if *data.get(20)? != 0x2F {
+                    return None;
+                }
+                let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+            }
+            b"VP8X" => Some((le24(data, 24)? + 1, le24(data, 27)? + 1)),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 2, 0, 0, 0]);
+        data
+    }
+
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        // SOI, an APP0 segment, then a baseline SOF0
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46];
+        data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[0x03; 10]);
+        data
+    }
+
+    fn webp_lossless(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2F".to_vec();
+        let bits = (width - 1) | (height - 1) << 14;
+        data.extend_from_slice(&bits.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_dimensions() {
+        assert_eq!(dimensions(ImageFormat::Png, &png(800, 600)), Some((800, 600)));
+        assert_eq!(dimensions(ImageFormat::Jpeg, &jpeg(1024, 768)), Some((1024, 768)));
+        assert_eq!(dimensions(ImageFormat::Webp, &webp_lossless(300, 200)), Some((300, 200)));
+        // Cut off before the frame header
+        assert_eq!(dimensions(ImageFormat::Jpeg, &jpeg(1024, 768)[..12]), None);
+    }
+
+    #[test]
+    fn test_check() {
+        let limits = UploadLimits {
+            min_side: 100,
+            max_side: 10000,
+            max_pixels: 50_000_000,
+        };
+        assert_eq!(limits.check(&png(800, 600)), Ok(ImageFormat::Png));
+        assert_eq!(limits.check(&[]), Err(UploadError::Empty));
+        assert_eq!(limits.check(b"GIF89a..."), Err(UploadError::UnsupportedFormat));
+        assert_eq!(limits.check(b"<svg></svg>"), Err(UploadError::UnsupportedFormat));
+        assert_eq!(limits.check(&png(800, 600)[..20]), Err(UploadError::Corrupt));
+        assert_eq!(limits.check(&jpeg(50, 600)), Err(UploadError::TooSmall { width: 50, height: 600 }));
+        assert_eq!(
+            limits.check(&jpeg(20000, 100)),
+            Err(UploadError::TooLarge { width: 20000, height: 100 })
+        );
+        // Small file, huge canvas
+        assert_eq!(
+            limits.check(&png(9000, 9000)),
+            Err(UploadError::TooLarge { width: 9000, height: 9000 })
+        );
+    }
+}
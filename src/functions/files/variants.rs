@@ -0,0 +1,147 @@
+use magick_rust::MagickWand;
+use rocket::http::ContentType;
+
+use crate::functions::files::init_magick;
+use crate::utils::structs::APIErrors;
+
+/// Width of the stored master, variants are never larger
+pub const MASTER_WIDTH: u32 = 640;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn parse(value: &str) -> Option<ImageFormat> {
+        match value.to_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            "png" => Some(ImageFormat::Png),
+            "webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            ImageFormat::Jpeg => ContentType::JPEG,
+            ImageFormat::Png => ContentType::PNG,
+            ImageFormat::Webp => ContentType::WEBP,
+        }
+    }
+}
+
+/// Size and format of a served image, the master is 640px JPEG
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageVariant {
+    pub width: u32,
+    pub format: ImageFormat,
+}
+
+impl ImageVariant {
+    pub const MASTER: ImageVariant = ImageVariant {
+        width: MASTER_WIDTH,
+        format: ImageFormat::Jpeg,
+    };
+
+    /// Cache key of the variant of `<item>` (the primary image) or `<item>@<image_id>`.
+    /// `@` can't appear in an item code, so keys of different images never collide.
+    pub fn cache_key(&self, name: &str) -> String {
+        format!("{}@{}.{}", name, self.width, self.format.extension())
+    }
+}
+
+/// Widths clients may ask for, anything else is refused so variants can't be used to fill the cache
+pub struct ImageVariants {
+    widths: Vec<u32>,
+}
+
+impl ImageVariants {
+    pub fn new(mut widths: Vec<u32>) -> ImageVariants {
+        widths.retain(|width| *width > 0 && *width < MASTER_WIDTH);
+        widths.push(MASTER_WIDTH);
+        widths.sort();
+        widths.dedup();
+        ImageVariants { widths }
+    }
+
+    /// `IMAGE_VARIANT_WIDTHS`, comma separated, 64, 128, 256 and 320 by default.
+    /// The 640px master is always allowed.
+    pub fn from_env() -> ImageVariants {
+        let widths = std::env::var("IMAGE_VARIANT_WIDTHS").unwrap_or("64,128,256,320".to_string());
+        ImageVariants::new(widths.split(',').filter_map(|width| width.trim().parse::<u32>().ok()).collect())
+    }
+
+    /// `w` and `fmt` query parameters, the master when both are missing
+    pub fn variant(&self, width: Option<u32>, format: Option<&str>) -> Result<ImageVariant, APIErrors> {
+        let width = width.unwrap_or(MASTER_WIDTH);
+        if !self.widths.contains(&width) {
+            return Err(APIErrors::InvalidData);
+        }
+        let format = match format {
+            Some(format) => ImageFormat::parse(format).ok_or(APIErrors::InvalidData)?,
+            None => ImageFormat::Jpeg,
+        };
+        Ok(ImageVariant { width, format })
+    }
+}
+
+/// Scale the master down and convert it
+pub fn render_variant(master: &[u8], variant: ImageVariant) -> Result<Vec<u8>, APIErrors> {
+    init_magick();
+    let magick_error = |e| {
+        error!("Error rendering image variant {:?}: {}", variant, e);
+        APIErrors::InternalServerError
+    };
+    let wand = MagickWand::new();
+    wand.read_image_blob(master).map_err(magick_error)?;
+    wand.strip_image().map_err(magick_error)?;
+    if variant.width < MASTER_WIDTH {
+        wand.fit(variant.width as usize, variant.width as usize);
+    }
+    if variant.format != ImageFormat::Png {
+        wand.set_image_compression_quality(85).map_err(magick_error)?;
+    }
+    wand.write_image_blob(variant.format.extension()).map_err(magick_error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_variant() {
+        let variants = ImageVariants::new(vec![128, 0, 2000, 64, 128]);
+        assert_eq!(variants.widths, vec![64, 128, 640]);
+        assert_eq!(variants.variant(None, None).unwrap(), ImageVariant::MASTER);
+        assert_eq!(
+            variants.variant(Some(128), Some("WebP")).unwrap(),
+            ImageVariant {
+                width: 128,
+                format: ImageFormat::Webp
+            }
+        );
+        assert!(variants.variant(Some(100), None).is_err());
+        assert!(variants.variant(None, Some("gif")).is_err());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(ImageVariant::MASTER.cache_key("123"), "123@640.jpg");
+        let thumbnail = ImageVariant {
+            width: 128,
+            format: ImageFormat::Webp,
+        };
+        assert_eq!(thumbnail.cache_key("123"), "123@128.webp");
+    }
+}
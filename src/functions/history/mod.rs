@@ -0,0 +1,338 @@
+pub mod structs;
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Duration, Local, NaiveDate};
+use oracle::pool::Pool;
+use oracle::sql_type::ToSql;
+use oracle::Connection;
+
+use crate::functions::history::structs::{Change, ChangeFeedParams, StoreState, PRICE_FIELDS, QTY_FIELD};
+use crate::functions::products::structs::{Product, QTY_STORES};
+use crate::functions::products::{get_decimal, get_user_store_ids};
+use crate::functions::reports::get_report_products;
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::db::{db_error, in_transaction, NUMBER};
+use crate::utils::env::env_number;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// (ITEM_ID, STORE_ID)
+type StateKey = (String, String);
+
+/// Settings of the snapshot job, read from the environment
+pub struct ChangeTracker {
+    pub enabled: bool,
+    pub refresh_minutes: u64,
+}
+
+impl ChangeTracker {
+    pub fn from_env() -> ChangeTracker {
+        let enabled = std::env::var("HISTORY_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let refresh_minutes = env_number("HISTORY_REFRESH_MINUTES", 60);
+        ChangeTracker {
+            enabled,
+            refresh_minutes,
+        }
+    }
+}
+
+fn current_states(products: &[Product]) -> Vec<(StateKey, StoreState)> {
+    let mut states: Vec<(StateKey, StoreState)> = Vec::new();
+    for product in products {
+        let item_id = match &product.ITEM_ID {
+            Some(item_id) => item_id,
+            None => continue,
+        };
+        for store_id in QTY_STORES {
+            let (price, first_disc, second_disc) = product.store_pricing(store_id);
+            let state = StoreState {
+                qty: product.store_qty(store_id),
+                price,
+                first_disc,
+                second_disc,
+            };
+            if !state.is_empty() {
+                states.push(((item_id.clone(), store_id.to_string()), state));
+            }
+        }
+    }
+    states
+}
+
+fn read_states(conn: &Connection, sql_manager: &SQLManager) -> Result<HashMap<StateKey, StoreState>, APIErrors> {
+    let sql = sql_manager.get_sql("get_inv_state")?;
+    let mut stmt = conn.statement(&sql).fetch_array_size(1000).build().map_err(db_error)?;
+    let rows = stmt.query(&[]).map_err(db_error)?;
+    let mut states: HashMap<StateKey, StoreState> = HashMap::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        let key: StateKey = (
+            row.get("ITEM_ID").map_err(db_error)?,
+            row.get("STORE_ID").map_err(db_error)?,
+        );
+        states.insert(
+            key,
+            StoreState {
+                qty: get_decimal(&row, "QTY"),
+                price: get_decimal(&row, "SALE_PRICE_NOTAX"),
+                first_disc: get_decimal(&row, "FIRST_DISC_PER"),
+                second_disc: get_decimal(&row, "SECOND_DISC_PER"),
+            },
+        );
+    }
+    Ok(states)
+}
+
+fn push_changes(changes: &mut Vec<Change>, key: &StateKey, old: &StoreState, new: &StoreState, changed_at: &str) {
+    for ((field, old_value), (_, new_value)) in old.fields().into_iter().zip(new.fields()) {
+        if old_value != new_value {
+            changes.push(Change {
+                ITEM_ID: key.0.clone(),
+                STORE_ID: key.1.clone(),
+                FIELD: field.to_string(),
+                OLD_VALUE: old_value,
+                NEW_VALUE: new_value,
+                CHANGED_AT: Some(changed_at.to_string()),
+            });
+        }
+    }
+}
+
+/// Field level changes between the stored and current states, the indexes of `current`
+/// that have to be written back and the stored pairs to delete. New item/store pairs count
+/// as changed from NULL, pairs gone from JHC_INVDATA (or now all NULL) as changed to NULL.
+fn diff_states(
+    previous: &HashMap<StateKey, StoreState>,
+    current: &[(StateKey, StoreState)],
+    changed_at: &str,
+) -> (Vec<Change>, Vec<usize>, Vec<StateKey>) {
+    let mut changes: Vec<Change> = Vec::new();
+    let mut updated: Vec<usize> = Vec::new();
+    let empty = StoreState::default();
+    for (index, (key, state)) in current.iter().enumerate() {
+        let old = previous.get(key).unwrap_or(&empty);
+        if old == state {
+            continue;
+        }
+        updated.push(index);
+        push_changes(&mut changes, key, old, state, changed_at);
+    }
+
+    let current_keys: HashSet<&StateKey> = current.iter().map(|(key, _)| key).collect();
+    let mut removed: Vec<StateKey> = previous.keys().filter(|key| !current_keys.contains(key)).cloned().collect();
+    removed.sort();
+    for key in removed.iter() {
+        push_changes(&mut changes, key, &previous[key], &empty, changed_at);
+    }
+    (changes, updated, removed)
+}
+
+/// Capture the tracked columns of JHC_INVDATA and record what changed since the last run.
+/// The first run only stores the baseline and reports no changes.
+pub fn snapshot(pool: &Pool, sql_manager: &SQLManager) -> Result<Vec<Change>, APIErrors> {
+    let now = std::time::Instant::now();
+    let all_stores: HashSet<String> = QTY_STORES.iter().map(|s| s.to_string()).collect();
+    let products = get_report_products(&[], &all_stores, false, pool, sql_manager)?;
+    let current = current_states(&products);
+
+    let conn = pool.get().map_err(db_error)?;
+    let previous = read_states(&conn, sql_manager)?;
+    let baseline = previous.is_empty();
+    let changed_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let (changes, updated, removed) = diff_states(&previous, &current, &changed_at);
+
+    in_transaction(&conn, || {
+        let merge_sql = sql_manager.get_sql("merge_inv_state")?;
+        let mut merge = conn.statement(&merge_sql).build().map_err(db_error)?;
+        for index in updated.iter() {
+            let ((item_id, store_id), state) = &current[*index];
+            merge
+                .execute_named(&[
+                    ("item_id", item_id),
+                    ("store_id", store_id),
+                    ("qty", &(&state.qty.map(|v| v.to_string()), &NUMBER)),
+                    ("price", &(&state.price.map(|v| v.to_string()), &NUMBER)),
+                    ("first_disc", &(&state.first_disc.map(|v| v.to_string()), &NUMBER)),
+                    ("second_disc", &(&state.second_disc.map(|v| v.to_string()), &NUMBER)),
+                ])
+                .map_err(db_error)?;
+        }
+        let delete_sql = sql_manager.get_sql("delete_inv_state")?;
+        let mut delete = conn.statement(&delete_sql).build().map_err(db_error)?;
+        for (item_id, store_id) in removed.iter() {
+            delete
+                .execute_named(&[("item_id", item_id), ("store_id", store_id)])
+                .map_err(db_error)?;
+        }
+        if !baseline {
+            let insert_sql = sql_manager.get_sql("insert_inv_change")?;
+            let mut insert = conn.statement(&insert_sql).build().map_err(db_error)?;
+            for change in changes.iter() {
+                insert
+                    .execute(&[
+                        &change.ITEM_ID,
+                        &change.STORE_ID,
+                        &change.FIELD,
+                        &(&change.OLD_VALUE.map(|v| v.to_string()), &NUMBER),
+                        &(&change.NEW_VALUE.map(|v| v.to_string()), &NUMBER),
+                    ])
+                    .map_err(db_error)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    info!(
+        "Inventory snapshot: {} states updated, {} removed, {} changes in {} ms",
+        updated.len(),
+        removed.len(),
+        if baseline { 0 } else { changes.len() },
+        now.elapsed().as_millis()
+    );
+    if baseline {
+        return Ok(Vec::new());
+    }
+    Ok(changes)
+}
+
+fn parse_date(value: Option<&String>, default: NaiveDate) -> Result<NaiveDate, APIErrors> {
+    match value {
+        Some(value) => NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|_| APIErrors::InvalidData),
+        None => Ok(default),
+    }
+}
+
+/// Changes in the user's stores, newest first
+pub async fn get_changes(
+    params: &ChangeFeedParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Vec<Change>, APIErrors> {
+    let fields: Vec<&str> = match params.kind.as_deref() {
+        Some("price") => PRICE_FIELDS.to_vec(),
+        Some("stock") => vec![QTY_FIELD],
+        None => [QTY_FIELD].into_iter().chain(PRICE_FIELDS).collect(),
+        Some(_) => return Err(APIErrors::InvalidData),
+    };
+    let today = Local::now().date_naive();
+    let date_to = parse_date(params.to.as_ref(), today)?;
+    let date_from = parse_date(params.from.as_ref(), date_to - Duration::days(7))?;
+    if date_from > date_to {
+        return Err(APIErrors::InvalidData);
+    }
+
+    let mut store_ids: Vec<String> = get_user_store_ids(key, pool, sql_manager).await?.into_iter().collect();
+    if let Some(store) = &params.store {
+        store_ids.retain(|store_id| store_id == store);
+    }
+    if store_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    store_ids.sort();
+
+    let date_from = date_from.format("%Y-%m-%d").to_string();
+    let date_to = date_to.format("%Y-%m-%d").to_string();
+    let mut sql = sql_manager.get_sql("get_inv_changes")?;
+    let mut binds: Vec<(&str, &dyn ToSql)> = vec![("date_from", &date_from), ("date_to", &date_to)];
+
+    let store_names: Vec<String> = (0..store_ids.len()).map(|i| format!("store_{}", i)).collect();
+    let placeholders: Vec<String> = store_names.iter().map(|name| format!(":{}", name)).collect();
+    sql.push_str(&format!(" AND STORE_ID IN ({})", placeholders.join(", ")));
+    for (name, store_id) in store_names.iter().zip(store_ids.iter()) {
+        binds.push((name.as_str(), store_id as &dyn ToSql));
+    }
+
+    let field_names: Vec<String> = (0..fields.len()).map(|i| format!("field_{}", i)).collect();
+    let placeholders: Vec<String> = field_names.iter().map(|name| format!(":{}", name)).collect();
+    sql.push_str(&format!(" AND FIELD IN ({})", placeholders.join(", ")));
+    for (name, field) in field_names.iter().zip(fields.iter()) {
+        binds.push((name.as_str(), field as &dyn ToSql));
+    }
+
+    if let Some(item) = &params.item {
+        sql.push_str(" AND ITEM_ID = :item_id");
+        binds.push(("item_id", item as &dyn ToSql));
+    }
+    sql.push_str(" ORDER BY CHANGED_AT DESC, ITEM_ID, STORE_ID FETCH FIRST 5000 ROWS ONLY");
+
+    let conn = pool.get().map_err(db_error)?;
+    let mut stmt = conn.statement(&sql).build().map_err(db_error)?;
+    let rows = stmt.query_named(&binds).map_err(db_error)?;
+    let mut changes: Vec<Change> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        changes.push(Change {
+            ITEM_ID: row.get("ITEM_ID").map_err(db_error)?,
+            STORE_ID: row.get("STORE_ID").map_err(db_error)?,
+            FIELD: row.get("FIELD").map_err(db_error)?,
+            OLD_VALUE: get_decimal(&row, "OLD_VALUE"),
+            NEW_VALUE: get_decimal(&row, "NEW_VALUE"),
+            CHANGED_AT: row.get("CHANGED_AT").unwrap_or(None),
+        });
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn key(item_id: &str, store_id: &str) -> StateKey {
+        (item_id.to_string(), store_id.to_string())
+    }
+
+    #[test]
+    fn test_diff_states() {
+        let before = StoreState {
+            qty: Some(dec("5")),
+            price: Some(dec("1.5")),
+            first_disc: None,
+            second_disc: None,
+        };
+        let previous = HashMap::from([
+            (key("1", "05"), before.clone()),
+            (key("2", "05"), before.clone()),
+            (key("4", "05"), before.clone()),
+        ]);
+
+        let mut repriced = before.clone();
+        repriced.price = Some(dec("1.75"));
+        repriced.first_disc = Some(dec("10"));
+        let current = vec![
+            (key("1", "05"), repriced),
+            (key("2", "05"), before.clone()),
+            (key("3", "01"), StoreState { qty: Some(dec("2")), ..Default::default() }),
+        ];
+
+        let (changes, updated, removed) = diff_states(&previous, &current, "2024-05-01 10:00:00");
+        assert_eq!(updated, vec![0, 2]);
+        // Item 4 is gone from the snapshot, its stored state is cleared
+        assert_eq!(removed, vec![key("4", "05")]);
+        let fields: Vec<(&str, &str)> = changes.iter().map(|c| (c.ITEM_ID.as_str(), c.FIELD.as_str())).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("1", "SALE_PRICE_NOTAX"),
+                ("1", "FIRST_DISC_PER"),
+                ("3", "QTY"),
+                ("4", "QTY"),
+                ("4", "SALE_PRICE_NOTAX"),
+            ]
+        );
+        assert_eq!(changes[0].OLD_VALUE, Some(dec("1.5")));
+        assert_eq!(changes[0].NEW_VALUE, Some(dec("1.75")));
+        assert_eq!(changes[2].OLD_VALUE, None);
+        assert_eq!(changes[3].OLD_VALUE, Some(dec("5")));
+        assert_eq!(changes[3].NEW_VALUE, None);
+    }
+}
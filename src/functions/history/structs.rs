@@ -0,0 +1,52 @@
+#![allow(non_snake_case)]
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+pub const QTY_FIELD: &str = "QTY";
+pub const PRICE_FIELDS: [&str; 3] = ["SALE_PRICE_NOTAX", "FIRST_DISC_PER", "SECOND_DISC_PER"];
+
+/// Tracked values of an item in one store
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoreState {
+    pub qty: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub first_disc: Option<Decimal>,
+    pub second_disc: Option<Decimal>,
+}
+
+impl StoreState {
+    pub fn is_empty(&self) -> bool {
+        *self == StoreState::default()
+    }
+
+    /// (FIELD, value) pairs in the same order as the INV_CHANGES_JHC field names
+    pub fn fields(&self) -> [(&'static str, Option<Decimal>); 4] {
+        [
+            (QTY_FIELD, self.qty),
+            (PRICE_FIELDS[0], self.price),
+            (PRICE_FIELDS[1], self.first_disc),
+            (PRICE_FIELDS[2], self.second_disc),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Change {
+    pub ITEM_ID: String,
+    pub STORE_ID: String,
+    pub FIELD: String,
+    pub OLD_VALUE: Option<Decimal>,
+    pub NEW_VALUE: Option<Decimal>,
+    pub CHANGED_AT: Option<String>,
+}
+
+/// `kind` is `price` (price and discounts) or `stock` (quantity), both when missing.
+/// Dates are YYYY-MM-DD and inclusive, the last 7 days by default.
+#[derive(Debug, Default, FromForm)]
+pub struct ChangeFeedParams {
+    pub store: Option<String>,
+    pub item: Option<String>,
+    pub kind: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
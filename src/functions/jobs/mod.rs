@@ -0,0 +1,103 @@
+pub mod queue;
+pub mod structs;
+
+use oracle::pool::Pool;
+use oracle::sql_type::OracleType;
+use oracle::{Connection, Row};
+
+use crate::functions::jobs::structs::Job;
+use crate::utils::db::db_error;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+pub const QUEUED: &str = "QUEUED";
+pub const DONE: &str = "DONE";
+pub const FAILED: &str = "FAILED";
+
+fn job_from_row(row: &Row) -> Result<Job, APIErrors> {
+    let json = |column: &str| -> Option<serde_json::Value> {
+        let value: Option<String> = row.get(column).unwrap_or(None);
+        value.and_then(|value| serde_json::from_str(&value).ok())
+    };
+    Ok(Job {
+        JOB_ID: row.get("JOB_ID").map_err(db_error)?,
+        KIND: row.get("KIND").map_err(db_error)?,
+        STATUS: row.get("STATUS").map_err(db_error)?,
+        CREATED_BY: row.get("CREATED_BY").map_err(db_error)?,
+        CREATED_AT: row.get("CREATED_AT").unwrap_or(None),
+        FINISHED_AT: row.get("FINISHED_AT").unwrap_or(None),
+        ATTEMPTS: row.get("ATTEMPTS").map_err(db_error)?,
+        LAST_ERROR: row.get("LAST_ERROR").unwrap_or(None),
+        PAYLOAD: json("PAYLOAD").unwrap_or_default(),
+        RESULT: json("RESULT"),
+    })
+}
+
+pub(crate) fn read_job(conn: &Connection, sql_manager: &SQLManager, job_id: i64) -> Result<Job, APIErrors> {
+    let mut rows = conn
+        .query(sql_manager.get_sql("get_job")?.as_str(), &[&job_id])
+        .map_err(db_error)?;
+    match rows.next() {
+        Some(row_result) => job_from_row(&row_result.map_err(db_error)?),
+        None => Err(APIErrors::NoData),
+    }
+}
+
+/// Insert a queued job, left uncommitted
+pub(crate) fn insert_job(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    kind: &str,
+    username: &str,
+    payload: &serde_json::Value,
+) -> Result<i64, APIErrors> {
+    let payload = payload.to_string();
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("insert_job")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    stmt.execute(&[
+        &kind,
+        &QUEUED,
+        &username,
+        &(&payload, &OracleType::CLOB),
+        &OracleType::Number(0, 0),
+    ])
+    .map_err(db_error)?;
+    let ids: Vec<i64> = stmt.returned_values(5).map_err(db_error)?;
+    ids.first().copied().ok_or(APIErrors::DBError)
+}
+
+/// Store the outcome of a job, `status` is `DONE` or `FAILED`
+pub(crate) fn finish_job(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    job_id: i64,
+    status: &str,
+    result: &serde_json::Value,
+    error: Option<&str>,
+) -> Result<(), APIErrors> {
+    let result = result.to_string();
+    conn.execute(
+        sql_manager.get_sql("finish_job")?.as_str(),
+        &[&status, &(&result, &OracleType::CLOB), &error, &job_id],
+    )
+    .map_err(db_error)?;
+    conn.commit().map_err(db_error)
+}
+
+/// Jobs are visible to the user who started them and to admins
+pub async fn get_job(
+    job_id: i64,
+    username: &str,
+    admin: bool,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+) -> Result<Job, APIErrors> {
+    let conn = pool.get().map_err(db_error)?;
+    let job = read_job(&conn, sql_manager, job_id)?;
+    if !admin && job.CREATED_BY != username {
+        return Err(APIErrors::NoData);
+    }
+    Ok(job)
+}
@@ -0,0 +1,361 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use oracle::pool::Pool;
+use oracle::Connection;
+use tokio::sync::mpsc;
+
+use crate::functions::files::cache::ImageCache;
+use crate::functions::files::store::ImageStore;
+use crate::functions::files::tasks::{can_retry, run_image_job};
+use crate::functions::files::validation::UploadLimits;
+use crate::functions::jobs::structs::Job;
+use crate::functions::jobs::{finish_job, insert_job, read_job, DONE, FAILED};
+use crate::utils::db::{db_error, in_transaction};
+use crate::utils::env::env_number;
+use crate::utils::scheduler::spawn_periodic;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Longest wait between two attempts of a job
+const MAX_BACKOFF_SECONDS: u64 = 3600;
+
+/// What job handlers run with
+#[derive(Clone)]
+pub struct JobContext {
+    pub pool: Pool,
+    pub sql_manager: SQLManager,
+    pub image_store: Arc<dyn ImageStore>,
+    pub image_cache: Arc<ImageCache>,
+    pub upload_limits: UploadLimits,
+}
+
+/// Wait before the next attempt after `attempts` failed ones, doubling each time
+fn backoff(attempts: i64, retry_seconds: u64) -> u64 {
+    let doublings = (attempts - 1).clamp(0, 16) as u32;
+    retry_seconds.saturating_mul(1 << doublings).min(MAX_BACKOFF_SECONDS)
+}
+
+/// Failures that can go away by themselves: the database or the image store couldn't
+/// be reached. Bad input, refused requests and local errors fail the same way every time.
+fn is_retryable(err: &APIErrors) -> bool {
+    matches!(err, APIErrors::DBError | APIErrors::SFTPError | APIErrors::StoreUnavailable)
+}
+
+/// Run a job's DB work on the blocking pool, oracle calls would stall the runtime threads
+async fn with_conn<T: Send + 'static>(
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    work: impl FnOnce(&Connection, &SQLManager) -> Result<T, APIErrors> + Send + 'static,
+) -> Result<T, APIErrors> {
+    let (pool, sql_manager) = (pool.clone(), sql_manager.clone());
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(db_error)?;
+        work(&conn, &sql_manager)
+    })
+    .await
+    .map_err(|e| {
+        error!("Job queue DB call panicked: {:?}", e);
+        APIErrors::InternalServerError
+    })?
+}
+
+/// Mark a due job RUNNING and read it. `None` when it was run by another worker,
+/// already finished, or is a retry that isn't due yet.
+fn claim_job(conn: &Connection, sql_manager: &SQLManager, job_id: i64) -> Result<Option<Job>, APIErrors> {
+    let claim = conn
+        .execute(sql_manager.get_sql("claim_job")?.as_str(), &[&job_id])
+        .map_err(db_error)?;
+    let claimed = claim.row_count().map_err(db_error)?;
+    conn.commit().map_err(db_error)?;
+    match claimed {
+        0 => Ok(None),
+        _ => read_job(conn, sql_manager, job_id).map(Some),
+    }
+}
+
+/// Where a job's uploaded file waits until the job is done
+fn input_path(spool_dir: &Path, job_id: i64) -> PathBuf {
+    spool_dir.join(format!("{}.input", job_id))
+}
+
+/// Jobs left RUNNING by a stopped server go back to QUEUED
+fn requeue_running_jobs(conn: &Connection, sql_manager: &SQLManager) -> Result<u64, APIErrors> {
+    in_transaction(conn, || {
+        let stmt = conn
+            .execute(sql_manager.get_sql("requeue_running_jobs")?.as_str(), &[])
+            .map_err(db_error)?;
+        stmt.row_count().map_err(db_error)
+    })
+}
+
+/// Jobs live in `JOBS_JHC`, the channel only carries the ids of jobs that are due.
+/// Workers claim a job in the table before running it, so an id handed out twice
+/// (by `enqueue` and the poller) still runs once. Queued work left by a stopped
+/// server is picked up by the poller once the workers are started again.
+///
+/// A job's input is only in the spool dir, which has to outlive the server and be
+/// shared by every server polling `JOBS_JHC`. A job whose input is gone fails.
+pub struct JobQueue {
+    sender: mpsc::Sender<i64>,
+    receiver: Mutex<Option<mpsc::Receiver<i64>>>,
+    /// Ids in the channel, so the poller doesn't send them again
+    pending: Mutex<HashSet<i64>>,
+    workers: usize,
+    max_attempts: i64,
+    retry_seconds: u64,
+    poll_seconds: u64,
+    spool_dir: PathBuf,
+}
+
+impl JobQueue {
+    pub fn new(
+        capacity: usize,
+        workers: usize,
+        max_attempts: i64,
+        retry_seconds: u64,
+        poll_seconds: u64,
+        spool_dir: impl Into<PathBuf>,
+    ) -> JobQueue {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        JobQueue {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            pending: Mutex::new(HashSet::new()),
+            workers: workers.max(1),
+            max_attempts: max_attempts.max(1),
+            retry_seconds,
+            poll_seconds: poll_seconds.max(1),
+            spool_dir: spool_dir.into(),
+        }
+    }
+
+    /// `JOB_QUEUE_SIZE` (100), `JOB_WORKERS` (2), `JOB_MAX_ATTEMPTS` (5), `JOB_RETRY_SECONDS` (30),
+    /// `JOB_POLL_SECONDS` (15) and `JOB_SPOOL_DIR` (`spool/jobs`, must be persistent storage)
+    pub fn from_env() -> JobQueue {
+        JobQueue::new(
+            env_number("JOB_QUEUE_SIZE", 100),
+            env_number("JOB_WORKERS", 2),
+            env_number("JOB_MAX_ATTEMPTS", 5),
+            env_number("JOB_RETRY_SECONDS", 30),
+            env_number("JOB_POLL_SECONDS", 15),
+            std::env::var("JOB_SPOOL_DIR").unwrap_or("spool/jobs".to_string()),
+        )
+    }
+
+    /// Hand a job to the workers, false when the channel is full
+    fn dispatch(&self, job_id: i64) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.contains(&job_id) {
+            return true;
+        }
+        match self.sender.try_send(job_id) {
+            Ok(()) => {
+                pending.insert(job_id);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Persist a job with its uploaded file and queue it. `QueueFull` when the workers
+    /// are too far behind to take more.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn enqueue(
+        &self,
+        kind: &str,
+        payload: serde_json::Value,
+        input: Vec<u8>,
+        username: &str,
+        pool: &Pool,
+        sql_manager: &SQLManager,
+    ) -> Result<Job, APIErrors> {
+        if self.sender.capacity() == 0 {
+            return Err(APIErrors::QueueFull);
+        }
+        let (kind, username) = (kind.to_string(), username.to_string());
+        let spool_dir = self.spool_dir.clone();
+        let job = with_conn(pool, sql_manager, move |conn, sql_manager| {
+            let job_id = insert_job(conn, sql_manager, &kind, &username, &payload)?;
+            let spooled = std::fs::create_dir_all(&spool_dir)
+                .and_then(|_| std::fs::write(input_path(&spool_dir, job_id), &input))
+                .map_err(|e| {
+                    error!("Error spooling input of job {}: {}", job_id, e);
+                    APIErrors::IOError
+                });
+            in_transaction(conn, || spooled)?;
+            read_job(conn, sql_manager, job_id)
+        })
+        .await?;
+
+        info!("Job {} ({}) queued", job.JOB_ID, job.KIND);
+        if !self.dispatch(job.JOB_ID) {
+            info!("Job {} waits for the next poll", job.JOB_ID);
+        }
+        Ok(job)
+    }
+
+    /// Send due jobs to the workers: retries whose backoff has passed and jobs that
+    /// didn't fit in the channel or were queued before a restart. Blocking.
+    fn poll(&self, pool: &Pool, sql_manager: &SQLManager) -> Result<(), APIErrors> {
+        let free = self.sender.capacity();
+        if free == 0 {
+            return Ok(());
+        }
+        let conn = pool.get().map_err(db_error)?;
+        let rows = conn
+            .query(sql_manager.get_sql("get_due_jobs")?.as_str(), &[&(free as i64)])
+            .map_err(db_error)?;
+        for row_result in rows {
+            let job_id: i64 = row_result.map_err(db_error)?.get("JOB_ID").map_err(db_error)?;
+            if !self.dispatch(job_id) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Claim a job and run it. A failed attempt is retried after a backoff until
+    /// `max_attempts`, then the job fails with the last error.
+    async fn run(&self, job_id: i64, context: &JobContext) -> Result<(), APIErrors> {
+        let (pool, sql_manager) = (&context.pool, &context.sql_manager);
+        let job = match with_conn(pool, sql_manager, move |conn, sql_manager| claim_job(conn, sql_manager, job_id)).await? {
+            Some(job) => job,
+            None => return Ok(()),
+        };
+        info!("Job {} ({}) started, attempt {}", job_id, job.KIND, job.ATTEMPTS);
+
+        let path = input_path(&self.spool_dir, job_id);
+        let outcome = match tokio::fs::read(&path).await {
+            Ok(input) => {
+                // Run apart so a panicking handler fails the attempt instead of the worker, on the
+                // blocking pool since handlers make oracle calls and wait on item locks
+                let (handler_job, handler_context) = (job.clone(), context.clone());
+                let handle = tokio::runtime::Handle::current();
+                tokio::task::spawn_blocking(move || {
+                    handle.block_on(run_image_job(&handler_job, input, &handler_context))
+                })
+                .await
+                .unwrap_or_else(|e| {
+                    error!("Job {} panicked: {:?}", job_id, e);
+                    Err(APIErrors::InternalServerError)
+                })
+            }
+            Err(e) => {
+                error!("Error reading input of job {}: {}", job_id, e);
+                Err(APIErrors::IOError)
+            }
+        };
+
+        let (max_attempts, retry_seconds) = (self.max_attempts, self.retry_seconds);
+        let finished = with_conn(pool, sql_manager, move |conn, sql_manager| match outcome {
+            Ok(result) => {
+                finish_job(conn, sql_manager, job.JOB_ID, DONE, &result, None)?;
+                info!("Job {} done", job.JOB_ID);
+                Ok(true)
+            }
+            Err(err) => {
+                let message = err.to_string();
+                if is_retryable(&err) && can_retry(&job.KIND) && job.ATTEMPTS < max_attempts {
+                    let delay = backoff(job.ATTEMPTS, retry_seconds);
+                    warn!("Job {} failed ({}), retrying in {}s", job.JOB_ID, message, delay);
+                    conn.execute(
+                        sql_manager.get_sql("retry_job")?.as_str(),
+                        &[&(delay as i64), &message, &job.JOB_ID],
+                    )
+                    .map_err(db_error)?;
+                    conn.commit().map_err(db_error)?;
+                    return Ok(false);
+                }
+                error!("Job {} failed: {}", job.JOB_ID, message);
+                let result = serde_json::json!({ "ERROR": message });
+                finish_job(conn, sql_manager, job.JOB_ID, FAILED, &result, Some(&message))?;
+                Ok(true)
+            }
+        })
+        .await?;
+        if finished {
+            tokio::fs::remove_file(&path).await.ok();
+        }
+        Ok(())
+    }
+
+    /// Start the workers and the poller. Jobs left RUNNING by a stopped server are
+    /// queued again first, their attempt counts as failed.
+    pub fn start(self: &Arc<Self>, context: JobContext) {
+        let receiver = match self.receiver.lock().unwrap().take() {
+            Some(receiver) => Arc::new(tokio::sync::Mutex::new(receiver)),
+            None => return,
+        };
+        let queue = self.clone();
+        tokio::spawn(async move {
+            match with_conn(&context.pool, &context.sql_manager, requeue_running_jobs).await {
+                Ok(0) => (),
+                Ok(count) => info!("{} interrupted jobs queued again", count),
+                Err(e) => error!("Error requeuing interrupted jobs: {}", e),
+            }
+
+            for _ in 0..queue.workers {
+                let queue = queue.clone();
+                let receiver = receiver.clone();
+                let context = context.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let job_id = match receiver.lock().await.recv().await {
+                            Some(job_id) => job_id,
+                            None => return,
+                        };
+                        queue.pending.lock().unwrap().remove(&job_id);
+                        if let Err(e) = queue.run(job_id, &context).await {
+                            error!("Error running job {}: {}", job_id, e);
+                        }
+                    }
+                });
+            }
+
+            let poller = queue.clone();
+            let (pool, sql_manager) = (context.pool.clone(), context.sql_manager.clone());
+            spawn_periodic("job_queue", Duration::from_secs(queue.poll_seconds), move || {
+                poller.poll(&pool, &sql_manager)
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1, 30), 30);
+        assert_eq!(backoff(2, 30), 60);
+        assert_eq!(backoff(4, 30), 240);
+        assert_eq!(backoff(10, 30), MAX_BACKOFF_SECONDS);
+        assert_eq!(backoff(0, 30), 30);
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&APIErrors::SFTPError));
+        assert!(is_retryable(&APIErrors::DBError));
+        assert!(is_retryable(&APIErrors::StoreUnavailable));
+        assert!(!is_retryable(&APIErrors::InvalidData));
+        assert!(!is_retryable(&APIErrors::NoData));
+        assert!(!is_retryable(&APIErrors::InternalServerError));
+        assert!(!is_retryable(&APIErrors::IOError));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let queue = JobQueue::new(2, 1, 3, 30, 15, std::env::temp_dir());
+        assert!(queue.dispatch(1));
+        // Already waiting in the channel, not sent twice
+        assert!(queue.dispatch(1));
+        assert!(queue.dispatch(2));
+        assert!(!queue.dispatch(3));
+        assert_eq!(queue.pending.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,21 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub JOB_ID: i64,
+    pub KIND: String,
+    /// QUEUED, RUNNING, DONE or FAILED
+    pub STATUS: String,
+    pub CREATED_BY: String,
+    pub CREATED_AT: Option<String>,
+    pub FINISHED_AT: Option<String>,
+    pub ATTEMPTS: i64,
+    /// Error of the last failed attempt
+    pub LAST_ERROR: Option<String>,
+    /// Request parameters the job runs with, not sent to clients
+    #[serde(skip)]
+    pub PAYLOAD: serde_json::Value,
+    /// Set once the job has finished, its shape depends on `KIND`
+    pub RESULT: Option<serde_json::Value>,
+}
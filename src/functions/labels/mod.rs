@@ -0,0 +1,466 @@
+pub mod structs;
+pub mod symbology;
+
+use std::collections::{HashMap, HashSet};
+
+use magick_rust::MagickWand;
+use oracle::pool::Pool;
+
+use crate::functions::files::init_magick;
+use crate::functions::labels::structs::{LabelData, LabelFormat, LabelParams, LabelTemplate, Symbology};
+use crate::functions::labels::symbology::Symbol;
+use crate::functions::products::barcode::normalize;
+use crate::functions::products::pricing::PricingConfig;
+use crate::functions::products::structs::Product;
+use crate::functions::products::{get_products_by_ids, get_user_store_ids};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Labels per request, PDF sheets are split into pages
+const MAX_LABELS: usize = 500;
+/// Labels in one PNG, whatever the template's sheet holds
+const MAX_PNG_LABELS: usize = 40;
+const PADDING_MM: f64 = 1.5;
+/// Quiet zone on each side of a linear barcode, in modules
+const QUIET_ZONE: usize = 10;
+
+/// Built-in templates, plus the ones in the JSON file at `LABEL_TEMPLATES_FILE`
+/// (a list of templates, an entry with a built-in name replaces it)
+pub struct LabelTemplates {
+    templates: Vec<LabelTemplate>,
+}
+
+fn template(name: &str, width_mm: f64, height_mm: f64, columns: u32, rows: u32, margin_mm: f64, gap_mm: f64) -> LabelTemplate {
+    LabelTemplate {
+        name: name.to_string(),
+        width_mm,
+        height_mm,
+        columns,
+        rows,
+        margin_mm,
+        gap_mm,
+        dpi: 300,
+        font_family: "DejaVu Sans".to_string(),
+        description_pt: 8.0,
+        price_pt: 16.0,
+        price_prefix: String::new(),
+    }
+}
+
+fn valid_template(template: &LabelTemplate) -> bool {
+    template.width_mm > 0.0
+        && template.height_mm > 0.0
+        && template.columns > 0
+        && template.rows > 0
+        && template.margin_mm >= 0.0
+        && template.gap_mm >= 0.0
+        && (72..=1200).contains(&template.dpi)
+        && template.description_pt > 0.0
+        && template.price_pt > 0.0
+}
+
+impl LabelTemplates {
+    pub fn new(custom: Vec<LabelTemplate>) -> LabelTemplates {
+        let mut templates = vec![
+            // Single labels for thermal label printers
+            template("shelf", 60.0, 40.0, 1, 1, 0.0, 0.0),
+            template("small", 38.0, 25.0, 1, 1, 0.0, 0.0),
+            // A4 sheet of 3 x 7 labels
+            template("a4-3x7", 63.5, 38.1, 3, 7, 7.0, 2.5),
+        ];
+        for custom in custom {
+            if !valid_template(&custom) {
+                error!("Invalid label template {}, skipped", custom.name);
+                continue;
+            }
+            templates.retain(|t| t.name != custom.name);
+            templates.push(custom);
+        }
+        LabelTemplates { templates }
+    }
+
+    pub fn from_env() -> LabelTemplates {
+        let custom = match std::env::var("LABEL_TEMPLATES_FILE") {
+            Ok(path) if !path.is_empty() => std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str::<Vec<LabelTemplate>>(&json).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| {
+                    error!("Error loading label templates from {}: {}", path, e);
+                    Vec::new()
+                }),
+            _ => Vec::new(),
+        };
+        LabelTemplates::new(custom)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LabelTemplate> {
+        self.templates.iter().find(|t| t.name == name)
+    }
+
+    pub fn list(&self) -> &[LabelTemplate] {
+        &self.templates
+    }
+}
+
+/// EAN-13 for valid EAN-13/UPC-A main barcodes, Code 128 of the barcode or item id otherwise
+fn pick_symbol(label: &LabelData, symbology: Symbology) -> Option<Symbol> {
+    let code = label.barcode.as_deref().unwrap_or(&label.item_id);
+    match symbology {
+        Symbology::Auto => symbology::ean13(code).or_else(|| symbology::code128(code)),
+        Symbology::Ean13 => symbology::ean13(code),
+        Symbology::Code128 => symbology::code128(code),
+        Symbology::Qr => symbology::qr(code),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Up to `max_lines` lines of about `max_chars`, the last one cut with an ellipsis
+fn wrap_text(text: &str, max_chars: usize, max_lines: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if candidate.chars().count() <= max_chars || current.is_empty() {
+            current = candidate;
+        } else {
+            lines.push(current);
+            current = word.to_string();
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = lines.last_mut().unwrap();
+        last.push('…');
+    }
+    for line in lines.iter_mut() {
+        if line.chars().count() > max_chars {
+            *line = line.chars().take(max_chars.saturating_sub(1)).collect::<String>() + "…";
+        }
+    }
+    lines
+}
+
+/// One label as an SVG group at (`x`, `y`), all values in dots of the template's dpi
+fn label_svg(label: &LabelData, symbol: &Symbol, template: &LabelTemplate, x: f64, y: f64) -> String {
+    let dots_mm = template.dpi as f64 / 25.4;
+    let dots_pt = template.dpi as f64 / 72.0;
+    let width = template.width_mm * dots_mm;
+    let height = template.height_mm * dots_mm;
+    let pad = PADDING_MM * dots_mm;
+    let description_size = template.description_pt * dots_pt;
+    let price_size = template.price_pt * dots_pt;
+    let font = escape_xml(&template.font_family);
+
+    let mut svg = format!("<g transform=\"translate({:.2} {:.2})\">", x, y);
+    let max_chars = ((width - 2.0 * pad) / (0.55 * description_size)) as usize;
+    let mut baseline = pad;
+    for line in wrap_text(&label.description, max_chars, 2) {
+        baseline += description_size * 1.15;
+        svg.push_str(&format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"{}\" font-size=\"{:.2}\">{}</text>",
+            pad,
+            baseline,
+            font,
+            description_size,
+            escape_xml(&line)
+        ));
+    }
+    baseline += price_size;
+    svg.push_str(&format!(
+        "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"{}\" font-size=\"{:.2}\" font-weight=\"bold\">{}{}</text>",
+        pad,
+        baseline,
+        font,
+        price_size,
+        escape_xml(&template.price_prefix),
+        escape_xml(&label.price)
+    ));
+
+    let top = baseline + 0.8 * dots_mm;
+    let bottom = height - pad;
+    let available = width - 2.0 * pad;
+    match symbol {
+        Symbol::Linear { modules, text } => {
+            let module = (available / (modules.len() + 2 * QUIET_ZONE) as f64).floor().max(1.0);
+            let bars_height = (bottom - top - description_size * 1.2).max(dots_mm);
+            let left = pad + (available - module * modules.len() as f64) / 2.0;
+            let mut start: Option<usize> = None;
+            for (i, dark) in modules.iter().chain(std::iter::once(&false)).enumerate() {
+                match (dark, start) {
+                    (true, None) => start = Some(i),
+                    (false, Some(s)) => {
+                        svg.push_str(&format!(
+                            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\"/>",
+                            left + s as f64 * module,
+                            top,
+                            (i - s) as f64 * module,
+                            bars_height
+                        ));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            svg.push_str(&format!(
+                "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"{}\" font-size=\"{:.2}\" text-anchor=\"middle\">{}</text>",
+                width / 2.0,
+                top + bars_height + description_size * 1.1,
+                font,
+                description_size,
+                escape_xml(text)
+            ));
+        }
+        Symbol::Matrix { width: size, modules } => {
+            let side = (bottom - top).min(available);
+            let module = (side / (*size + 8) as f64).floor().max(1.0);
+            let left = pad + (available - module * *size as f64) / 2.0;
+            let offset = top + (side - module * *size as f64) / 2.0;
+            for (i, dark) in modules.iter().enumerate() {
+                if *dark {
+                    svg.push_str(&format!(
+                        "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\"/>",
+                        left + (i % size) as f64 * module,
+                        offset + (i / size) as f64 * module,
+                        module,
+                        module
+                    ));
+                }
+            }
+        }
+    }
+    svg.push_str("</g>");
+    svg
+}
+
+/// A sheet of `rows` x `columns` labels, filled row by row
+fn sheet_svg(labels: &[(LabelData, Symbol)], template: &LabelTemplate, rows: u32) -> String {
+    let dots_mm = template.dpi as f64 / 25.4;
+    let columns = template.columns as f64;
+    let width_mm = 2.0 * template.margin_mm + columns * template.width_mm + (columns - 1.0) * template.gap_mm;
+    let height_mm =
+        2.0 * template.margin_mm + rows as f64 * template.height_mm + (rows as f64 - 1.0) * template.gap_mm;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.2}mm\" height=\"{h:.2}mm\" viewBox=\"0 0 {vw:.2} {vh:.2}\">\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/><g fill=\"black\">",
+        w = width_mm,
+        h = height_mm,
+        vw = width_mm * dots_mm,
+        vh = height_mm * dots_mm
+    );
+    for (i, (label, symbol)) in labels.iter().enumerate() {
+        let column = (i as u32 % template.columns) as f64;
+        let row = (i as u32 / template.columns) as f64;
+        let x = (template.margin_mm + column * (template.width_mm + template.gap_mm)) * dots_mm;
+        let y = (template.margin_mm + row * (template.height_mm + template.gap_mm)) * dots_mm;
+        svg.push_str(&label_svg(label, symbol, template, x, y));
+    }
+    svg.push_str("</g></svg>");
+    svg
+}
+
+fn magick_error(e: magick_rust::MagickError) -> APIErrors {
+    error!("Label Rendering Error: {}", e);
+    APIErrors::InternalServerError
+}
+
+fn rasterize(sheets: &[String], dpi: u32, format: LabelFormat) -> Result<Vec<u8>, APIErrors> {
+    init_magick();
+    let mut output = MagickWand::new();
+    for sheet in sheets {
+        let page = MagickWand::new();
+        // Sizes are in mm, so the density sets the pixel count and the PDF page size
+        page.set_resolution(dpi as f64, dpi as f64).map_err(magick_error)?;
+        page.set_format("svg").map_err(magick_error)?;
+        page.read_image_blob(sheet).map_err(magick_error)?;
+        output.add_image(&page).map_err(magick_error)?;
+    }
+    match format {
+        LabelFormat::Png => output.write_image_blob("png").map_err(magick_error),
+        LabelFormat::Pdf => output.write_images_blob("pdf").map_err(magick_error),
+    }
+}
+
+/// Most labels a request can ask for. A PNG is a single image, so it holds one sheet at
+/// most; bulk printing goes through PDF.
+fn label_limit(template: &LabelTemplate, format: LabelFormat) -> usize {
+    match format {
+        LabelFormat::Png => ((template.columns * template.rows) as usize).min(MAX_PNG_LABELS),
+        LabelFormat::Pdf => MAX_LABELS,
+    }
+}
+
+/// PNG: one image with every label, up to one sheet. PDF: one page per `columns` x `rows` labels.
+fn render(labels: Vec<(LabelData, Symbol)>, template: &LabelTemplate, format: LabelFormat) -> Result<Vec<u8>, APIErrors> {
+    let sheets: Vec<String> = match format {
+        LabelFormat::Png => {
+            let rows = (labels.len() as u32).div_ceil(template.columns);
+            vec![sheet_svg(&labels, template, rows)]
+        }
+        LabelFormat::Pdf => labels
+            .chunks((template.columns * template.rows) as usize)
+            .map(|page| sheet_svg(page, template, template.rows))
+            .collect(),
+    };
+    rasterize(&sheets, template.dpi, format)
+}
+
+fn label_data(product: &Product, store_id: &str, pricing: &PricingConfig, with_tax: bool) -> LabelData {
+    let price = pricing.net_prices(product, with_tax).get(store_id).map(|net| {
+        let price = if with_tax { net.NET_PRICE_TAX.unwrap_or(net.NET_PRICE) } else { net.NET_PRICE };
+        format!("{:.*}", pricing.scale as usize, price.round_dp(pricing.scale))
+    });
+    LabelData {
+        item_id: product.ITEM_ID.clone().unwrap_or_default(),
+        description: product
+            .ITEM_DESC
+            .clone()
+            .or(product.ITEM_DESC_S.clone())
+            .unwrap_or_default(),
+        price: price.unwrap_or_default(),
+        barcode: product
+            .ITEM_MAIN_BARCODE
+            .as_deref()
+            .map(normalize)
+            .filter(|barcode| !barcode.is_empty()),
+    }
+}
+
+/// Labels for the listed items in one of the caller's stores. Items repeat as often as
+/// they are listed. Product data and prices are read like `get_product` does.
+pub async fn render_labels(
+    params: &LabelParams,
+    templates: &LabelTemplates,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    pricing: &PricingConfig,
+    key: &ApiKey<'_>,
+) -> Result<(LabelFormat, Vec<u8>), APIErrors> {
+    let format = LabelFormat::parse(params.p_format.as_deref()).ok_or(APIErrors::InvalidData)?;
+    let symbology = Symbology::parse(params.p_symbology.as_deref()).ok_or(APIErrors::InvalidData)?;
+    let template = templates
+        .get(params.p_template.as_deref().unwrap_or("shelf"))
+        .ok_or(APIErrors::InvalidData)?
+        .clone();
+    let item_ids: Vec<String> = params.p_item_ids.iter().map(|id| id.trim().to_string()).collect();
+    if item_ids.is_empty() || item_ids.len() > label_limit(&template, format) || item_ids.iter().any(|id| id.is_empty()) {
+        return Err(APIErrors::InvalidData);
+    }
+
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    if !store_ids.contains(&params.p_store_id) {
+        return Err(APIErrors::NoStoreAccess);
+    }
+    let label_store: HashSet<String> = HashSet::from([params.p_store_id.clone()]);
+    let mut unique: Vec<String> = item_ids.clone();
+    unique.sort();
+    unique.dedup();
+    let products: HashMap<String, Product> = get_products_by_ids(&unique, pool, &label_store, false)?
+        .into_iter()
+        .filter_map(|product| product.ITEM_ID.clone().map(|id| (id, product)))
+        .collect();
+
+    let with_tax = params.p_with_tax.unwrap_or(false);
+    let mut labels: Vec<(LabelData, Symbol)> = Vec::new();
+    for item_id in item_ids.iter() {
+        let product = products.get(item_id).ok_or_else(|| {
+            error!("Unknown item for label: {}", item_id);
+            APIErrors::InvalidData
+        })?;
+        let label = label_data(product, &params.p_store_id, pricing, with_tax);
+        let symbol = pick_symbol(&label, symbology).ok_or_else(|| {
+            error!("Item {} can't be encoded as {:?}", item_id, symbology);
+            APIErrors::InvalidData
+        })?;
+        labels.push((label, symbol));
+    }
+
+    let body = tokio::task::spawn_blocking(move || render(labels, &template, format))
+        .await
+        .map_err(|e| {
+            error!("Label rendering panicked: {:?}", e);
+            APIErrors::InternalServerError
+        })??;
+    Ok((format, body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn label(barcode: Option<&str>) -> LabelData {
+        LabelData {
+            item_id: "100".to_string(),
+            description: "Olive Oil <Extra> & Virgin 1L".to_string(),
+            price: "12.500".to_string(),
+            barcode: barcode.map(|b| b.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_pick_symbol() {
+        let ean = pick_symbol(&label(Some("036000291452")), Symbology::Auto);
+        assert!(matches!(ean, Some(Symbol::Linear { ref text, .. }) if text == "0036000291452"));
+        let code128 = pick_symbol(&label(Some("ABC-1")), Symbology::Auto);
+        assert!(matches!(code128, Some(Symbol::Linear { ref text, .. }) if text == "ABC-1"));
+        let item_id = pick_symbol(&label(None), Symbology::Auto);
+        assert!(matches!(item_id, Some(Symbol::Linear { ref text, .. }) if text == "100"));
+        assert!(pick_symbol(&label(Some("ABC-1")), Symbology::Ean13).is_none());
+        assert!(matches!(pick_symbol(&label(None), Symbology::Qr), Some(Symbol::Matrix { .. })));
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(wrap_text("Olive Oil Extra Virgin", 10, 2), vec!["Olive Oil", "Extra…"]);
+        assert_eq!(wrap_text("Short", 10, 2), vec!["Short"]);
+        assert_eq!(wrap_text("Supercalifragilistic", 10, 2), vec!["Supercali…"]);
+    }
+
+    #[test]
+    fn test_sheet_svg() {
+        let templates = LabelTemplates::new(vec![]);
+        let template = templates.get("a4-3x7").unwrap();
+        let item = label(Some("4006381333931"));
+        let symbol = pick_symbol(&item, Symbology::Auto).unwrap();
+        let svg = sheet_svg(&[(item, symbol)], template, template.rows);
+        assert!(svg.contains("width=\"209.50mm\""));
+        assert!(svg.contains("Olive Oil &lt;Extra&gt; &amp; Virgin 1L"));
+        assert!(svg.contains(">12.500</text>"));
+        assert!(svg.contains(">4006381333931</text>"));
+    }
+
+    #[test]
+    fn test_label_limit() {
+        let templates = LabelTemplates::new(vec![]);
+        let sheet = templates.get("a4-3x7").unwrap();
+        assert_eq!(label_limit(sheet, LabelFormat::Png), 21);
+        assert_eq!(label_limit(sheet, LabelFormat::Pdf), MAX_LABELS);
+        assert_eq!(label_limit(&template("roll", 50.0, 30.0, 1, 1000, 0.0, 0.0), LabelFormat::Png), MAX_PNG_LABELS);
+    }
+
+    #[test]
+    fn test_custom_templates() {
+        let mut custom = template("shelf", 50.0, 30.0, 1, 1, 0.0, 0.0);
+        custom.price_prefix = "KD ".to_string();
+        let mut invalid = template("broken", 50.0, 30.0, 0, 1, 0.0, 0.0);
+        invalid.dpi = 10;
+        let templates = LabelTemplates::new(vec![custom.clone(), invalid]);
+        assert_eq!(templates.get("shelf"), Some(&custom));
+        assert!(templates.get("broken").is_none());
+        assert_eq!(templates.list().len(), 3);
+    }
+}
@@ -0,0 +1,108 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelParams {
+    pub p_store_id: String,
+    pub p_item_ids: Vec<String>,
+    /// Template name, `shelf` by default
+    pub p_template: Option<String>,
+    /// `png` (default) or `pdf`
+    pub p_format: Option<String>,
+    /// `auto` (default: EAN-13 when the main barcode is a valid EAN-13/UPC-A, Code 128 otherwise),
+    /// `ean13`, `code128` or `qr`
+    pub p_symbology: Option<String>,
+    pub p_with_tax: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelFormat {
+    Png,
+    Pdf,
+}
+
+impl LabelFormat {
+    pub fn parse(value: Option<&str>) -> Option<LabelFormat> {
+        match value.unwrap_or("png") {
+            "png" => Some(LabelFormat::Png),
+            "pdf" => Some(LabelFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbology {
+    Auto,
+    Ean13,
+    Code128,
+    Qr,
+}
+
+impl Symbology {
+    pub fn parse(value: Option<&str>) -> Option<Symbology> {
+        match value.unwrap_or("auto") {
+            "auto" => Some(Symbology::Auto),
+            "ean13" => Some(Symbology::Ean13),
+            "code128" => Some(Symbology::Code128),
+            "qr" => Some(Symbology::Qr),
+            _ => None,
+        }
+    }
+}
+
+/// Label size and layout. Sizes are in millimetres, text sizes in points.
+/// PNG output stacks the labels in `columns`, PDF pages hold `columns` x `rows` labels.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LabelTemplate {
+    pub name: String,
+    pub width_mm: f64,
+    pub height_mm: f64,
+    #[serde(default = "one")]
+    pub columns: u32,
+    #[serde(default = "one")]
+    pub rows: u32,
+    #[serde(default)]
+    pub margin_mm: f64,
+    #[serde(default)]
+    pub gap_mm: f64,
+    #[serde(default = "default_dpi")]
+    pub dpi: u32,
+    #[serde(default = "default_font")]
+    pub font_family: String,
+    #[serde(default = "default_description_pt")]
+    pub description_pt: f64,
+    #[serde(default = "default_price_pt")]
+    pub price_pt: f64,
+    #[serde(default)]
+    pub price_prefix: String,
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn default_dpi() -> u32 {
+    300
+}
+
+fn default_font() -> String {
+    "DejaVu Sans".to_string()
+}
+
+fn default_description_pt() -> f64 {
+    8.0
+}
+
+fn default_price_pt() -> f64 {
+    16.0
+}
+
+/// What gets printed on one label
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelData {
+    pub item_id: String,
+    pub description: String,
+    pub price: String,
+    pub barcode: Option<String>,
+}
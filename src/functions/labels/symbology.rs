@@ -0,0 +1,167 @@
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::functions::products::barcode::gtin_format;
+use crate::functions::products::structs::GtinFormat;
+
+/// Encoded symbol, `true` is a dark module
+pub enum Symbol {
+    /// Bars left to right, with the text printed under them
+    Linear { modules: Vec<bool>, text: String },
+    /// Square matrix, row by row
+    Matrix { width: usize, modules: Vec<bool> },
+}
+
+const EAN_L: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011", "0110111", "0001011",
+];
+
+/// Parity of the left half, selected by the first digit (L = odd, G = even)
+const EAN_PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL", "LGGLGL",
+];
+
+fn push_bits(modules: &mut Vec<bool>, bits: impl Iterator<Item = char>) {
+    modules.extend(bits.map(|bit| bit == '1'));
+}
+
+/// EAN-13 from a code with a valid check digit, UPC-A is printed as EAN-13 with a leading 0
+pub fn ean13(code: &str) -> Option<Symbol> {
+    let code = match gtin_format(code)? {
+        GtinFormat::Ean13 => code.to_string(),
+        GtinFormat::UpcA => format!("0{}", code),
+        _ => return None,
+    };
+    let digits: Vec<usize> = code.chars().map(|c| c.to_digit(10).unwrap() as usize).collect();
+    let mut modules: Vec<bool> = Vec::with_capacity(95);
+    push_bits(&mut modules, "101".chars());
+    for (digit, parity) in digits[1..7].iter().zip(EAN_PARITY[digits[0]].chars()) {
+        let l = EAN_L[*digit];
+        match parity {
+            // G is the mirrored complement of L
+            'G' => push_bits(&mut modules, l.chars().rev().map(|b| if b == '1' { '0' } else { '1' })),
+            _ => push_bits(&mut modules, l.chars()),
+        }
+    }
+    push_bits(&mut modules, "01010".chars());
+    for digit in digits[7..].iter() {
+        push_bits(&mut modules, EAN_L[*digit].chars().map(|b| if b == '1' { '0' } else { '1' }));
+    }
+    push_bits(&mut modules, "101".chars());
+    Some(Symbol::Linear { modules, text: code })
+}
+
+/// Bar and space widths of the Code 128 symbols, 103-105 are the start codes, 106 is stop
+const CODE128: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212", "221213",
+    "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221", "223211", "221132",
+    "221231", "213212", "223112", "312131", "311222", "321122", "321221", "312212", "322112", "322211",
+    "212123", "212321", "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121", "313121", "211331",
+    "231131", "213113", "213311", "213131", "311123", "311321", "331121", "312113", "312311", "332111",
+    "314111", "221411", "431111", "111224", "111422", "121124", "121421", "141122", "141221", "112214",
+    "112412", "122114", "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311", "113141",
+    "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: usize = 106;
+
+/// Code 128 in code set B (printable ASCII)
+pub fn code128(text: &str) -> Option<Symbol> {
+    if text.is_empty() || !text.chars().all(|c| (' '..='~').contains(&c)) {
+        return None;
+    }
+    let mut values: Vec<usize> = vec![CODE128_START_B];
+    values.extend(text.chars().map(|c| c as usize - 32));
+    let checksum = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| i.max(1) * value)
+        .sum::<usize>()
+        % 103;
+    values.push(checksum);
+    values.push(CODE128_STOP);
+
+    let mut modules: Vec<bool> = Vec::new();
+    for value in values {
+        for (i, width) in CODE128[value].chars().enumerate() {
+            let width = width.to_digit(10).unwrap() as usize;
+            modules.extend(std::iter::repeat(i % 2 == 0).take(width));
+        }
+    }
+    Some(Symbol::Linear {
+        modules,
+        text: text.to_string(),
+    })
+}
+
+pub fn qr(text: &str) -> Option<Symbol> {
+    let code = QrCode::with_error_correction_level(text.as_bytes(), EcLevel::M).ok()?;
+    Some(Symbol::Matrix {
+        width: code.width(),
+        modules: code.to_colors().into_iter().map(|color| color == Color::Dark).collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn linear(symbol: Option<Symbol>) -> (String, String) {
+        match symbol {
+            Some(Symbol::Linear { modules, text }) => {
+                (modules.iter().map(|dark| if *dark { '1' } else { '0' }).collect(), text)
+            }
+            _ => panic!("expected a linear symbol"),
+        }
+    }
+
+    #[test]
+    fn test_ean13() {
+        let (modules, text) = linear(ean13("4006381333931"));
+        assert_eq!(text, "4006381333931");
+        assert_eq!(modules.len(), 95);
+        // Start guard, then 0 in G parity (first digit 4 is LGLLGG) and 0 in L parity
+        assert!(modules.starts_with(&format!("101{}{}", "0001101", "0100111")));
+        assert!(modules.ends_with("101"));
+
+        let (_, text) = linear(ean13("036000291452"));
+        assert_eq!(text, "0036000291452");
+        assert!(ean13("4006381333932").is_none());
+        assert!(ean13("96385074").is_none());
+    }
+
+    #[test]
+    fn test_code128() {
+        let unique: HashSet<&str> = CODE128.iter().copied().collect();
+        assert_eq!(unique.len(), CODE128.len());
+        for (value, pattern) in CODE128.iter().enumerate() {
+            let width: u32 = pattern.chars().map(|c| c.to_digit(10).unwrap()).sum();
+            assert_eq!(width, if value == CODE128_STOP { 13 } else { 11 });
+        }
+
+        // Start B, 'A' (33), checksum (104 + 33) % 103 = 34, stop
+        let (modules, text) = linear(code128("A"));
+        assert_eq!(text, "A");
+        assert_eq!(modules.len(), 11 * 3 + 13);
+        assert!(modules.starts_with("11010010000"));
+        assert!(modules.ends_with("1100011101011"));
+        assert!(code128("").is_none());
+        assert!(code128("é").is_none());
+    }
+
+    #[test]
+    fn test_qr() {
+        match qr("6251234567892") {
+            Some(Symbol::Matrix { width, modules }) => {
+                assert_eq!(width, 21);
+                assert_eq!(modules.len(), 21 * 21);
+                // Finder pattern in the top left corner
+                assert!(modules[0..7].iter().all(|dark| *dark));
+            }
+            _ => panic!("expected a matrix symbol"),
+        }
+    }
+}
@@ -1,8 +1,16 @@
+pub mod counts;
 pub mod stores;
+pub mod transfers;
 pub mod files;
+pub mod labels;
+pub mod history;
+pub mod jobs;
 pub mod logs;
 pub mod permissions;
 pub mod products;
+pub mod reference;
+pub mod reports;
 pub mod authentication;
 pub mod users;
-pub mod versions;
\ No newline at end of file
+pub mod versions;
+pub mod watchlist;
\ No newline at end of file
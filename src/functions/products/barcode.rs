@@ -0,0 +1,202 @@
+use crate::functions::products::structs::{BarcodeCheck, GtinFormat};
+
+/// Split the concatenated BARCODE_LISTED column into individual barcodes.
+/// Any non alphanumeric character is treated as a separator, duplicates are dropped.
+pub fn parse_barcodes(listed: &str) -> Vec<String> {
+    let mut barcodes: Vec<String> = Vec::new();
+    for barcode in listed.split(|c: char| !c.is_ascii_alphanumeric()) {
+        if !barcode.is_empty() && !barcodes.iter().any(|b| b == barcode) {
+            barcodes.push(barcode.to_string());
+        }
+    }
+    barcodes
+}
+
+/// Drop what scanners add around a code (spaces, dashes, prefixes/suffixes like CR),
+/// a barcode token only holds alphanumeric characters
+pub fn normalize(code: &str) -> String {
+    code.chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+/// GS1 mod 10 check digit for the digits before it
+pub fn check_digit(body: &str) -> Option<u32> {
+    if body.is_empty() || !body.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let sum: u32 = body
+        .chars()
+        .rev()
+        .enumerate()
+        .map(|(i, c)| c.to_digit(10).unwrap() * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    Some((10 - sum % 10) % 10)
+}
+
+/// Format of a code with a correct check digit, None for anything else
+pub fn gtin_format(code: &str) -> Option<GtinFormat> {
+    let format = match code.len() {
+        8 => GtinFormat::Ean8,
+        12 => GtinFormat::UpcA,
+        13 => GtinFormat::Ean13,
+        14 => GtinFormat::Gtin14,
+        _ => return None,
+    };
+    let (body, check) = code.split_at(code.len() - 1);
+    match (check_digit(body), check.chars().next().and_then(|c| c.to_digit(10))) {
+        (Some(expected), Some(actual)) if expected == actual => Some(format),
+        _ => None,
+    }
+}
+
+fn zero_pad(code: &str, len: usize) -> String {
+    format!("{:0>width$}", code, width = len)
+}
+
+/// Codes a scan can be stored as, the normalized scan first.
+/// A valid GTIN adds the same number as UPC-A, EAN-13 and GTIN-14 (leading zeros)
+/// and the scan without its check digit, unless that is a valid GTIN of another item.
+/// Other numeric scans try the leading zero a scanner drops and a missing check digit.
+pub fn variants(code: &str) -> Vec<String> {
+    let code = normalize(code);
+    if code.is_empty() {
+        return Vec::new();
+    }
+    let mut variants: Vec<String> = vec![code.clone()];
+    if !code.chars().all(|c| c.is_ascii_digit()) {
+        return variants;
+    }
+
+    let mut gtins: Vec<String> = Vec::new();
+    if gtin_format(&code).is_some() {
+        gtins.push(code.clone());
+        // Only stored codes that aren't GTINs can be missing their check digit
+        let stripped = &code[..code.len() - 1];
+        if gtin_format(stripped).is_none() {
+            variants.push(stripped.to_string());
+        }
+    } else {
+        let padded = format!("0{}", code);
+        if gtin_format(&padded).is_some() {
+            gtins.push(padded);
+        }
+        if let Some(check) = check_digit(&code) {
+            let completed = format!("{}{}", code, check);
+            if gtin_format(&completed).is_some() {
+                gtins.push(completed);
+            }
+        }
+    }
+
+    for gtin in gtins {
+        let significant = gtin.trim_start_matches('0');
+        // GTIN-8 is only equivalent to the longer forms when scanned as such
+        let lengths: &[usize] = if gtin.len() == 8 { &[8, 12, 13, 14] } else { &[12, 13, 14] };
+        for len in lengths {
+            if significant.len() <= *len {
+                variants.push(zero_pad(significant, *len));
+            }
+        }
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+    for variant in variants {
+        if !unique.contains(&variant) {
+            unique.push(variant);
+        }
+    }
+    unique
+}
+
+pub fn is_main_barcode(variants: &[String], main_barcode: Option<&str>) -> bool {
+    main_barcode.map(|main| variants.iter().any(|v| v == main.trim())).unwrap_or(false)
+}
+
+/// Exact match on the main barcode or a whole token of BARCODE_LISTED
+pub fn matches(variants: &[String], main_barcode: Option<&str>, listed: Option<&str>) -> bool {
+    is_main_barcode(variants, main_barcode)
+        || parse_barcodes(listed.unwrap_or("")).iter().any(|barcode| variants.contains(barcode))
+}
+
+/// SQL condition for `matches`, with binds named `barcode_<i>` and `barcode_pattern`.
+/// Variants are alphanumeric only, so they are safe inside the regular expression.
+pub fn sql_condition(variants: &[String]) -> (String, Vec<(String, String)>) {
+    let mut binds: Vec<(String, String)> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    for (i, variant) in variants.iter().enumerate() {
+        let name = format!("barcode_{}", i);
+        names.push(format!(":{}", name));
+        binds.push((name, variant.clone()));
+    }
+    binds.push((
+        "barcode_pattern".to_string(),
+        format!("(^|[^[:alnum:]])({})($|[^[:alnum:]])", variants.join("|")),
+    ));
+    let condition = format!(
+        "(ITEM_MAIN_BARCODE IN ({}) OR REGEXP_LIKE(BARCODE_LISTED, :barcode_pattern))",
+        names.join(", ")
+    );
+    (condition, binds)
+}
+
+pub fn check(code: &str) -> BarcodeCheck {
+    let normalized = normalize(code);
+    let format = gtin_format(&normalized);
+    BarcodeCheck {
+        VARIANTS: variants(&normalized),
+        VALID: format.is_some(),
+        FORMAT: format,
+        CODE: normalized,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_barcodes() {
+        assert_eq!(parse_barcodes("6251234567892, 012345678905;6251234567892"), vec!["6251234567892", "012345678905"]);
+        assert_eq!(parse_barcodes(" | "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_gtin_format() {
+        assert_eq!(gtin_format("96385074"), Some(GtinFormat::Ean8));
+        assert_eq!(gtin_format("036000291452"), Some(GtinFormat::UpcA));
+        assert_eq!(gtin_format("4006381333931"), Some(GtinFormat::Ean13));
+        assert_eq!(gtin_format("10036000291459"), Some(GtinFormat::Gtin14));
+        assert_eq!(gtin_format("4006381333932"), None);
+        assert_eq!(gtin_format("123"), None);
+        assert_eq!(gtin_format("40063813339A1"), None);
+    }
+
+    #[test]
+    fn test_variants() {
+        // UPC-A and its EAN-13 / GTIN-14 forms, plus the code without check digit
+        assert_eq!(
+            variants(" 0360-0029-1452\r"),
+            vec!["036000291452", "03600029145", "0036000291452", "00036000291452"]
+        );
+        // Leading zero dropped by the scanner, or the check digit left out
+        let dropped = variants("36000291452");
+        assert!(dropped.contains(&"036000291452".to_string()));
+        assert!(dropped.contains(&"360002914522".to_string()));
+        // The body of this EAN-13 is a valid UPC-A, another item's barcode
+        assert!(!variants("0360002914522").contains(&"036000291452".to_string()));
+        // Short codes only match themselves
+        assert_eq!(variants("123"), vec!["123"]);
+        assert_eq!(variants("AB-12"), vec!["AB12"]);
+        assert!(variants("  ").is_empty());
+    }
+
+    #[test]
+    fn test_matches() {
+        let scan = variants("0036000291452");
+        assert!(matches(&scan, Some("036000291452"), None));
+        assert!(is_main_barcode(&scan, Some("036000291452")));
+        assert!(matches(&scan, Some("1"), Some("6251234567892, 36000291452, 036000291452")));
+        assert!(!is_main_barcode(&scan, Some("1")));
+        assert!(!matches(&variants("123"), None, Some("6251234567892, 41234")));
+        assert!(!matches(&variants("0360002914522"), Some("036000291452"), None));
+    }
+}
@@ -0,0 +1,302 @@
+#![allow(non_snake_case)]
+use std::sync::RwLock;
+
+use chrono::{DateTime, Local};
+use oracle::pool::Pool;
+use serde::Serialize;
+
+use crate::functions::products::barcode;
+use crate::functions::products::search;
+use crate::functions::products::structs::FetchParams;
+use crate::utils::env::env_number;
+use crate::utils::structs::APIErrors;
+
+/// Product master data kept in memory, per-store values are never indexed
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexEntry {
+    pub ITEM_ID: String,
+    pub ITEM_DESC: Option<String>,
+    pub ITEM_DESC_S: Option<String>,
+    pub FOREIGN_ITEM_CODE: Option<String>,
+    pub ITEM_MAIN_BARCODE: Option<String>,
+    pub BARCODE_LISTED: Option<String>,
+    pub ITEM_CAT: Option<String>,
+    pub ITEM_SUB_CAT: Option<String>,
+    #[serde(skip)]
+    norm_desc: String,
+    #[serde(skip)]
+    norm_desc_s: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexStatus {
+    pub enabled: bool,
+    pub entries: usize,
+    pub built_at: Option<String>,
+}
+
+#[derive(Default)]
+struct IndexData {
+    entries: Vec<IndexEntry>,
+    built_at: Option<DateTime<Local>>,
+}
+
+pub struct ProductIndex {
+    pub enabled: bool,
+    pub refresh_minutes: u64,
+    data: RwLock<IndexData>,
+}
+
+/// Matches a value the way the SQL query does, LIKE when the param starts or ends with %
+fn param_matches(param: &str, value: Option<&str>) -> bool {
+    let value = value.unwrap_or("");
+    if param.starts_with('%') || param.ends_with('%') {
+        like_match(param, value)
+    } else {
+        param == value
+    }
+}
+
+/// Oracle LIKE semantics for % and _, case sensitive
+fn like_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    // matches[j] is true when pattern[..i] matches value[..j]
+    let mut matches = vec![false; value.len() + 1];
+    matches[0] = true;
+    for p in pattern {
+        let mut next = vec![false; value.len() + 1];
+        match p {
+            '%' => {
+                let mut reached = false;
+                for j in 0..=value.len() {
+                    reached |= matches[j];
+                    next[j] = reached;
+                }
+            }
+            _ => {
+                for j in 1..=value.len() {
+                    next[j] = matches[j - 1] && (p == '_' || p == value[j - 1]);
+                }
+            }
+        }
+        matches = next;
+    }
+    matches[value.len()]
+}
+
+impl ProductIndex {
+    pub fn from_env() -> ProductIndex {
+        let enabled = std::env::var("PRODUCT_INDEX_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        let refresh_minutes = env_number("PRODUCT_INDEX_REFRESH_MINUTES", 15);
+        ProductIndex {
+            enabled,
+            refresh_minutes,
+            data: RwLock::new(IndexData::default()),
+        }
+    }
+
+    /// Reload every product from the DB, the previous index keeps serving until the swap
+    pub fn rebuild(&self, pool: &Pool, sql: &str) -> Result<usize, APIErrors> {
+        let now = std::time::Instant::now();
+        let conn = pool.get();
+        if conn.is_err() {
+            error!("Error connecting to DB");
+            return Err(APIErrors::DBError);
+        }
+        let conn = conn.unwrap();
+
+        let stmt = conn.statement(sql).fetch_array_size(1000).build();
+        if stmt.is_err() {
+            error!("Error building statement");
+            return Err(APIErrors::DBError);
+        }
+        let mut stmt = stmt.unwrap();
+
+        let rows = stmt.query(&[]);
+        if rows.is_err() {
+            error!("Error executing query");
+            return Err(APIErrors::DBError);
+        }
+        let rows = rows.unwrap();
+
+        let mut entries: Vec<IndexEntry> = Vec::new();
+        for row_result in rows {
+            if row_result.is_err() {
+                error!("Error fetching row");
+                return Err(APIErrors::DBError);
+            }
+            let row = row_result.unwrap();
+            let item_desc: Option<String> = row.get("ITEM_DESC").unwrap_or(None);
+            let item_desc_s: Option<String> = row.get("ITEM_DESC_S").unwrap_or(None);
+            entries.push(IndexEntry {
+                ITEM_ID: row.get("ITEM_ID").unwrap_or_default(),
+                norm_desc: search::normalize(item_desc.as_deref().unwrap_or("")),
+                norm_desc_s: search::normalize(item_desc_s.as_deref().unwrap_or("")),
+                ITEM_DESC: item_desc,
+                ITEM_DESC_S: item_desc_s,
+                FOREIGN_ITEM_CODE: row.get("FOREIGN_ITEM_CODE").unwrap_or(None),
+                ITEM_MAIN_BARCODE: row.get("ITEM_MAIN_BARCODE").unwrap_or(None),
+                BARCODE_LISTED: row.get("BARCODE_LISTED").unwrap_or(None),
+                ITEM_CAT: row.get("ITEM_CAT").unwrap_or(None),
+                ITEM_SUB_CAT: row.get("ITEM_SUB_CAT").unwrap_or(None),
+            });
+        }
+
+        let count = entries.len();
+        let mut data = self.data.write().map_err(|_| APIErrors::InternalServerError)?;
+        data.entries = entries;
+        data.built_at = Some(Local::now());
+        info!("Product index rebuilt with {} items in {} ms", count, now.elapsed().as_millis());
+        Ok(count)
+    }
+
+    pub fn status(&self) -> IndexStatus {
+        let data = self.data.read();
+        match data {
+            Ok(data) => IndexStatus {
+                enabled: self.enabled,
+                entries: data.entries.len(),
+                built_at: data.built_at.map(|t| t.to_rfc3339()),
+            },
+            Err(_) => IndexStatus {
+                enabled: self.enabled,
+                entries: 0,
+                built_at: None,
+            },
+        }
+    }
+
+    /// Item ids matching the fetch params, None when the index can't answer
+    /// (disabled or not built yet) and the DB has to be queried instead
+    pub fn lookup(&self, params: &FetchParams) -> Option<Vec<String>> {
+        if !self.enabled {
+            return None;
+        }
+        let data = self.data.read().ok()?;
+        data.built_at?;
+
+        let search_tokens = params.p_search.as_deref().map(search::tokenize).unwrap_or_default();
+        // Same as the SQL query: whole barcodes, or a partial match when wildcarded
+        let barcode_pattern = params
+            .p_barcode
+            .as_ref()
+            .filter(|b| b.starts_with('%') || b.ends_with('%'))
+            .map(|b| format!("%{}", b));
+        let barcode_variants = params
+            .p_barcode
+            .as_ref()
+            .filter(|_| barcode_pattern.is_none())
+            .map(|b| barcode::variants(b));
+
+        let mut matches: Vec<(u32, &IndexEntry)> = Vec::new();
+        for entry in data.entries.iter() {
+            if let Some(p_ref) = &params.p_ref {
+                if !param_matches(p_ref, entry.FOREIGN_ITEM_CODE.as_deref()) {
+                    continue;
+                }
+            }
+            if let Some(p_id) = &params.p_id {
+                if !param_matches(p_id, Some(&entry.ITEM_ID)) {
+                    continue;
+                }
+            }
+            if let Some(p_desc) = &params.p_desc {
+                if !param_matches(p_desc, entry.ITEM_DESC_S.as_deref()) {
+                    continue;
+                }
+            }
+            if let Some(pattern) = &barcode_pattern {
+                if !like_match(pattern, entry.BARCODE_LISTED.as_deref().unwrap_or("")) {
+                    continue;
+                }
+            }
+            if let Some(variants) = &barcode_variants {
+                if !barcode::matches(variants, entry.ITEM_MAIN_BARCODE.as_deref(), entry.BARCODE_LISTED.as_deref()) {
+                    continue;
+                }
+            }
+            let mut score = 0;
+            if !search_tokens.is_empty() {
+                match search::score_normalized(&search_tokens, &entry.norm_desc, &entry.norm_desc_s) {
+                    Some(s) => score = s,
+                    None => continue,
+                }
+            }
+            matches.push((score, entry));
+        }
+
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score).then_with(|| a.ITEM_ID.cmp(&b.ITEM_ID))
+        });
+        Some(matches.into_iter().map(|(_, entry)| entry.ITEM_ID.clone()).collect())
+    }
+
+    /// Type-ahead on item code, foreign code, main barcode and description words,
+    /// codes matching the query exactly come first, then code prefixes, then descriptions
+    pub fn autocomplete(&self, query: &str, limit: usize) -> Option<Vec<IndexEntry>> {
+        if !self.enabled {
+            return None;
+        }
+        let data = self.data.read().ok()?;
+        data.built_at?;
+
+        let code = query.trim().to_uppercase();
+        let tokens = search::tokenize(query);
+        if code.is_empty() || tokens.is_empty() {
+            return Some(Vec::new());
+        }
+
+        let mut matches: Vec<(u8, &IndexEntry)> = Vec::new();
+        for entry in data.entries.iter() {
+            let codes = [
+                Some(entry.ITEM_ID.as_str()),
+                entry.FOREIGN_ITEM_CODE.as_deref(),
+                entry.ITEM_MAIN_BARCODE.as_deref(),
+            ];
+            let codes = codes.iter().flatten().map(|c| c.to_uppercase());
+            let mut tier = None;
+            for c in codes {
+                if c == code {
+                    tier = Some(0);
+                    break;
+                } else if c.starts_with(&code) {
+                    tier = Some(1);
+                }
+            }
+            if tier.is_none() {
+                let words = entry.norm_desc.split_whitespace().chain(entry.norm_desc_s.split_whitespace());
+                let words: Vec<&str> = words.collect();
+                if tokens.iter().all(|t| words.iter().any(|w| w.starts_with(t.as_str()))) {
+                    tier = Some(2);
+                }
+            }
+            if let Some(tier) = tier {
+                matches.push((tier, entry));
+            }
+        }
+
+        matches.sort_by(|(a_tier, a), (b_tier, b)| {
+            a_tier.cmp(b_tier).then_with(|| a.ITEM_ID.cmp(&b.ITEM_ID))
+        });
+        Some(matches.into_iter().take(limit).map(|(_, entry)| entry.clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_like_match() {
+        assert!(like_match("%123%", "0001234"));
+        assert!(like_match("12%", "1234"));
+        assert!(like_match("1_3", "123"));
+        assert!(!like_match("%124", "1234"));
+        assert!(param_matches("ABC", Some("ABC")));
+        assert!(!param_matches("AB", Some("ABC")));
+        assert!(param_matches("AB%", Some("ABC")));
+    }
+}
@@ -1,15 +1,31 @@
 #[allow(non_snake_case)]
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::sync::Arc;
 
 use oracle::pool::Pool;
-use oracle::Row;
+use oracle::{Connection, Row};
 
+use oracle::sql_type::Timestamp;
 use oracle::sql_type::ToSql;
 use rocket::log::private::info;
 use rocket::serde::json::Json;
 
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+use crate::functions::products::index::ProductIndex;
+use crate::functions::products::pricing::PricingConfig;
 use crate::functions::products::structs::FetchParams;
 use crate::functions::products::structs::Product;
+use crate::functions::products::structs::ProductDetail;
+use crate::functions::products::structs::ProductSuggestion;
+use crate::functions::products::structs::StoreBreakdown;
+use crate::functions::products::structs::QTY_STORES;
+
+use crate::functions::files::image_exists;
+use crate::functions::files::store::ImageStore;
 
 use crate::functions::stores::get_stores;
 
@@ -20,21 +36,205 @@ use crate::utils::permissions::is_cost_perm;
 use crate::utils::sql::SQLManager;
 use crate::utils::structs::APIErrors;
 
+pub mod barcode;
+pub mod index;
+pub mod pricing;
+pub mod search;
 pub mod structs;
 
-#[allow(unused_assignments)]
-pub async fn get_product(
-    params: Json<FetchParams>,
-    pool: &Pool,
-    sql_manager: &SQLManager,
-    key: &ApiKey<'_>,
-) -> Result<Vec<Product>, APIErrors> {
-    // Empty params are not an error, but they should return an empty vec
-    if params.is_none() {
-        println!("Empty params");
-        return Ok(Vec::new());
+/// Read a NUMBER column as an exact decimal, NULL or unparseable values become None
+pub(crate) fn get_decimal(row: &Row, column_name: &str) -> Option<Decimal> {
+    let value: String = row.get(column_name).ok()?;
+    parse_decimal(&value)
+}
+
+/// Oracle may return numbers like ".5" or "1E-7", both are accepted here
+fn parse_decimal(value: &str) -> Option<Decimal> {
+    let value = value.trim();
+    let value = match value.strip_prefix('.') {
+        Some(fraction) => format!("0.{}", fraction),
+        None => value.replace("-.", "-0."),
+    };
+    Decimal::from_str(&value)
+        .or_else(|_| Decimal::from_scientific(&value))
+        .ok()
+}
+
+/// Read a DATE column, the time part is dropped
+fn get_date(row: &Row, column_name: &str) -> Option<NaiveDate> {
+    let value: Timestamp = row.get(column_name).ok()?;
+    NaiveDate::from_ymd_opt(value.year(), value.month(), value.day())
+}
+
+// Helper function to get value from row and check if store is in store_ids
+fn get_value(
+    store_ids: &HashSet<String>,
+    row: &Row,
+    store_id: &str,
+    column_name: &str,
+) -> Option<Decimal> {
+    if store_ids.contains(&store_id.to_string()) {
+        get_decimal(row, column_name)
+    } else {
+        None
     }
+}
 
+/// Map a JHC_INVDATA row, store columns outside `store_ids` and the cost are left empty
+pub(crate) fn product_from_row(row: &Row, store_ids: &HashSet<String>, show_cost: bool) -> Product {
+    Product {
+        ITEM_ID: row.get("ITEM_ID").unwrap(),
+        IS_ACTIVE: row.get("IS_ACTIVE").unwrap(),
+        CAN_BE_SOLD: row.get("CAN_BE_SOLD").unwrap(),
+        ITEM_DESC: row.get("ITEM_DESC").unwrap(),
+        ITEM_DESC_S: row.get("ITEM_DESC_S").unwrap(),
+        FOREIGN_ITEM_CODE: row.get("FOREIGN_ITEM_CODE").unwrap(),
+        ITEM_CAT: row.get("ITEM_CAT").unwrap(),
+        ITEM_SUB_CAT: row.get("ITEM_SUB_CAT").unwrap(),
+        SALE_UNIT: row.get("SALE_UNIT").unwrap(),
+        UNIT_DESC: row.get("UNIT_DESC").unwrap(),
+        PACKING: row.get("PACKING").unwrap(),
+        CARD_OPEN_DATE: get_date(row, "CARD_OPEN_DATE"),
+        HS_CODE: row.get("HS_CODE").unwrap(),
+        COUNTRY: row.get("COUNTRY").unwrap(),
+        COUNTRY_DESC: row.get("COUNTRY_DESC").unwrap(),
+        SUPPLIER_ID: row.get("SUPPLIER_ID").unwrap(),
+        SUPPLIER_DESC: row.get("SUPPLIER_DESC").unwrap(),
+        ITEM_MAIN_BARCODE: row.get("ITEM_MAIN_BARCODE").unwrap(),
+        NATURE_ID: row.get("NATURE_ID").unwrap(),
+        NATURE_DESC: row.get("NATURE_DESC").unwrap(),
+        TRADE_ID: row.get("TRADE_ID").unwrap(),
+        TRADE_DESC: row.get("TRADE_DESC").unwrap(),
+        QTY_STORE_01: get_value(store_ids, row, "01", "QTY_STORE_01"),
+        QTY_STORE_02: get_value(store_ids, row, "02", "QTY_STORE_02"),
+        QTY_STORE_05: get_value(store_ids, row, "05", "QTY_STORE_05"),
+        QTY_STORE_06: get_value(store_ids, row, "06", "QTY_STORE_06"),
+        QTY_STORE_07: get_value(store_ids, row, "07", "QTY_STORE_07"),
+        QTY_STORE_08: get_value(store_ids, row, "08", "QTY_STORE_08"),
+        QTY_STORE_09: get_value(store_ids, row, "09", "QTY_STORE_09"),
+        QTY_STORE_10: get_value(store_ids, row, "10", "QTY_STORE_10"),
+        QTY_STORE_11: get_value(store_ids, row, "11", "QTY_STORE_11"),
+        QTY_STORE_12: get_value(store_ids, row, "12", "QTY_STORE_12"),
+        QTY_STORE_19: get_value(store_ids, row, "19", "QTY_STORE_19"),
+        QTY_STORE_21: get_value(store_ids, row, "21", "QTY_STORE_21"),
+        QTY_STORE_23: get_value(store_ids, row, "23", "QTY_STORE_23"),
+        QTY_STORE_31: get_value(store_ids, row, "31", "QTY_STORE_31"),
+        QTY_STORE_32: get_value(store_ids, row, "32", "QTY_STORE_32"),
+        QTY_STORE_33: get_value(store_ids, row, "33", "QTY_STORE_33"),
+        QTY_STORE_34: get_value(store_ids, row, "34", "QTY_STORE_34"),
+        QTY_STORE_35: get_value(store_ids, row, "35", "QTY_STORE_35"),
+        SALE_PRICE_NOTAX_STORE_01: get_value(
+            &store_ids,
+            &row,
+            "01",
+            "SALE_PRICE_NOTAX_STORE_01",
+        ),
+        SALE_PRICE_NOTAX_STORE_02: get_value(
+            &store_ids,
+            &row,
+            "02",
+            "SALE_PRICE_NOTAX_STORE_02",
+        ),
+        SALE_PRICE_NOTAX_STORE_05: get_value(
+            &store_ids,
+            &row,
+            "05",
+            "SALE_PRICE_NOTAX_STORE_05",
+        ),
+        SALE_PRICE_NOTAX_STORE_06: get_value(
+            &store_ids,
+            &row,
+            "06",
+            "SALE_PRICE_NOTAX_STORE_06",
+        ),
+        SALE_PRICE_NOTAX_STORE_08: get_value(
+            &store_ids,
+            &row,
+            "08",
+            "SALE_PRICE_NOTAX_STORE_08",
+        ),
+        SALE_PRICE_NOTAX_STORE_09: get_value(
+            &store_ids,
+            &row,
+            "09",
+            "SALE_PRICE_NOTAX_STORE_09",
+        ),
+        SALE_PRICE_NOTAX_STORE_07: get_value(
+            &store_ids,
+            &row,
+            "07",
+            "SALE_PRICE_NOTAX_STORE_07",
+        ),
+        SALE_PRICE_NOTAX_STORE_31: get_value(
+            &store_ids,
+            &row,
+            "31",
+            "SALE_PRICE_NOTAX_STORE_31",
+        ),
+        SALE_PRICE_NOTAX_STORE_32: get_value(
+            &store_ids,
+            &row,
+            "32",
+            "SALE_PRICE_NOTAX_STORE_32",
+        ),
+        SALE_PRICE_NOTAX_STORE_33: get_value(
+            &store_ids,
+            &row,
+            "33",
+            "SALE_PRICE_NOTAX_STORE_33",
+        ),
+        SALE_PRICE_NOTAX_STORE_34: get_value(
+            &store_ids,
+            &row,
+            "34",
+            "SALE_PRICE_NOTAX_STORE_34",
+        ),
+        SALE_PRICE_NOTAX_STORE_35: get_value(
+            &store_ids,
+            &row,
+            "35",
+            "SALE_PRICE_NOTAX_STORE_35",
+        ),
+        FIRST_DISC_PER_STORE_01: get_value(store_ids, row, "01", "FIRST_DISC_PER_STORE_01"),
+        FIRST_DISC_PER_STORE_02: get_value(store_ids, row, "02", "FIRST_DISC_PER_STORE_02"),
+        FIRST_DISC_PER_STORE_05: get_value(store_ids, row, "05", "FIRST_DISC_PER_STORE_05"),
+        FIRST_DISC_PER_STORE_06: get_value(store_ids, row, "06", "FIRST_DISC_PER_STORE_06"),
+        FIRST_DISC_PER_STORE_07: get_value(store_ids, row, "07", "FIRST_DISC_PER_STORE_07"),
+        FIRST_DISC_PER_STORE_08: get_value(store_ids, row, "08", "FIRST_DISC_PER_STORE_08"),
+        FIRST_DISC_PER_STORE_09: get_value(store_ids, row, "09", "FIRST_DISC_PER_STORE_09"),
+        FIRST_DISC_PER_STORE_31: get_value(store_ids, row, "31", "FIRST_DISC_PER_STORE_31"),
+        FIRST_DISC_PER_STORE_32: get_value(store_ids, row, "32", "FIRST_DISC_PER_STORE_32"),
+        FIRST_DISC_PER_STORE_33: get_value(store_ids, row, "33", "FIRST_DISC_PER_STORE_33"),
+        FIRST_DISC_PER_STORE_34: get_value(store_ids, row, "34", "FIRST_DISC_PER_STORE_34"),
+        FIRST_DISC_PER_STORE_35: get_value(store_ids, row, "35", "FIRST_DISC_PER_STORE_35"),
+        SECOND_DISC_PER_STORE_01: get_value(store_ids, row, "01", "SECOND_DISC_PER_STORE_01"),
+        SECOND_DISC_PER_STORE_02: get_value(store_ids, row, "02", "SECOND_DISC_PER_STORE_02"),
+        SECOND_DISC_PER_STORE_05: get_value(store_ids, row, "05", "SECOND_DISC_PER_STORE_05"),
+        SECOND_DISC_PER_STORE_06: get_value(store_ids, row, "06", "SECOND_DISC_PER_STORE_06"),
+        SECOND_DISC_PER_STORE_07: get_value(store_ids, row, "07", "SECOND_DISC_PER_STORE_07"),
+        SECOND_DISC_PER_STORE_08: get_value(store_ids, row, "08", "SECOND_DISC_PER_STORE_08"),
+        SECOND_DISC_PER_STORE_09: get_value(store_ids, row, "09", "SECOND_DISC_PER_STORE_09"),
+        SECOND_DISC_PER_STORE_31: get_value(store_ids, row, "31", "SECOND_DISC_PER_STORE_31"),
+        SECOND_DISC_PER_STORE_32: get_value(store_ids, row, "32", "SECOND_DISC_PER_STORE_32"),
+        SECOND_DISC_PER_STORE_33: get_value(store_ids, row, "33", "SECOND_DISC_PER_STORE_33"),
+        SECOND_DISC_PER_STORE_34: get_value(store_ids, row, "34", "SECOND_DISC_PER_STORE_34"),
+        SECOND_DISC_PER_STORE_35: get_value(store_ids, row, "35", "SECOND_DISC_PER_STORE_35"),
+        T_AVE_COST: if show_cost {
+            get_decimal(row, "T_AVE_COST")
+        } else {
+            None
+        },
+        NET_PRICES: None,
+        MAIN_BARCODE_MATCH: None,
+    }
+}
+
+/// Stores the token's user has access to
+pub(crate) async fn get_user_store_ids(
+    key: &ApiKey<'_>,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+) -> Result<HashSet<String>, APIErrors> {
     // Get username from token
     let username: String;
 
@@ -63,22 +263,56 @@ pub async fn get_product(
             return Err(e);
         }
     }
+    Ok(store_ids)
+}
+
+#[allow(unused_assignments)]
+pub async fn get_product(
+    params: Json<FetchParams>,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    pricing: &PricingConfig,
+    index: &ProductIndex,
+    key: &ApiKey<'_>,
+) -> Result<Vec<Product>, APIErrors> {
+    // Empty params are not an error, but they should return an empty vec
+    if params.is_none() {
+        println!("Empty params");
+        return Ok(Vec::new());
+    }
+
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
 
     // To call the function once, otherwise will call on each product found, and touch DB every time
     let show_cost = is_cost_perm(key, pool, &sql_manager).await;
+    let with_tax = params.p_with_tax.unwrap_or(false);
 
-    // Helper function to get value from row and check if store is in store_ids
-    fn get_value(
-        store_ids: &HashSet<String>,
-        row: &Row,
-        store_id: &str,
-        column_name: &str,
-    ) -> Option<String> {
-        if store_ids.contains(&store_id.to_string()) {
-            row.get(column_name).ok()
-        } else {
-            None
+    // The index answers the lookup when enabled, stock and prices are still read live
+    // Scans match whole barcodes, a `%` at either end keeps the old partial match
+    let barcode_variants: Option<Vec<String>> = match &params.p_barcode {
+        Some(p_barcode) if !(p_barcode.starts_with("%") || p_barcode.ends_with("%")) => {
+            let variants = barcode::variants(p_barcode);
+            if variants.is_empty() {
+                return Err(APIErrors::InvalidData);
+            }
+            Some(variants)
         }
+        _ => None,
+    };
+    let set_barcode_match = |product: &mut Product| {
+        if let Some(variants) = &barcode_variants {
+            product.MAIN_BARCODE_MATCH = Some(barcode::is_main_barcode(variants, product.ITEM_MAIN_BARCODE.as_deref()));
+        }
+    };
+
+    if let Some(item_ids) = index.lookup(&params) {
+        info!("Index lookup matched {} items", item_ids.len());
+        let mut products = get_products_by_ids(&item_ids, pool, &store_ids, show_cost)?;
+        for product in products.iter_mut() {
+            product.NET_PRICES = Some(pricing.net_prices(product, with_tax));
+            set_barcode_match(product);
+        }
+        return Ok(products);
     }
 
     fn add_param(sql: &mut String, param_count: &mut i32, column_name: &str, param: &str) {
@@ -93,6 +327,27 @@ pub async fn get_product(
         }
     }
 
+    // Search tokens are pre-selected in SQL on normalized descriptions, then ranked in rust
+    let search_tokens = params.p_search.as_deref().map(search::tokenize).unwrap_or_default();
+    let (norm_from, norm_to) = search::normalize_binds();
+    let mut search_binds: Vec<(String, String)> = Vec::new();
+    let mut search_conditions: Vec<String> = Vec::new();
+    for (i, token) in search_tokens.iter().enumerate() {
+        let mut alternatives: Vec<String> = Vec::new();
+        for (j, pattern) in search::token_patterns(token).into_iter().enumerate() {
+            let bind_name = format!("search_{}_{}", i, j);
+            for column_name in ["ITEM_DESC", "ITEM_DESC_S"] {
+                alternatives.push(format!(
+                    "{} LIKE :{}",
+                    search::normalized_column(column_name),
+                    bind_name
+                ));
+            }
+            search_binds.push((bind_name, pattern));
+        }
+        search_conditions.push(format!("({})", alternatives.join(" OR ")));
+    }
+
     let mut sql = String::from("SELECT * FROM ODBC_JHC.JHC_INVDATA WHERE");
     let mut my_params: Vec<(&str, &dyn ToSql)> = Vec::new();
     let mut param_count = 0;
@@ -112,18 +367,43 @@ pub async fn get_product(
         my_params.push(("ITEM_DESC_S", p_desc as &dyn ToSql));
     }
 
+    let barcode_binds: Vec<(String, String)>;
     if let Some(p_barcode) = &params.p_barcode {
         if param_count > 0 {
             sql.push_str(" AND");
         }
         param_count += 1;
-        if p_barcode.starts_with("%") || p_barcode.ends_with("%") {
-            sql.push_str(" BARCODE_LISTED LIKE '%' || :barcode");
-        } else {
-            param_count += 1;
-            sql.push_str(" BARCODE_LISTED LIKE '%' || :barcode || '%'");
+        match &barcode_variants {
+            Some(variants) => {
+                let (condition, binds) = barcode::sql_condition(variants);
+                sql.push_str(&format!(" {}", condition));
+                barcode_binds = binds;
+                for (bind_name, value) in &barcode_binds {
+                    my_params.push((bind_name.as_str(), value as &dyn ToSql));
+                }
+            }
+            None => {
+                sql.push_str(" BARCODE_LISTED LIKE '%' || :barcode");
+                my_params.push(("barcode", p_barcode as &dyn ToSql));
+            }
         }
-        my_params.push(("barcode", p_barcode as &dyn ToSql));
+    }
+
+    if !search_conditions.is_empty() {
+        if param_count > 0 {
+            sql.push_str(" AND");
+        }
+        param_count += 1;
+        sql.push_str(&format!(" {}", search_conditions.join(" AND ")));
+        my_params.push(("norm_from", &norm_from as &dyn ToSql));
+        my_params.push(("norm_to", &norm_to as &dyn ToSql));
+        for (bind_name, pattern) in &search_binds {
+            my_params.push((bind_name.as_str(), pattern as &dyn ToSql));
+        }
+    }
+
+    if let Some(limit) = search::row_limit(&search_tokens) {
+        sql.push_str(&format!(" ORDER BY ITEM_ID FETCH FIRST {} ROWS ONLY", limit));
     }
 
     info!("SQL Statement: {:?}", sql);
@@ -155,152 +435,269 @@ pub async fn get_product(
             return Err(APIErrors::DBError);
         }
         let row = row_result.unwrap();
-        products.push(Product {
-            ITEM_ID: row.get("ITEM_ID").unwrap(),
-            IS_ACTIVE: row.get("IS_ACTIVE").unwrap(),
-            CAN_BE_SOLD: row.get("CAN_BE_SOLD").unwrap(),
-            ITEM_DESC: row.get("ITEM_DESC").unwrap(),
-            ITEM_DESC_S: row.get("ITEM_DESC_S").unwrap(),
-            FOREIGN_ITEM_CODE: row.get("FOREIGN_ITEM_CODE").unwrap(),
-            ITEM_CAT: row.get("ITEM_CAT").unwrap(),
-            ITEM_SUB_CAT: row.get("ITEM_SUB_CAT").unwrap(),
-            SALE_UNIT: row.get("SALE_UNIT").unwrap(),
-            UNIT_DESC: row.get("UNIT_DESC").unwrap(),
-            PACKING: row.get("PACKING").unwrap(),
-            CARD_OPEN_DATE: row.get("CARD_OPEN_DATE").unwrap(),
-            HS_CODE: row.get("HS_CODE").unwrap(),
-            COUNTRY: row.get("COUNTRY").unwrap(),
-            COUNTRY_DESC: row.get("COUNTRY_DESC").unwrap(),
-            SUPPLIER_ID: row.get("SUPPLIER_ID").unwrap(),
-            SUPPLIER_DESC: row.get("SUPPLIER_DESC").unwrap(),
-            ITEM_MAIN_BARCODE: row.get("ITEM_MAIN_BARCODE").unwrap(),
-            NATURE_ID: row.get("NATURE_ID").unwrap(),
-            NATURE_DESC: row.get("NATURE_DESC").unwrap(),
-            TRADE_ID: row.get("TRADE_ID").unwrap(),
-            TRADE_DESC: row.get("TRADE_DESC").unwrap(),
-            QTY_STORE_01: get_value(&store_ids, &row, "01", "QTY_STORE_01"),
-            QTY_STORE_02: get_value(&store_ids, &row, "02", "QTY_STORE_02"),
-            QTY_STORE_05: get_value(&store_ids, &row, "05", "QTY_STORE_05"),
-            QTY_STORE_06: get_value(&store_ids, &row, "06", "QTY_STORE_06"),
-            QTY_STORE_07: get_value(&store_ids, &row, "07", "QTY_STORE_07"),
-            QTY_STORE_08: get_value(&store_ids, &row, "08", "QTY_STORE_08"),
-            QTY_STORE_09: get_value(&store_ids, &row, "09", "QTY_STORE_09"),
-            QTY_STORE_10: get_value(&store_ids, &row, "10", "QTY_STORE_10"),
-            QTY_STORE_11: get_value(&store_ids, &row, "11", "QTY_STORE_11"),
-            QTY_STORE_12: get_value(&store_ids, &row, "12", "QTY_STORE_12"),
-            QTY_STORE_19: get_value(&store_ids, &row, "19", "QTY_STORE_19"),
-            QTY_STORE_21: get_value(&store_ids, &row, "21", "QTY_STORE_21"),
-            QTY_STORE_23: get_value(&store_ids, &row, "23", "QTY_STORE_23"),
-            QTY_STORE_31: get_value(&store_ids, &row, "31", "QTY_STORE_31"),
-            QTY_STORE_32: get_value(&store_ids, &row, "32", "QTY_STORE_32"),
-            QTY_STORE_33: get_value(&store_ids, &row, "33", "QTY_STORE_33"),
-            QTY_STORE_34: get_value(&store_ids, &row, "34", "QTY_STORE_34"),
-            QTY_STORE_35: get_value(&store_ids, &row, "35", "QTY_STORE_35"),
-            SALE_PRICE_NOTAX_STORE_01: get_value(
-                &store_ids,
-                &row,
-                "01",
-                "SALE_PRICE_NOTAX_STORE_01",
-            ),
-            SALE_PRICE_NOTAX_STORE_02: get_value(
-                &store_ids,
-                &row,
-                "02",
-                "SALE_PRICE_NOTAX_STORE_02",
-            ),
-            SALE_PRICE_NOTAX_STORE_05: get_value(
-                &store_ids,
-                &row,
-                "05",
-                "SALE_PRICE_NOTAX_STORE_05",
-            ),
-            SALE_PRICE_NOTAX_STORE_06: get_value(
-                &store_ids,
-                &row,
-                "06",
-                "SALE_PRICE_NOTAX_STORE_06",
-            ),
-            SALE_PRICE_NOTAX_STORE_08: get_value(
-                &store_ids,
-                &row,
-                "08",
-                "SALE_PRICE_NOTAX_STORE_08",
-            ),
-            SALE_PRICE_NOTAX_STORE_09: get_value(
-                &store_ids,
-                &row,
-                "09",
-                "SALE_PRICE_NOTAX_STORE_09",
-            ),
-            SALE_PRICE_NOTAX_STORE_07: get_value(
-                &store_ids,
-                &row,
-                "07",
-                "SALE_PRICE_NOTAX_STORE_07",
-            ),
-            SALE_PRICE_NOTAX_STORE_31: get_value(
-                &store_ids,
-                &row,
-                "31",
-                "SALE_PRICE_NOTAX_STORE_31",
-            ),
-            SALE_PRICE_NOTAX_STORE_32: get_value(
-                &store_ids,
-                &row,
-                "32",
-                "SALE_PRICE_NOTAX_STORE_32",
-            ),
-            SALE_PRICE_NOTAX_STORE_33: get_value(
-                &store_ids,
-                &row,
-                "33",
-                "SALE_PRICE_NOTAX_STORE_33",
-            ),
-            SALE_PRICE_NOTAX_STORE_34: get_value(
-                &store_ids,
-                &row,
-                "34",
-                "SALE_PRICE_NOTAX_STORE_34",
-            ),
-            SALE_PRICE_NOTAX_STORE_35: get_value(
-                &store_ids,
-                &row,
-                "35",
-                "SALE_PRICE_NOTAX_STORE_35",
-            ),
-            FIRST_DISC_PER_STORE_01: get_value(&store_ids, &row, "01", "FIRST_DISC_PER_STORE_01"),
-            FIRST_DISC_PER_STORE_02: get_value(&store_ids, &row, "02", "FIRST_DISC_PER_STORE_02"),
-            FIRST_DISC_PER_STORE_05: get_value(&store_ids, &row, "05", "FIRST_DISC_PER_STORE_05"),
-            FIRST_DISC_PER_STORE_06: get_value(&store_ids, &row, "06", "FIRST_DISC_PER_STORE_06"),
-            FIRST_DISC_PER_STORE_07: get_value(&store_ids, &row, "07", "FIRST_DISC_PER_STORE_07"),
-            FIRST_DISC_PER_STORE_08: get_value(&store_ids, &row, "08", "FIRST_DISC_PER_STORE_08"),
-            FIRST_DISC_PER_STORE_09: get_value(&store_ids, &row, "09", "FIRST_DISC_PER_STORE_09"),
-            FIRST_DISC_PER_STORE_31: get_value(&store_ids, &row, "31", "FIRST_DISC_PER_STORE_31"),
-            FIRST_DISC_PER_STORE_32: get_value(&store_ids, &row, "32", "FIRST_DISC_PER_STORE_32"),
-            FIRST_DISC_PER_STORE_33: get_value(&store_ids, &row, "33", "FIRST_DISC_PER_STORE_33"),
-            FIRST_DISC_PER_STORE_34: get_value(&store_ids, &row, "34", "FIRST_DISC_PER_STORE_34"),
-            FIRST_DISC_PER_STORE_35: get_value(&store_ids, &row, "35", "FIRST_DISC_PER_STORE_35"),
-            SECOND_DISC_PER_STORE_01: get_value(&store_ids, &row, "01", "SECOND_DISC_PER_STORE_01"),
-            SECOND_DISC_PER_STORE_02: get_value(&store_ids, &row, "02", "SECOND_DISC_PER_STORE_02"),
-            SECOND_DISC_PER_STORE_05: get_value(&store_ids, &row, "05", "SECOND_DISC_PER_STORE_05"),
-            SECOND_DISC_PER_STORE_06: get_value(&store_ids, &row, "06", "SECOND_DISC_PER_STORE_06"),
-            SECOND_DISC_PER_STORE_07: get_value(&store_ids, &row, "07", "SECOND_DISC_PER_STORE_07"),
-            SECOND_DISC_PER_STORE_08: get_value(&store_ids, &row, "08", "SECOND_DISC_PER_STORE_08"),
-            SECOND_DISC_PER_STORE_09: get_value(&store_ids, &row, "09", "SECOND_DISC_PER_STORE_09"),
-            SECOND_DISC_PER_STORE_31: get_value(&store_ids, &row, "31", "SECOND_DISC_PER_STORE_31"),
-            SECOND_DISC_PER_STORE_32: get_value(&store_ids, &row, "32", "SECOND_DISC_PER_STORE_32"),
-            SECOND_DISC_PER_STORE_33: get_value(&store_ids, &row, "33", "SECOND_DISC_PER_STORE_33"),
-            SECOND_DISC_PER_STORE_34: get_value(&store_ids, &row, "34", "SECOND_DISC_PER_STORE_34"),
-            SECOND_DISC_PER_STORE_35: get_value(&store_ids, &row, "35", "SECOND_DISC_PER_STORE_35"),
-            T_AVE_COST: if show_cost {
-                row.get("T_AVE_COST").ok()
-            } else {
-                None
-            },
-        });
+        let mut product = product_from_row(&row, &store_ids, show_cost);
+        product.NET_PRICES = Some(pricing.net_prices(&product, with_tax));
+        set_barcode_match(&mut product);
+        products.push(product);
+    }
+
+    if !search_tokens.is_empty() {
+        products = search::rank(products, &search_tokens);
     }
 
     info!("Products Count: {:?}", products.len());
 
     Ok(products)
 }
+
+/// Lightweight type-ahead on codes and descriptions, served from the index when available
+pub async fn suggest_products(
+    query: &str,
+    limit: usize,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    index: &ProductIndex,
+    key: &ApiKey<'_>,
+) -> Result<Vec<ProductSuggestion>, APIErrors> {
+    // Same access check as get_product, the user must exist and have store access resolved
+    get_user_store_ids(key, pool, sql_manager).await?;
+
+    let code = query.trim().to_uppercase();
+    if code.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if let Some(entries) = index.autocomplete(query, limit) {
+        return Ok(entries
+            .into_iter()
+            .map(|entry| ProductSuggestion {
+                ITEM_ID: Some(entry.ITEM_ID),
+                ITEM_DESC: entry.ITEM_DESC,
+                ITEM_MAIN_BARCODE: entry.ITEM_MAIN_BARCODE,
+            })
+            .collect());
+    }
+
+    let conn = pool.get().map_err(|e| {
+        error!("Connection Error: {:?}", e);
+        APIErrors::DBError
+    })?;
+
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("suggest_products")?.as_str())
+        .build()
+        .map_err(|e| {
+            error!("Error building statement: {:?}", e);
+            APIErrors::DBError
+        })?;
+
+    // LIKE wildcards typed by the user are matched literally
+    let escaped = code.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    let prefix = format!("{}%", escaped);
+    let word_prefix = format!("% {}%", escaped);
+    let limit = limit as i64;
+    let rows = stmt
+        .query_named(&[
+            ("code", &code as &dyn ToSql),
+            ("prefix", &prefix),
+            ("word_prefix", &word_prefix),
+            ("limit", &limit),
+        ])
+        .map_err(|e| {
+            error!("Error executing query: {:?}", e);
+            APIErrors::DBError
+        })?;
+
+    let mut suggestions: Vec<ProductSuggestion> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(|e| {
+            error!("Row Error: {:?}", e);
+            APIErrors::DBError
+        })?;
+        suggestions.push(ProductSuggestion {
+            ITEM_ID: row.get("ITEM_ID").unwrap_or(None),
+            ITEM_DESC: row.get("ITEM_DESC").unwrap_or(None),
+            ITEM_MAIN_BARCODE: row.get("ITEM_MAIN_BARCODE").unwrap_or(None),
+        });
+    }
+    Ok(suggestions)
+}
+
+/// Full product with parsed barcodes and a breakdown of the caller's stores
+pub async fn get_product_detail(
+    item_id: &str,
+    with_tax: bool,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    pricing: &PricingConfig,
+    image_store: &Arc<dyn ImageStore>,
+    key: &ApiKey<'_>,
+) -> Result<ProductDetail, APIErrors> {
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    let show_cost = is_cost_perm(key, pool, &sql_manager).await;
+
+    let mut products = get_products_by_ids(&[item_id.to_string()], pool, &store_ids, show_cost)?;
+    if products.is_empty() {
+        return Err(APIErrors::NoData);
+    }
+    let mut product = products.remove(0);
+    let net_prices = pricing.net_prices(&product, with_tax);
+
+    let conn = pool.get().map_err(|e| {
+        error!("Connection Error: {:?}", e);
+        APIErrors::DBError
+    })?;
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("get_product_barcodes")?.as_str())
+        .build()
+        .map_err(|e| {
+            error!("Error building statement: {:?}", e);
+            APIErrors::DBError
+        })?;
+    let row = stmt.query_row(&[&item_id]).map_err(|e| {
+        error!("Error executing query: {:?}", e);
+        APIErrors::DBError
+    })?;
+    let listed: Option<String> = row.get("BARCODE_LISTED").unwrap_or(None);
+
+    let mut barcodes: Vec<String> = Vec::new();
+    if let Some(main_barcode) = &product.ITEM_MAIN_BARCODE {
+        barcodes.push(main_barcode.clone());
+    }
+    for barcode in barcode::parse_barcodes(listed.as_deref().unwrap_or("")) {
+        if !barcodes.contains(&barcode) {
+            barcodes.push(barcode);
+        }
+    }
+
+    // Every store with prices also has a quantity column
+    let mut stores: Vec<StoreBreakdown> = Vec::new();
+    for store_id in QTY_STORES {
+        if !store_ids.contains(store_id) {
+            continue;
+        }
+        let (price, first_disc, second_disc) = product.store_pricing(store_id);
+        stores.push(StoreBreakdown {
+            STORE_ID: store_id.to_string(),
+            QTY: product.store_qty(store_id),
+            SALE_PRICE_NOTAX: price,
+            FIRST_DISC_PER: first_disc,
+            SECOND_DISC_PER: second_disc,
+            NET_PRICE: net_prices.get(store_id).cloned(),
+        });
+    }
+
+    // A storage error shouldn't hide the product, the image is just reported as missing
+    let has_image = match image_exists(image_store, item_id).await {
+        Ok(exists) => exists,
+        Err(e) => {
+            error!("Error checking image for {}: {}", item_id, e);
+            false
+        }
+    };
+
+    product.NET_PRICES = Some(net_prices);
+    Ok(ProductDetail {
+        product,
+        BARCODES: barcodes,
+        STORES: stores,
+        HAS_IMAGE: has_image,
+    })
+}
+
+/// Fetch full rows for a list of item ids, keeping the order of `item_ids`
+pub fn get_products_by_ids(
+    item_ids: &[String],
+    pool: &Pool,
+    store_ids: &HashSet<String>,
+    show_cost: bool,
+) -> Result<Vec<Product>, APIErrors> {
+    if item_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let conn = pool.get().map_err(|e| {
+        error!("Connection Error: {:?}", e);
+        APIErrors::DBError
+    })?;
+    query_products_by_ids(&conn, item_ids, store_ids, show_cost)
+}
+
+/// `get_products_by_ids` on a connection the caller already holds, for use inside its transaction
+pub fn query_products_by_ids(
+    conn: &Connection,
+    item_ids: &[String],
+    store_ids: &HashSet<String>,
+    show_cost: bool,
+) -> Result<Vec<Product>, APIErrors> {
+    let mut products: Vec<Product> = Vec::new();
+
+    // Oracle limits IN lists to 1000 expressions
+    for chunk in item_ids.chunks(500) {
+        let bind_names: Vec<String> = (0..chunk.len()).map(|i| format!("id_{}", i)).collect();
+        let sql = format!(
+            "SELECT * FROM ODBC_JHC.JHC_INVDATA WHERE ITEM_ID IN ({})",
+            bind_names.iter().map(|n| format!(":{}", n)).collect::<Vec<String>>().join(", ")
+        );
+        let binds: Vec<(&str, &dyn ToSql)> = bind_names
+            .iter()
+            .zip(chunk.iter())
+            .map(|(name, id)| (name.as_str(), id as &dyn ToSql))
+            .collect();
+
+        let mut stmt = conn.statement(&sql).build().map_err(|e| {
+            error!("Error building statement: {:?}", e);
+            APIErrors::DBError
+        })?;
+        let rows = stmt.query_named(&binds).map_err(|e| {
+            error!("Error executing query: {:?}", e);
+            APIErrors::DBError
+        })?;
+        for row_result in rows {
+            let row = row_result.map_err(|e| {
+                error!("Row Error: {:?}", e);
+                APIErrors::DBError
+            })?;
+            products.push(product_from_row(&row, store_ids, show_cost));
+        }
+    }
+
+    let positions: HashMap<&String, usize> =
+        item_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+    products.sort_by_key(|p| {
+        p.ITEM_ID.as_ref().and_then(|id| positions.get(id).copied()).unwrap_or(usize::MAX)
+    });
+    Ok(products)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("12.50"), Decimal::from_str("12.5").ok());
+        assert_eq!(parse_decimal(".5"), Decimal::from_str("0.5").ok());
+        assert_eq!(parse_decimal("-.25"), Decimal::from_str("-0.25").ok());
+        assert_eq!(parse_decimal("1E-3"), Decimal::from_str("0.001").ok());
+        assert_eq!(parse_decimal("abc"), None);
+    }
+
+    #[test]
+    fn test_legacy_product_format() {
+        let mut product: Product = serde_json::from_str("{}").unwrap();
+        product.ITEM_ID = Some("1001".to_string());
+        product.QTY_STORE_01 = Decimal::from_str("12.50").ok();
+        product.CARD_OPEN_DATE = NaiveDate::from_ymd_opt(2021, 3, 7);
+
+        let typed = serde_json::to_value(&product).unwrap();
+        assert_eq!(typed["QTY_STORE_01"].to_string(), "12.50");
+        assert_eq!(typed["CARD_OPEN_DATE"], "2021-03-07");
+
+        let legacy = serde_json::to_value(structs::LegacyProduct(product)).unwrap();
+        assert_eq!(legacy["ITEM_ID"], "1001");
+        assert_eq!(legacy["QTY_STORE_01"], "12.50");
+        assert_eq!(legacy["CARD_OPEN_DATE"], "2021-03-07 00:00:00");
+        assert_eq!(legacy["QTY_STORE_02"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_decimal::RoundingStrategy;
+use serde::Deserialize;
+
+use crate::functions::products::structs::NetPrice;
+use crate::functions::products::structs::Product;
+use crate::functions::products::structs::PRICE_STORES;
+
+/// Tax and rounding settings, read from the `pricing` table of Rocket.toml
+///
+/// Rates are fractions ("0.16" is 16%). A rate configured for the product's
+/// `NATURE_ID` wins over the store's rate, which wins over `default_tax_rate`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PricingConfig {
+    pub default_tax_rate: Decimal,
+    pub scale: u32,
+    pub store_tax_rates: HashMap<String, Decimal>,
+    pub nature_tax_rates: HashMap<String, Decimal>,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        PricingConfig {
+            default_tax_rate: Decimal::ZERO,
+            scale: 3,
+            store_tax_rates: HashMap::new(),
+            nature_tax_rates: HashMap::new(),
+        }
+    }
+}
+
+impl PricingConfig {
+    pub fn load() -> PricingConfig {
+        match rocket::Config::figment().extract_inner::<PricingConfig>("pricing") {
+            Ok(config) => config,
+            Err(e) => {
+                info!("No pricing config found, using defaults: {}", e);
+                PricingConfig::default()
+            }
+        }
+    }
+
+    pub fn tax_rate(&self, store_id: &str, nature_id: Option<&str>) -> Decimal {
+        if let Some(rate) = nature_id.and_then(|id| self.nature_tax_rates.get(id)) {
+            return *rate;
+        }
+        match self.store_tax_rates.get(store_id) {
+            Some(rate) => *rate,
+            None => self.default_tax_rate,
+        }
+    }
+
+    /// Rounding rule: both discounts are applied in cascade on the unrounded price,
+    /// then the result is rounded half away from zero to `scale` decimal places.
+    /// Tax is applied on the rounded net price and rounded again with the same rule.
+    pub fn net_price(
+        &self,
+        price: Decimal,
+        first_disc: Option<Decimal>,
+        second_disc: Option<Decimal>,
+    ) -> Decimal {
+        let hundred = Decimal::ONE_HUNDRED;
+        let mut net = price;
+        for disc in [first_disc, second_disc].into_iter().flatten() {
+            net = net * (hundred - disc) / hundred;
+        }
+        self.round(net)
+    }
+
+    pub fn with_tax(&self, net_price: Decimal, tax_rate: Decimal) -> Decimal {
+        self.round(net_price * (Decimal::ONE + tax_rate))
+    }
+
+    fn round(&self, value: Decimal) -> Decimal {
+        value.round_dp_with_strategy(self.scale, RoundingStrategy::MidpointAwayFromZero)
+    }
+
+    /// Net prices for every store the product has a visible price for
+    pub fn net_prices(&self, product: &Product, with_tax: bool) -> BTreeMap<String, NetPrice> {
+        let mut prices = BTreeMap::new();
+        for store_id in PRICE_STORES {
+            let (price, first_disc, second_disc) = product.store_pricing(store_id);
+            let price = match price {
+                Some(price) => price,
+                None => continue,
+            };
+            let net_price = self.net_price(price, first_disc, second_disc);
+            let mut entry = NetPrice {
+                NET_PRICE: net_price,
+                TAX_RATE: None,
+                NET_PRICE_TAX: None,
+            };
+            if with_tax {
+                let rate = self.tax_rate(store_id, product.NATURE_ID.as_deref());
+                entry.TAX_RATE = Some(rate);
+                entry.NET_PRICE_TAX = Some(self.with_tax(net_price, rate));
+            }
+            prices.insert(store_id.to_string(), entry);
+        }
+        prices
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_cascading_discounts() {
+        let config = PricingConfig::default();
+        // 10.000 - 10% = 9.000, - 5% = 8.550
+        assert_eq!(config.net_price(dec("10"), Some(dec("10")), Some(dec("5"))), dec("8.55"));
+        assert_eq!(config.net_price(dec("10"), None, None), dec("10"));
+        // 1.2345 rounds half away from zero to 1.235
+        assert_eq!(config.net_price(dec("1.2345"), Some(dec("0")), None), dec("1.235"));
+    }
+
+    #[test]
+    fn test_tax_rate_precedence() {
+        let mut config = PricingConfig::default();
+        config.default_tax_rate = dec("0.16");
+        config.store_tax_rates.insert("31".to_string(), dec("0"));
+        config.nature_tax_rates.insert("2".to_string(), dec("0.04"));
+
+        assert_eq!(config.tax_rate("01", None), dec("0.16"));
+        assert_eq!(config.tax_rate("31", Some("1")), dec("0"));
+        assert_eq!(config.tax_rate("31", Some("2")), dec("0.04"));
+        assert_eq!(config.with_tax(dec("8.55"), dec("0.16")), dec("9.918"));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::functions::products::structs::Product;
+
+// Letter variants folded to a single form, applied after uppercasing
+const FOLD: &[(char, char)] = &[
+    ('À', 'A'), ('Á', 'A'), ('Â', 'A'), ('Ã', 'A'), ('Ä', 'A'), ('Å', 'A'),
+    ('Ç', 'C'),
+    ('È', 'E'), ('É', 'E'), ('Ê', 'E'), ('Ë', 'E'),
+    ('Ì', 'I'), ('Í', 'I'), ('Î', 'I'), ('Ï', 'I'),
+    ('Ñ', 'N'),
+    ('Ò', 'O'), ('Ó', 'O'), ('Ô', 'O'), ('Õ', 'O'), ('Ö', 'O'), ('Ø', 'O'),
+    ('Ù', 'U'), ('Ú', 'U'), ('Û', 'U'), ('Ü', 'U'),
+    ('Ý', 'Y'), ('Ÿ', 'Y'),
+    ('أ', 'ا'), ('إ', 'ا'), ('آ', 'ا'), ('ٱ', 'ا'),
+    ('ة', 'ه'),
+    ('ى', 'ي'), ('ئ', 'ي'),
+    ('ؤ', 'و'),
+];
+
+// Arabic diacritics (tashkeel) and tatweel, removed entirely
+const STRIP: &[char] = &[
+    '\u{064B}', '\u{064C}', '\u{064D}', '\u{064E}', '\u{064F}', '\u{0650}', '\u{0651}',
+    '\u{0652}', '\u{0670}', '\u{0640}',
+];
+
+// Score given to a token depending on how it matched a word
+const EXACT_SCORE: u32 = 40;
+const PREFIX_SCORE: u32 = 30;
+const INFIX_SCORE: u32 = 20;
+const FUZZY_SCORE: u32 = 10;
+const SAME_FIELD_BONUS: u32 = 5;
+const PHRASE_BONUS: u32 = 15;
+
+/// Uppercase, fold letter variants and replace punctuation with spaces
+pub fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_uppercase) {
+        if STRIP.contains(&c) {
+            continue;
+        }
+        match FOLD.iter().find(|(from, _)| *from == c) {
+            Some((_, to)) => normalized.push(*to),
+            None if c.is_alphanumeric() => normalized.push(c),
+            None => normalized.push(' '),
+        }
+    }
+    normalized
+}
+
+/// Normalized, deduplicated query tokens
+pub fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for token in normalize(query).split_whitespace() {
+        if !tokens.iter().any(|t| t == token) {
+            tokens.push(token.to_string());
+        }
+    }
+    tokens
+}
+
+/// SQL expression applying the same folding as `normalize` to a column.
+/// Uses the `norm_from` and `norm_to` binds returned by `normalize_binds`.
+pub fn normalized_column(column_name: &str) -> String {
+    format!("TRANSLATE(UPPER({}), :norm_from, :norm_to)", column_name)
+}
+
+pub fn normalize_binds() -> (String, String) {
+    // TRANSLATE drops characters of `from` that have no counterpart in `to`
+    let mut from: String = FOLD.iter().map(|(from, _)| *from).collect();
+    let to: String = FOLD.iter().map(|(_, to)| *to).collect();
+    from.extend(STRIP.iter());
+    (from, to)
+}
+
+/// Number of typos tolerated for a token of this length
+fn allowed_typos(token: &str) -> usize {
+    match token.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Patterns used to pre-select rows in SQL. A fuzzy token is split in one more
+/// piece than the typos it allows, each typo can only break one of them so at
+/// least one piece is still intact in a matching description.
+pub fn token_patterns(token: &str) -> Vec<String> {
+    let mut patterns = vec![format!("%{}%", token)];
+    let pieces = allowed_typos(token) + 1;
+    if pieces > 1 {
+        let chars: Vec<char> = token.chars().collect();
+        for i in 0..pieces {
+            let piece: String = chars[chars.len() * i / pieces..chars.len() * (i + 1) / pieces].iter().collect();
+            patterns.push(format!("%{}%", piece));
+        }
+    }
+    patterns
+}
+
+/// Rows pre-selected in SQL when every token is this short or shorter,
+/// a single character matches most of the table
+const SHORT_TOKEN_CHARS: usize = 2;
+const SHORT_TOKEN_ROWS: usize = 2000;
+
+/// Cap on the pre-selected rows, None when a token is long enough to narrow them down
+pub fn row_limit(tokens: &[String]) -> Option<usize> {
+    let all_short = !tokens.is_empty() && tokens.iter().all(|token| token.chars().count() <= SHORT_TOKEN_CHARS);
+    all_short.then_some(SHORT_TOKEN_ROWS)
+}
+
+/// Levenshtein distance over chars
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+fn token_score(token: &str, word: &str) -> u32 {
+    if word == token {
+        return EXACT_SCORE;
+    }
+    if word.starts_with(token) {
+        return PREFIX_SCORE;
+    }
+    if word.contains(token) {
+        return INFIX_SCORE;
+    }
+    let allowed = allowed_typos(token);
+    if allowed > 0 {
+        // Compare against the whole word and against a prefix of the same length,
+        // so "choclate" still finds "CHOCOLATES"
+        let prefix: String = word.chars().take(token.chars().count()).collect();
+        if edit_distance(token, word) <= allowed || edit_distance(token, &prefix) <= allowed {
+            return FUZZY_SCORE;
+        }
+    }
+    0
+}
+
+fn best_score(token: &str, words: &[&str]) -> u32 {
+    words.iter().map(|word| token_score(token, word)).max().unwrap_or(0)
+}
+
+/// Relevance of a product for the query tokens, None if any token is missing from both descriptions
+pub fn score(tokens: &[String], item_desc: Option<&str>, item_desc_s: Option<&str>) -> Option<u32> {
+    let desc = normalize(item_desc.unwrap_or(""));
+    let desc_s = normalize(item_desc_s.unwrap_or(""));
+    score_normalized(tokens, &desc, &desc_s)
+}
+
+/// Same as `score`, for descriptions that already went through `normalize`
+pub fn score_normalized(tokens: &[String], desc: &str, desc_s: &str) -> Option<u32> {
+    let words: Vec<&str> = desc.split_whitespace().collect();
+    let words_s: Vec<&str> = desc_s.split_whitespace().collect();
+
+    let mut total = 0;
+    let mut all_in_desc = true;
+    let mut all_in_desc_s = true;
+    for token in tokens {
+        let primary = best_score(token, &words);
+        let secondary = best_score(token, &words_s);
+        if primary == 0 && secondary == 0 {
+            return None;
+        }
+        all_in_desc &= primary > 0;
+        all_in_desc_s &= secondary > 0;
+        total += primary.max(secondary);
+    }
+
+    if all_in_desc || all_in_desc_s {
+        total += SAME_FIELD_BONUS;
+    }
+    let phrase = tokens.join(" ");
+    if tokens.len() > 1 && (desc.contains(phrase.as_str()) || desc_s.contains(phrase.as_str())) {
+        total += PHRASE_BONUS;
+    }
+    Some(total)
+}
+
+/// Drop products that don't match every token and sort the rest by relevance
+pub fn rank(products: Vec<Product>, tokens: &[String]) -> Vec<Product> {
+    let mut scored: Vec<(u32, Product)> = products
+        .into_iter()
+        .filter_map(|product| {
+            let score = score(tokens, product.ITEM_DESC.as_deref(), product.ITEM_DESC_S.as_deref());
+            score.map(|score| (score, product))
+        })
+        .collect();
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score.cmp(a_score).then_with(|| a.ITEM_ID.cmp(&b.ITEM_ID))
+    });
+    scored.into_iter().map(|(_, product)| product).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Crème brûlée"), "CREME BRULEE");
+        assert_eq!(normalize("شوكولاتة"), "شوكولاته");
+        assert_eq!(normalize("إِسْتِكَانَة"), "استكانه");
+        assert_eq!(normalize("Milk-Choc, 100g"), "MILK CHOC  100G");
+        assert_eq!(tokenize(" milk MILK  choc "), vec!["MILK", "CHOC"]);
+    }
+
+    #[test]
+    fn test_token_patterns() {
+        assert_eq!(token_patterns("TEA"), vec!["%TEA%"]);
+        assert_eq!(token_patterns("CHOC"), vec!["%CHOC%", "%CH%", "%OC%"]);
+        assert_eq!(token_patterns("CHOCOLATE"), vec!["%CHOCOLATE%", "%CHO%", "%COL%", "%ATE%"]);
+    }
+
+    #[test]
+    fn test_row_limit() {
+        assert_eq!(row_limit(&tokenize("a 1")), Some(SHORT_TOKEN_ROWS));
+        assert_eq!(row_limit(&tokenize("a milk")), None);
+        assert_eq!(row_limit(&[]), None);
+    }
+
+    #[test]
+    fn test_two_typos_preselected() {
+        // Both typos in different pieces, one piece still matches in SQL
+        let token = "CHOKOLATR";
+        assert!(score(&[token.to_string()], Some("CHOCOLATE"), None).is_some());
+        assert!(token_patterns(token).iter().any(|pattern| "CHOCOLATE".contains(pattern.trim_matches('%'))));
+    }
+
+    #[test]
+    fn test_score() {
+        let tokens = tokenize("chocolate milk");
+        let reordered = score(&tokens, Some("MILK CHOCOLATE 100G"), None);
+        let typo = score(&tokenize("choclate milk"), Some("MILK CHOCOLATE 100G"), None);
+        let phrase = score(&tokens, Some("CHOCOLATE MILK 1L"), None);
+        assert!(reordered.is_some());
+        assert!(typo.is_some());
+        assert!(phrase > reordered);
+        assert!(reordered > typo);
+        assert_eq!(score(&tokens, Some("MILK 1L"), None), None);
+
+        // Tokens can be spread over both description fields
+        let mixed = score(&tokenize("milk حليب"), Some("MILK 1L"), Some("حليب طازج"));
+        assert!(mixed.is_some());
+    }
+}
@@ -1,8 +1,28 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
 use rocket::serde::Deserialize;
 use rocket::serde::Serialize;
+use rocket::serde::Serializer;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+use crate::functions::products::search;
+use crate::server::request_guard::api_version::ApiVersion;
+
+/// Stores with a QTY_STORE_xx column in JHC_INVDATA
+pub const QTY_STORES: [&str; 18] = [
+    "01", "02", "05", "06", "07", "08", "09", "10", "11", "12", "19", "21", "23", "31", "32", "33",
+    "34", "35",
+];
+
+/// Stores with price and discount columns in JHC_INVDATA
+pub const PRICE_STORES: [&str; 12] = [
+    "01", "02", "05", "06", "07", "08", "09", "31", "32", "33", "34", "35",
+];
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct Product {
     pub ITEM_ID: Option<String>,
     pub IS_ACTIVE: Option<String>,
@@ -15,7 +35,7 @@ pub struct Product {
     pub SALE_UNIT: Option<String>,
     pub UNIT_DESC: Option<String>,
     pub PACKING: Option<String>,
-    pub CARD_OPEN_DATE: Option<String>,
+    pub CARD_OPEN_DATE: Option<NaiveDate>,
     pub HS_CODE: Option<String>,
     pub COUNTRY: Option<String>,
     pub COUNTRY_DESC: Option<String>,
@@ -26,61 +46,200 @@ pub struct Product {
     pub NATURE_DESC: Option<String>,
     pub TRADE_ID: Option<String>,
     pub TRADE_DESC: Option<String>,
-    pub QTY_STORE_01: Option<String>,
-    pub QTY_STORE_02: Option<String>,
-    pub QTY_STORE_05: Option<String>,
-    pub QTY_STORE_06: Option<String>,
-    pub QTY_STORE_07: Option<String>,
-    pub QTY_STORE_08: Option<String>,
-    pub QTY_STORE_09: Option<String>,
-    pub QTY_STORE_10: Option<String>,
-    pub QTY_STORE_11: Option<String>,
-    pub QTY_STORE_12: Option<String>,
-    pub QTY_STORE_19: Option<String>,
-    pub QTY_STORE_21: Option<String>,
-    pub QTY_STORE_23: Option<String>,
-    pub QTY_STORE_31: Option<String>,
-    pub QTY_STORE_32: Option<String>,
-    pub QTY_STORE_33: Option<String>,
-    pub QTY_STORE_34: Option<String>,
-    pub QTY_STORE_35: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_01: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_02: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_05: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_06: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_08: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_09: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_07: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_31: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_32: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_33: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_34: Option<String>,
-    pub SALE_PRICE_NOTAX_STORE_35: Option<String>,
-    pub FIRST_DISC_PER_STORE_01: Option<String>,
-    pub FIRST_DISC_PER_STORE_02: Option<String>,
-    pub FIRST_DISC_PER_STORE_05: Option<String>,
-    pub FIRST_DISC_PER_STORE_06: Option<String>,
-    pub FIRST_DISC_PER_STORE_07: Option<String>,
-    pub FIRST_DISC_PER_STORE_08: Option<String>,
-    pub FIRST_DISC_PER_STORE_09: Option<String>,
-    pub FIRST_DISC_PER_STORE_31: Option<String>,
-    pub FIRST_DISC_PER_STORE_32: Option<String>,
-    pub FIRST_DISC_PER_STORE_33: Option<String>,
-    pub FIRST_DISC_PER_STORE_34: Option<String>,
-    pub FIRST_DISC_PER_STORE_35: Option<String>,
-    pub SECOND_DISC_PER_STORE_01: Option<String>,
-    pub SECOND_DISC_PER_STORE_02: Option<String>,
-    pub SECOND_DISC_PER_STORE_05: Option<String>,
-    pub SECOND_DISC_PER_STORE_06: Option<String>,
-    pub SECOND_DISC_PER_STORE_07: Option<String>,
-    pub SECOND_DISC_PER_STORE_08: Option<String>,
-    pub SECOND_DISC_PER_STORE_09: Option<String>,
-    pub SECOND_DISC_PER_STORE_31: Option<String>,
-    pub SECOND_DISC_PER_STORE_32: Option<String>,
-    pub SECOND_DISC_PER_STORE_33: Option<String>,
-    pub SECOND_DISC_PER_STORE_34: Option<String>,
-    pub SECOND_DISC_PER_STORE_35: Option<String>,
-    pub T_AVE_COST: Option<String>,
+    pub QTY_STORE_01: Option<Decimal>,
+    pub QTY_STORE_02: Option<Decimal>,
+    pub QTY_STORE_05: Option<Decimal>,
+    pub QTY_STORE_06: Option<Decimal>,
+    pub QTY_STORE_07: Option<Decimal>,
+    pub QTY_STORE_08: Option<Decimal>,
+    pub QTY_STORE_09: Option<Decimal>,
+    pub QTY_STORE_10: Option<Decimal>,
+    pub QTY_STORE_11: Option<Decimal>,
+    pub QTY_STORE_12: Option<Decimal>,
+    pub QTY_STORE_19: Option<Decimal>,
+    pub QTY_STORE_21: Option<Decimal>,
+    pub QTY_STORE_23: Option<Decimal>,
+    pub QTY_STORE_31: Option<Decimal>,
+    pub QTY_STORE_32: Option<Decimal>,
+    pub QTY_STORE_33: Option<Decimal>,
+    pub QTY_STORE_34: Option<Decimal>,
+    pub QTY_STORE_35: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_01: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_02: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_05: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_06: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_08: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_09: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_07: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_31: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_32: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_33: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_34: Option<Decimal>,
+    pub SALE_PRICE_NOTAX_STORE_35: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_01: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_02: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_05: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_06: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_07: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_08: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_09: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_31: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_32: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_33: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_34: Option<Decimal>,
+    pub FIRST_DISC_PER_STORE_35: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_01: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_02: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_05: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_06: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_07: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_08: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_09: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_31: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_32: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_33: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_34: Option<Decimal>,
+    pub SECOND_DISC_PER_STORE_35: Option<Decimal>,
+    pub T_AVE_COST: Option<Decimal>,
+    // Computed from the store price and discounts, keyed by store id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub NET_PRICES: Option<BTreeMap<String, NetPrice>>,
+    // Set on barcode searches, whether the scanned code is ITEM_MAIN_BARCODE
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub MAIN_BARCODE_MATCH: Option<bool>,
+}
+
+impl Product {
+    /// Quantity on hand for a store
+    pub fn store_qty(&self, store_id: &str) -> Option<Decimal> {
+        match store_id {
+            "01" => self.QTY_STORE_01,
+            "02" => self.QTY_STORE_02,
+            "05" => self.QTY_STORE_05,
+            "06" => self.QTY_STORE_06,
+            "07" => self.QTY_STORE_07,
+            "08" => self.QTY_STORE_08,
+            "09" => self.QTY_STORE_09,
+            "10" => self.QTY_STORE_10,
+            "11" => self.QTY_STORE_11,
+            "12" => self.QTY_STORE_12,
+            "19" => self.QTY_STORE_19,
+            "21" => self.QTY_STORE_21,
+            "23" => self.QTY_STORE_23,
+            "31" => self.QTY_STORE_31,
+            "32" => self.QTY_STORE_32,
+            "33" => self.QTY_STORE_33,
+            "34" => self.QTY_STORE_34,
+            "35" => self.QTY_STORE_35,
+            _ => None,
+        }
+    }
+
+    /// Sale price, first and second discount for a store
+    pub fn store_pricing(&self, store_id: &str) -> (Option<Decimal>, Option<Decimal>, Option<Decimal>) {
+        match store_id {
+            "01" => (self.SALE_PRICE_NOTAX_STORE_01, self.FIRST_DISC_PER_STORE_01, self.SECOND_DISC_PER_STORE_01),
+            "02" => (self.SALE_PRICE_NOTAX_STORE_02, self.FIRST_DISC_PER_STORE_02, self.SECOND_DISC_PER_STORE_02),
+            "05" => (self.SALE_PRICE_NOTAX_STORE_05, self.FIRST_DISC_PER_STORE_05, self.SECOND_DISC_PER_STORE_05),
+            "06" => (self.SALE_PRICE_NOTAX_STORE_06, self.FIRST_DISC_PER_STORE_06, self.SECOND_DISC_PER_STORE_06),
+            "07" => (self.SALE_PRICE_NOTAX_STORE_07, self.FIRST_DISC_PER_STORE_07, self.SECOND_DISC_PER_STORE_07),
+            "08" => (self.SALE_PRICE_NOTAX_STORE_08, self.FIRST_DISC_PER_STORE_08, self.SECOND_DISC_PER_STORE_08),
+            "09" => (self.SALE_PRICE_NOTAX_STORE_09, self.FIRST_DISC_PER_STORE_09, self.SECOND_DISC_PER_STORE_09),
+            "31" => (self.SALE_PRICE_NOTAX_STORE_31, self.FIRST_DISC_PER_STORE_31, self.SECOND_DISC_PER_STORE_31),
+            "32" => (self.SALE_PRICE_NOTAX_STORE_32, self.FIRST_DISC_PER_STORE_32, self.SECOND_DISC_PER_STORE_32),
+            "33" => (self.SALE_PRICE_NOTAX_STORE_33, self.FIRST_DISC_PER_STORE_33, self.SECOND_DISC_PER_STORE_33),
+            "34" => (self.SALE_PRICE_NOTAX_STORE_34, self.FIRST_DISC_PER_STORE_34, self.SECOND_DISC_PER_STORE_34),
+            "35" => (self.SALE_PRICE_NOTAX_STORE_35, self.FIRST_DISC_PER_STORE_35, self.SECOND_DISC_PER_STORE_35),
+            _ => (None, None, None),
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetPrice {
+    pub NET_PRICE: Decimal,
+    pub TAX_RATE: Option<Decimal>,
+    pub NET_PRICE_TAX: Option<Decimal>,
+}
+
+/// Stock and pricing of a product in one store
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StoreBreakdown {
+    pub STORE_ID: String,
+    pub QTY: Option<Decimal>,
+    pub SALE_PRICE_NOTAX: Option<Decimal>,
+    pub FIRST_DISC_PER: Option<Decimal>,
+    pub SECOND_DISC_PER: Option<Decimal>,
+    pub NET_PRICE: Option<NetPrice>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize)]
+pub struct ProductDetail {
+    #[serde(flatten)]
+    pub product: Product,
+    pub BARCODES: Vec<String>,
+    pub STORES: Vec<StoreBreakdown>,
+    pub HAS_IMAGE: bool,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProductSuggestion {
+    pub ITEM_ID: Option<String>,
+    pub ITEM_DESC: Option<String>,
+    pub ITEM_MAIN_BARCODE: Option<String>,
+}
+
+/// Product in the pre-typed response format, where every value is a string
+pub struct LegacyProduct(pub Product);
+
+impl Serialize for LegacyProduct {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value = serde_json::to_value(&self.0).map_err(serde::ser::Error::custom)?;
+        if let Value::Object(fields) = &mut value {
+            for (key, field) in fields.iter_mut() {
+                match field {
+                    // Dates were returned as full Oracle timestamps
+                    Value::String(date) if key == "CARD_OPEN_DATE" => {
+                        *field = Value::String(format!("{} 00:00:00", date))
+                    }
+                    _ => stringify_numbers(field),
+                }
+            }
+        }
+        value.serialize(serializer)
+    }
+}
+
+// Numbers keep their exact text, as Oracle used to return them
+fn stringify_numbers(value: &mut Value) {
+    match value {
+        Value::Number(number) => *value = Value::String(number.to_string()),
+        Value::Object(fields) => fields.values_mut().for_each(stringify_numbers),
+        Value::Array(items) => items.iter_mut().for_each(stringify_numbers),
+        _ => {}
+    }
+}
+
+/// Product list serialized according to the requested API version
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ProductList {
+    Typed(Vec<Product>),
+    Legacy(Vec<LegacyProduct>),
+}
+
+impl ProductList {
+    pub fn new(products: Vec<Product>, version: ApiVersion) -> ProductList {
+        if version.is_legacy() {
+            ProductList::Legacy(products.into_iter().map(LegacyProduct).collect())
+        } else {
+            ProductList::Typed(products)
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Debug, Serialize, Clone, PartialEq)]
@@ -89,6 +248,10 @@ pub struct FetchParams {
     pub p_barcode: Option<String>,
     pub p_id: Option<String>,
     pub p_desc: Option<String>,
+    // Tokenized search over both descriptions, tolerant to typos and word order
+    pub p_search: Option<String>,
+    // Adds the taxed net price next to the net price, not a search parameter
+    pub p_with_tax: Option<bool>,
 }
 
 impl FetchParams {
@@ -97,5 +260,25 @@ impl FetchParams {
             && self.p_barcode.is_none()
             && self.p_id.is_none()
             && self.p_desc.is_none()
+            && self.p_search.as_deref().map_or(true, |q| search::tokenize(q).is_empty())
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GtinFormat {
+    Ean8,
+    UpcA,
+    Ean13,
+    Gtin14,
+}
+
+/// Result of `GET /barcodes/<code>`, VARIANTS are the codes a product search tries
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Serialize)]
+pub struct BarcodeCheck {
+    pub CODE: String,
+    pub VALID: bool,
+    pub FORMAT: Option<GtinFormat>,
+    pub VARIANTS: Vec<String>,
+}
@@ -0,0 +1,215 @@
+pub mod structs;
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use oracle::pool::Pool;
+
+use crate::functions::products::search;
+use crate::functions::reference::structs::{Category, ReferenceItem};
+use crate::utils::env::env_number;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Code and description pairs as returned by the reference queries
+type ReferenceRows = Arc<Vec<(String, Option<String>)>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    Categories,
+    Suppliers,
+    Countries,
+    Natures,
+    Trades,
+}
+
+impl ReferenceKind {
+    fn sql_name(&self) -> &'static str {
+        match self {
+            ReferenceKind::Categories => "get_ref_categories",
+            ReferenceKind::Suppliers => "get_ref_suppliers",
+            ReferenceKind::Countries => "get_ref_countries",
+            ReferenceKind::Natures => "get_ref_natures",
+            ReferenceKind::Trades => "get_ref_trades",
+        }
+    }
+}
+
+/// Reference values change rarely, they are kept in memory for `max_age` seconds,
+/// which is also the `max-age` sent to clients
+pub struct ReferenceCache {
+    pub max_age: u64,
+    data: RwLock<HashMap<ReferenceKind, (Instant, ReferenceRows)>>,
+}
+
+impl ReferenceCache {
+    pub fn from_env() -> ReferenceCache {
+        let max_age = env_number("REFERENCE_CACHE_SECONDS", 3600);
+        ReferenceCache {
+            max_age,
+            data: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn rows(&self, kind: ReferenceKind, pool: &Pool, sql_manager: &SQLManager) -> Result<ReferenceRows, APIErrors> {
+        {
+            let data = self.data.read().map_err(|_| APIErrors::InternalServerError)?;
+            if let Some((loaded_at, rows)) = data.get(&kind) {
+                if loaded_at.elapsed() < Duration::from_secs(self.max_age) {
+                    return Ok(rows.clone());
+                }
+            }
+        }
+
+        let rows = Arc::new(fetch_rows(pool, sql_manager.get_sql(kind.sql_name())?.as_str())?);
+        let mut data = self.data.write().map_err(|_| APIErrors::InternalServerError)?;
+        data.insert(kind, (Instant::now(), rows.clone()));
+        Ok(rows)
+    }
+}
+
+fn fetch_rows(pool: &Pool, sql: &str) -> Result<Vec<(String, Option<String>)>, APIErrors> {
+    let conn = pool.get();
+    if conn.is_err() {
+        error!("Error connecting to DB");
+        return Err(APIErrors::DBError);
+    }
+    let conn = conn.unwrap();
+
+    let stmt = conn.statement(sql).build();
+    if stmt.is_err() {
+        error!("Error building statement");
+        return Err(APIErrors::DBError);
+    }
+    let mut stmt = stmt.unwrap();
+
+    let rows = stmt.query(&[]);
+    if rows.is_err() {
+        error!("Error executing query");
+        return Err(APIErrors::DBError);
+    }
+    let rows = rows.unwrap();
+
+    let mut result: Vec<(String, Option<String>)> = Vec::new();
+    for row_result in rows {
+        if row_result.is_err() {
+            error!("Error fetching row");
+            return Err(APIErrors::DBError);
+        }
+        let row = row_result.unwrap();
+        let code: Option<String> = row.get(0).unwrap_or(None);
+        let desc: Option<String> = row.get(1).unwrap_or(None);
+        if let Some(code) = code {
+            result.push((code, desc));
+        }
+    }
+    Ok(result)
+}
+
+/// True when every token appears in the code or the description
+fn matches_tokens(tokens: &[String], code: &str, desc: Option<&str>) -> bool {
+    let text = format!("{} {}", search::normalize(code), search::normalize(desc.unwrap_or("")));
+    tokens.iter().all(|token| text.contains(token.as_str()))
+}
+
+/// Groups (category, subcategory) rows into a tree. When searching, a matching
+/// category keeps all its subcategories, otherwise only the matching ones are kept.
+fn build_category_tree(rows: &[(String, Option<String>)], tokens: &[String]) -> Vec<Category> {
+    let mut categories: Vec<(Category, bool)> = Vec::new();
+    for (cat, sub_cat) in rows {
+        if categories.last().map(|(c, _)| &c.ITEM_CAT != cat).unwrap_or(true) {
+            let cat_matches = matches_tokens(tokens, cat, None);
+            categories.push((
+                Category {
+                    ITEM_CAT: cat.clone(),
+                    SUB_CATEGORIES: Vec::new(),
+                },
+                cat_matches,
+            ));
+        }
+        let (category, cat_matches) = categories.last_mut().unwrap();
+        if let Some(sub_cat) = sub_cat {
+            if (*cat_matches || matches_tokens(tokens, sub_cat, None))
+                && !category.SUB_CATEGORIES.contains(sub_cat)
+            {
+                category.SUB_CATEGORIES.push(sub_cat.clone());
+            }
+        }
+    }
+    categories
+        .into_iter()
+        .filter(|(category, cat_matches)| *cat_matches || !category.SUB_CATEGORIES.is_empty())
+        .map(|(category, _)| category)
+        .collect()
+}
+
+pub async fn get_categories(
+    query: Option<&str>,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    cache: &ReferenceCache,
+) -> Result<Vec<Category>, APIErrors> {
+    let rows = cache.rows(ReferenceKind::Categories, pool, sql_manager)?;
+    let tokens = query.map(search::tokenize).unwrap_or_default();
+    Ok(build_category_tree(&rows, &tokens))
+}
+
+pub async fn get_reference_items(
+    kind: ReferenceKind,
+    query: Option<&str>,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    cache: &ReferenceCache,
+) -> Result<Vec<ReferenceItem>, APIErrors> {
+    let rows = cache.rows(kind, pool, sql_manager)?;
+    let tokens = query.map(search::tokenize).unwrap_or_default();
+
+    let mut items: Vec<ReferenceItem> = Vec::new();
+    for (code, desc) in rows.iter() {
+        // The same code can come with more than one description, the first one wins
+        if items.last().map(|item| &item.ID == code).unwrap_or(false) {
+            continue;
+        }
+        if !matches_tokens(&tokens, code, desc.as_deref()) {
+            continue;
+        }
+        items.push(ReferenceItem {
+            ID: code.clone(),
+            DESC: desc.clone(),
+        });
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows(values: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        values.iter().map(|(c, s)| (c.to_string(), s.map(|s| s.to_string()))).collect()
+    }
+
+    #[test]
+    fn test_build_category_tree() {
+        let rows = rows(&[
+            ("FOOD", Some("DAIRY")),
+            ("FOOD", Some("SNACKS")),
+            ("HOME", Some("CLEANING")),
+            ("TOYS", None),
+        ]);
+
+        let tree = build_category_tree(&rows, &[]);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree[0].SUB_CATEGORIES, vec!["DAIRY", "SNACKS"]);
+        assert!(tree[2].SUB_CATEGORIES.is_empty());
+
+        let tree = build_category_tree(&rows, &search::tokenize("snack"));
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].SUB_CATEGORIES, vec!["SNACKS"]);
+
+        let tree = build_category_tree(&rows, &search::tokenize("home"));
+        assert_eq!(tree[0].ITEM_CAT, "HOME");
+        assert_eq!(tree[0].SUB_CATEGORIES, vec!["CLEANING"]);
+    }
+}
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A code and its description, used for suppliers, countries, natures and trades
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceItem {
+    pub ID: String,
+    pub DESC: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Category {
+    pub ITEM_CAT: String,
+    pub SUB_CATEGORIES: Vec<String>,
+}
@@ -0,0 +1,393 @@
+pub mod structs;
+
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+
+use chrono::Local;
+use oracle::pool::Pool;
+use oracle::sql_type::ToSql;
+use oracle::Connection;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::functions::products::structs::{Product, QTY_STORES};
+use crate::functions::products::{get_decimal, get_user_store_ids, product_from_row};
+use crate::functions::reports::structs::{
+    LowStockParams, LowStockRow, Thresholds, ValuationLine, ValuationParams, ValuationReport,
+};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Stores the report covers: the user's stores, narrowed to `store` when given
+async fn report_store_ids(
+    store: Option<&str>,
+    key: &ApiKey<'_>,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+) -> Result<HashSet<String>, APIErrors> {
+    let mut store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    if let Some(store) = store {
+        store_ids.retain(|store_id| store_id == store);
+    }
+    Ok(store_ids)
+}
+
+/// Products for a report, `filters` are (column, value) pairs that must match exactly
+pub(crate) fn get_report_products(
+    filters: &[(&'static str, Option<&String>)],
+    store_ids: &HashSet<String>,
+    show_cost: bool,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+) -> Result<Vec<Product>, APIErrors> {
+    let conn = pool.get().map_err(|e| {
+        error!("Connection Error: {:?}", e);
+        APIErrors::DBError
+    })?;
+    query_report_products(&conn, filters, store_ids, show_cost, sql_manager)
+}
+
+/// `get_report_products` on a connection the caller already holds, for use inside its transaction
+pub(crate) fn query_report_products(
+    conn: &Connection,
+    filters: &[(&'static str, Option<&String>)],
+    store_ids: &HashSet<String>,
+    show_cost: bool,
+    sql_manager: &SQLManager,
+) -> Result<Vec<Product>, APIErrors> {
+    let mut sql = sql_manager.get_sql("get_report_products")?;
+    let mut binds: Vec<(&str, &dyn ToSql)> = Vec::new();
+    for (column_name, value) in filters {
+        if let Some(value) = value {
+            sql.push_str(&format!(" AND {} = :{}", column_name, column_name));
+            binds.push((column_name, *value as &dyn ToSql));
+        }
+    }
+    sql.push_str(" ORDER BY ITEM_ID");
+
+    let mut stmt = conn.statement(&sql).fetch_array_size(1000).build().map_err(|e| {
+        error!("Error building statement: {:?}", e);
+        APIErrors::DBError
+    })?;
+
+    let rows = stmt.query_named(&binds).map_err(|e| {
+        error!("Error executing query: {:?}", e);
+        APIErrors::DBError
+    })?;
+
+    let mut products: Vec<Product> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(|e| {
+            error!("Error fetching row: {:?}", e);
+            APIErrors::DBError
+        })?;
+        products.push(product_from_row(&row, store_ids, show_cost));
+    }
+    Ok(products)
+}
+
+fn get_thresholds(pool: &Pool, sql_manager: &SQLManager) -> Result<Thresholds, APIErrors> {
+    let conn = pool.get().map_err(|e| {
+        error!("Connection Error: {:?}", e);
+        APIErrors::DBError
+    })?;
+
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("get_reorder_thresholds")?.as_str())
+        .build()
+        .map_err(|e| {
+            error!("Error building statement: {:?}", e);
+            APIErrors::DBError
+        })?;
+
+    let rows = stmt.query(&[]).map_err(|e| {
+        error!("Error executing query: {:?}", e);
+        APIErrors::DBError
+    })?;
+
+    let mut thresholds = Thresholds::default();
+    for row_result in rows {
+        let row = row_result.map_err(|e| {
+            error!("Error fetching row: {:?}", e);
+            APIErrors::DBError
+        })?;
+        let scope_type: String = row.get("SCOPE_TYPE").unwrap_or_default();
+        let scope_value: String = row.get("SCOPE_VALUE").unwrap_or_default();
+        let min_qty = match get_decimal(&row, "MIN_QTY") {
+            Some(min_qty) => min_qty,
+            None => continue,
+        };
+        match scope_type.as_str() {
+            "GLOBAL" => thresholds.global = Some(min_qty),
+            "CATEGORY" => {
+                thresholds.categories.insert(scope_value, min_qty);
+            }
+            "ITEM" => {
+                thresholds.items.insert(scope_value, min_qty);
+            }
+            _ => info!("Unknown threshold scope: {}", scope_type),
+        }
+    }
+    Ok(thresholds)
+}
+
+/// Rows for every store where the item's quantity is under its threshold.
+/// Stores with no quantity recorded don't carry the item and are skipped.
+fn low_stock_rows(products: Vec<Product>, store_ids: &HashSet<String>, thresholds: &Thresholds) -> Vec<LowStockRow> {
+    let mut report: Vec<LowStockRow> = Vec::new();
+    for product in products {
+        let item_id = product.ITEM_ID.clone().unwrap_or_default();
+        let threshold = match thresholds.for_item(&item_id, product.ITEM_CAT.as_deref()) {
+            Some(threshold) => threshold,
+            None => continue,
+        };
+        for store_id in QTY_STORES {
+            if !store_ids.contains(store_id) {
+                continue;
+            }
+            let qty = match product.store_qty(store_id) {
+                Some(qty) => qty,
+                None => continue,
+            };
+            if qty >= threshold {
+                continue;
+            }
+            report.push(LowStockRow {
+                STORE_ID: store_id.to_string(),
+                ITEM_ID: item_id.clone(),
+                ITEM_DESC: product.ITEM_DESC.clone(),
+                ITEM_CAT: product.ITEM_CAT.clone(),
+                ITEM_SUB_CAT: product.ITEM_SUB_CAT.clone(),
+                SUPPLIER_ID: product.SUPPLIER_ID.clone(),
+                SUPPLIER_DESC: product.SUPPLIER_DESC.clone(),
+                QTY: qty,
+                THRESHOLD: threshold,
+                SHORTFALL: threshold - qty,
+            });
+        }
+    }
+    report.sort_by(|a, b| a.STORE_ID.cmp(&b.STORE_ID).then_with(|| a.ITEM_ID.cmp(&b.ITEM_ID)));
+    report
+}
+
+pub async fn get_low_stock_report(
+    params: &LowStockParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Vec<LowStockRow>, APIErrors> {
+    let mut thresholds = get_thresholds(pool, sql_manager)?;
+    if let Some(threshold) = &params.threshold {
+        let threshold = Decimal::from_str(threshold.trim()).map_err(|_| APIErrors::InvalidData)?;
+        thresholds.global = Some(threshold);
+    }
+
+    let store_ids = report_store_ids(params.store.as_deref(), key, pool, sql_manager).await?;
+    if store_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let filters = [
+        ("ITEM_CAT", params.category.as_ref()),
+        ("SUPPLIER_ID", params.supplier.as_ref()),
+    ];
+    let products = get_report_products(&filters, &store_ids, false, pool, sql_manager)?;
+    let report = low_stock_rows(products, &store_ids, &thresholds);
+    info!("Low stock report: {} rows", report.len());
+    Ok(report)
+}
+
+/// Running sums for one valuation group
+#[derive(Default)]
+struct ValuationSum {
+    desc: Option<String>,
+    items: HashSet<String>,
+    qty: Decimal,
+    cost_value: Decimal,
+    sale_value: Decimal,
+    priced_sale_value: Decimal,
+    margin: Decimal,
+}
+
+impl ValuationSum {
+    fn add(&mut self, item_id: &str, qty: Decimal, cost: Option<Decimal>, price: Option<Decimal>) {
+        self.items.insert(item_id.to_string());
+        self.qty += qty;
+        if let Some(cost) = cost {
+            self.cost_value += qty * cost;
+        }
+        if let Some(price) = price {
+            self.sale_value += qty * price;
+        }
+        if let (Some(cost), Some(price)) = (cost, price) {
+            self.priced_sale_value += qty * price;
+            self.margin += qty * (price - cost);
+        }
+    }
+
+    fn line(&self, key: &str, scale: u32) -> ValuationLine {
+        let round = |value: Decimal| value.round_dp_with_strategy(scale, RoundingStrategy::MidpointAwayFromZero);
+        let margin_per = if self.priced_sale_value.is_zero() {
+            None
+        } else {
+            Some((self.margin * Decimal::ONE_HUNDRED / self.priced_sale_value)
+                .round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero))
+        };
+        ValuationLine {
+            KEY: key.to_string(),
+            DESC: self.desc.clone(),
+            ITEMS: self.items.len() as u32,
+            QTY: self.qty,
+            COST_VALUE: round(self.cost_value),
+            SALE_VALUE: round(self.sale_value),
+            MARGIN: round(self.margin),
+            MARGIN_PER: margin_per,
+        }
+    }
+}
+
+fn valuation_lines(groups: &BTreeMap<String, ValuationSum>, scale: u32) -> Vec<ValuationLine> {
+    groups.iter().map(|(key, sum)| sum.line(key, scale)).collect()
+}
+
+/// Quantity times `T_AVE_COST` and times `SALE_PRICE_NOTAX_STORE_xx` (before discounts)
+/// for every store line with a non-zero quantity
+fn valuation(
+    products: &[Product],
+    store_ids: &HashSet<String>,
+    scale: u32,
+    label: String,
+    as_of: String,
+) -> ValuationReport {
+    let mut total = ValuationSum::default();
+    let mut by_store: BTreeMap<String, ValuationSum> = BTreeMap::new();
+    let mut by_category: BTreeMap<String, ValuationSum> = BTreeMap::new();
+    let mut by_supplier: BTreeMap<String, ValuationSum> = BTreeMap::new();
+    let mut by_country: BTreeMap<String, ValuationSum> = BTreeMap::new();
+
+    for product in products {
+        let item_id = product.ITEM_ID.clone().unwrap_or_default();
+        for store_id in QTY_STORES {
+            if !store_ids.contains(store_id) {
+                continue;
+            }
+            let qty = match product.store_qty(store_id) {
+                Some(qty) if !qty.is_zero() => qty,
+                _ => continue,
+            };
+            let cost = product.T_AVE_COST;
+            let (price, _, _) = product.store_pricing(store_id);
+
+            total.add(&item_id, qty, cost, price);
+            by_store.entry(store_id.to_string()).or_default().add(&item_id, qty, cost, price);
+            by_category
+                .entry(product.ITEM_CAT.clone().unwrap_or_default())
+                .or_default()
+                .add(&item_id, qty, cost, price);
+            let supplier = by_supplier.entry(product.SUPPLIER_ID.clone().unwrap_or_default()).or_default();
+            supplier.desc = product.SUPPLIER_DESC.clone();
+            supplier.add(&item_id, qty, cost, price);
+            let country = by_country.entry(product.COUNTRY.clone().unwrap_or_default()).or_default();
+            country.desc = product.COUNTRY_DESC.clone();
+            country.add(&item_id, qty, cost, price);
+        }
+    }
+
+    ValuationReport {
+        LABEL: label,
+        AS_OF: as_of,
+        TOTAL: total.line("TOTAL", scale),
+        BY_STORE: valuation_lines(&by_store, scale),
+        BY_CATEGORY: valuation_lines(&by_category, scale),
+        BY_SUPPLIER: valuation_lines(&by_supplier, scale),
+        BY_COUNTRY: valuation_lines(&by_country, scale),
+    }
+}
+
+pub async fn get_valuation_report(
+    params: &ValuationParams,
+    scale: u32,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<ValuationReport, APIErrors> {
+    let now = Local::now();
+    let label = match &params.label {
+        Some(label) if !label.trim().is_empty() => label.trim().to_string(),
+        _ => format!("As of {}", now.format("%Y-%m-%d %H:%M")),
+    };
+
+    let store_ids = report_store_ids(params.store.as_deref(), key, pool, sql_manager).await?;
+    let filters = [
+        ("ITEM_CAT", params.category.as_ref()),
+        ("SUPPLIER_ID", params.supplier.as_ref()),
+        ("COUNTRY", params.country.as_ref()),
+    ];
+    let products = if store_ids.is_empty() {
+        Vec::new()
+    } else {
+        get_report_products(&filters, &store_ids, true, pool, sql_manager)?
+    };
+
+    let report = valuation(&products, &store_ids, scale, label, now.to_rfc3339());
+    info!("Valuation report over {} items", report.TOTAL.ITEMS);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_low_stock_rows() {
+        let mut thresholds = Thresholds::default();
+        thresholds.global = Some(dec("5"));
+        thresholds.categories.insert("FOOD".to_string(), dec("20"));
+        thresholds.items.insert("2".to_string(), dec("1"));
+
+        let mut food = Product::default();
+        food.ITEM_ID = Some("1".to_string());
+        food.ITEM_CAT = Some("FOOD".to_string());
+        food.QTY_STORE_01 = Some(dec("10"));
+        food.QTY_STORE_02 = Some(dec("30"));
+        let mut item = Product::default();
+        item.ITEM_ID = Some("2".to_string());
+        item.ITEM_CAT = Some("FOOD".to_string());
+        item.QTY_STORE_01 = Some(dec("2"));
+
+        let store_ids: HashSet<String> = ["01", "02"].iter().map(|s| s.to_string()).collect();
+        let report = low_stock_rows(vec![food, item], &store_ids, &thresholds);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].ITEM_ID, "1");
+        assert_eq!(report[0].STORE_ID, "01");
+        assert_eq!(report[0].SHORTFALL, dec("10"));
+    }
+
+    #[test]
+    fn test_valuation() {
+        let mut product = Product::default();
+        product.ITEM_ID = Some("1".to_string());
+        product.ITEM_CAT = Some("FOOD".to_string());
+        product.T_AVE_COST = Some(dec("2.5"));
+        product.QTY_STORE_01 = Some(dec("10"));
+        product.SALE_PRICE_NOTAX_STORE_01 = Some(dec("4"));
+        // No price column for store 10, cost is still valued
+        product.QTY_STORE_10 = Some(dec("4"));
+
+        let store_ids: HashSet<String> = ["01", "10"].iter().map(|s| s.to_string()).collect();
+        let report = valuation(&[product], &store_ids, 3, "May".to_string(), String::new());
+        assert_eq!(report.TOTAL.QTY, dec("14"));
+        assert_eq!(report.TOTAL.COST_VALUE, dec("35"));
+        assert_eq!(report.TOTAL.SALE_VALUE, dec("40"));
+        assert_eq!(report.TOTAL.MARGIN, dec("15"));
+        assert_eq!(report.TOTAL.MARGIN_PER, Some(dec("37.5")));
+        assert_eq!(report.BY_STORE.len(), 2);
+        assert_eq!(report.BY_STORE[1].MARGIN_PER, None);
+        assert_eq!(report.BY_CATEGORY[0].KEY, "FOOD");
+        assert_eq!(report.BY_CATEGORY[0].ITEMS, 1);
+    }
+}
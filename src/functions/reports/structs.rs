@@ -0,0 +1,155 @@
+#![allow(non_snake_case)]
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::utils::export::{csv_document, CsvRow, CsvTable};
+
+#[derive(Debug, FromForm)]
+pub struct LowStockParams {
+    pub store: Option<String>,
+    pub category: Option<String>,
+    pub supplier: Option<String>,
+    /// Replaces the GLOBAL threshold for this request
+    pub threshold: Option<String>,
+}
+
+/// Reorder thresholds from REORDER_THRESHOLDS_JHC, see `for_item` for precedence
+#[derive(Debug, Default)]
+pub struct Thresholds {
+    pub global: Option<Decimal>,
+    pub categories: HashMap<String, Decimal>,
+    pub items: HashMap<String, Decimal>,
+}
+
+impl Thresholds {
+    /// Item threshold, then category threshold, then the global one
+    pub fn for_item(&self, item_id: &str, item_cat: Option<&str>) -> Option<Decimal> {
+        if let Some(threshold) = self.items.get(item_id) {
+            return Some(*threshold);
+        }
+        if let Some(threshold) = item_cat.and_then(|cat| self.categories.get(cat)) {
+            return Some(*threshold);
+        }
+        self.global
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LowStockRow {
+    pub STORE_ID: String,
+    pub ITEM_ID: String,
+    pub ITEM_DESC: Option<String>,
+    pub ITEM_CAT: Option<String>,
+    pub ITEM_SUB_CAT: Option<String>,
+    pub SUPPLIER_ID: Option<String>,
+    pub SUPPLIER_DESC: Option<String>,
+    pub QTY: Decimal,
+    pub THRESHOLD: Decimal,
+    pub SHORTFALL: Decimal,
+}
+
+impl CsvRow for LowStockRow {
+    fn headers() -> &'static [&'static str] {
+        &[
+            "STORE_ID", "ITEM_ID", "ITEM_DESC", "ITEM_CAT", "ITEM_SUB_CAT",
+            "SUPPLIER_ID", "SUPPLIER_DESC", "QTY", "THRESHOLD", "SHORTFALL",
+        ]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.STORE_ID.clone(),
+            self.ITEM_ID.clone(),
+            self.ITEM_DESC.clone().unwrap_or_default(),
+            self.ITEM_CAT.clone().unwrap_or_default(),
+            self.ITEM_SUB_CAT.clone().unwrap_or_default(),
+            self.SUPPLIER_ID.clone().unwrap_or_default(),
+            self.SUPPLIER_DESC.clone().unwrap_or_default(),
+            self.QTY.to_string(),
+            self.THRESHOLD.to_string(),
+            self.SHORTFALL.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, FromForm)]
+pub struct ValuationParams {
+    pub store: Option<String>,
+    pub category: Option<String>,
+    pub supplier: Option<String>,
+    pub country: Option<String>,
+    /// Free text naming the snapshot, e.g. "Month-end 2024-05"
+    pub label: Option<String>,
+}
+
+/// Valuation of one group. `MARGIN` only covers lines that have both a cost and a sale
+/// price, so stores without prices don't show up as a negative margin.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ValuationLine {
+    pub KEY: String,
+    pub DESC: Option<String>,
+    pub ITEMS: u32,
+    pub QTY: Decimal,
+    pub COST_VALUE: Decimal,
+    pub SALE_VALUE: Decimal,
+    pub MARGIN: Decimal,
+    pub MARGIN_PER: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValuationReport {
+    pub LABEL: String,
+    pub AS_OF: String,
+    pub TOTAL: ValuationLine,
+    pub BY_STORE: Vec<ValuationLine>,
+    pub BY_CATEGORY: Vec<ValuationLine>,
+    pub BY_SUPPLIER: Vec<ValuationLine>,
+    pub BY_COUNTRY: Vec<ValuationLine>,
+}
+
+impl ValuationReport {
+    fn groups(&self) -> [(&'static str, &Vec<ValuationLine>); 4] {
+        [
+            ("STORE", &self.BY_STORE),
+            ("CATEGORY", &self.BY_CATEGORY),
+            ("SUPPLIER", &self.BY_SUPPLIER),
+            ("COUNTRY", &self.BY_COUNTRY),
+        ]
+    }
+}
+
+fn valuation_values(label: &str, as_of: &str, dimension: &str, line: &ValuationLine) -> Vec<String> {
+    vec![
+        label.to_string(),
+        as_of.to_string(),
+        dimension.to_string(),
+        line.KEY.clone(),
+        line.DESC.clone().unwrap_or_default(),
+        line.ITEMS.to_string(),
+        line.QTY.to_string(),
+        line.COST_VALUE.to_string(),
+        line.SALE_VALUE.to_string(),
+        line.MARGIN.to_string(),
+        line.MARGIN_PER.map(|m| m.to_string()).unwrap_or_default(),
+    ]
+}
+
+/// One CSV line per group, the dimension in its own column and the total last
+impl CsvTable for ValuationReport {
+    fn to_csv(&self) -> String {
+        let mut lines: Vec<Vec<String>> = Vec::new();
+        for (dimension, group) in self.groups() {
+            for line in group {
+                lines.push(valuation_values(&self.LABEL, &self.AS_OF, dimension, line));
+            }
+        }
+        lines.push(valuation_values(&self.LABEL, &self.AS_OF, "TOTAL", &self.TOTAL));
+        let headers = [
+            "LABEL", "AS_OF", "DIMENSION", "KEY", "DESC", "ITEMS", "QTY",
+            "COST_VALUE", "SALE_VALUE", "MARGIN", "MARGIN_PER",
+        ];
+        csv_document(&headers, &lines)
+    }
+}
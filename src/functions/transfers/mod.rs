@@ -0,0 +1,474 @@
+pub mod structs;
+
+use std::collections::{HashMap, HashSet};
+
+use oracle::pool::Pool;
+use oracle::sql_type::ToSql;
+use oracle::{Connection, Row};
+use rust_decimal::Decimal;
+
+use crate::functions::authentication::get_username;
+use crate::functions::products::structs::QTY_STORES;
+use crate::functions::products::{get_decimal, get_user_store_ids, query_products_by_ids};
+use crate::functions::transfers::structs::{
+    CreateTransferParams, Transfer, TransferAction, TransferActionParams, TransferAudit, TransferLine,
+    TransferLineParams, TransferListParams, TransferStatus,
+};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::db::{db_error, in_transaction, NUMBER};
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+fn validate_store(store_id: &str) -> Result<(), APIErrors> {
+    if QTY_STORES.contains(&store_id) {
+        Ok(())
+    } else {
+        error!("Unknown store: {}", store_id);
+        Err(APIErrors::InvalidData)
+    }
+}
+
+/// Current quantity of each item in `store_id`, items missing from JHC_INVDATA are left out.
+/// Read on the caller's connection, which may hold the transfer lock.
+fn current_stock(
+    conn: &Connection,
+    item_ids: &[String],
+    store_id: &str,
+) -> Result<HashMap<String, Decimal>, APIErrors> {
+    let store_ids: HashSet<String> = HashSet::from([store_id.to_string()]);
+    let products = query_products_by_ids(conn, item_ids, &store_ids, false)?;
+    let mut stock: HashMap<String, Decimal> = HashMap::new();
+    for product in products {
+        if let Some(item_id) = &product.ITEM_ID {
+            stock.insert(item_id.clone(), product.store_qty(store_id).unwrap_or(Decimal::ZERO));
+        }
+    }
+    Ok(stock)
+}
+
+/// Fails when any quantity is over what the source store currently holds
+fn check_stock(quantities: &[(String, Decimal)], stock: &HashMap<String, Decimal>) -> Result<(), APIErrors> {
+    for (item_id, qty) in quantities {
+        match stock.get(item_id) {
+            None => {
+                error!("Item {} not found", item_id);
+                return Err(APIErrors::InvalidData);
+            }
+            Some(available) if qty > available => {
+                error!("Not enough stock for {}: {} requested, {} available", item_id, qty, available);
+                return Err(APIErrors::InvalidData);
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Quantities for the next step: each line starts from `base` (what the previous step
+/// allowed) and can be lowered through `overrides`, never raised above it
+fn resolve_quantities(
+    lines: &[TransferLine],
+    base: impl Fn(&TransferLine) -> Decimal,
+    overrides: Option<&Vec<TransferLineParams>>,
+) -> Result<Vec<(String, Decimal)>, APIErrors> {
+    let mut quantities: Vec<(String, Decimal)> = lines.iter().map(|line| (line.ITEM_ID.clone(), base(line))).collect();
+    for line in overrides.map(|o| o.as_slice()).unwrap_or(&[]) {
+        let index = lines.iter().position(|l| l.ITEM_ID == line.p_item_id).ok_or_else(|| {
+            error!("Item {} is not part of the transfer", line.p_item_id);
+            APIErrors::InvalidData
+        })?;
+        if line.p_qty < Decimal::ZERO || line.p_qty > base(&lines[index]) {
+            error!("Invalid quantity {} for {}", line.p_qty, line.p_item_id);
+            return Err(APIErrors::InvalidData);
+        }
+        quantities[index].1 = line.p_qty;
+    }
+    Ok(quantities)
+}
+
+fn transfer_from_row(row: &Row) -> Result<Transfer, APIErrors> {
+    let status: String = row.get("STATUS").map_err(db_error)?;
+    Ok(Transfer {
+        TRANSFER_ID: row.get("TRANSFER_ID").map_err(db_error)?,
+        FROM_STORE: row.get("FROM_STORE").map_err(db_error)?,
+        TO_STORE: row.get("TO_STORE").map_err(db_error)?,
+        STATUS: TransferStatus::parse(&status).ok_or(APIErrors::InternalServerError)?,
+        REQUESTED_BY: row.get("REQUESTED_BY").map_err(db_error)?,
+        NOTE: row.get("NOTE").unwrap_or(None),
+        CREATED_AT: row.get("CREATED_AT").unwrap_or(None),
+        UPDATED_AT: row.get("UPDATED_AT").unwrap_or(None),
+        LINES: Vec::new(),
+        AUDIT: Vec::new(),
+    })
+}
+
+/// Full transfer with lines and audit trail. With `for_update` the transfer row stays
+/// locked until the connection commits, so two users can't act on it at the same time.
+fn read_transfer(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    transfer_id: i64,
+    for_update: bool,
+) -> Result<Transfer, APIErrors> {
+    let sql = if for_update {
+        sql_manager.get_sql("get_transfer_for_update")?
+    } else {
+        format!("{} AND TRANSFER_ID = :1", sql_manager.get_sql("get_transfers")?)
+    };
+    let row = match conn.query_row(&sql, &[&transfer_id]) {
+        Ok(row) => row,
+        Err(e) => {
+            info!("Transfer {} not found: {}", transfer_id, e);
+            return Err(APIErrors::NoData);
+        }
+    };
+    let mut transfer = transfer_from_row(&row)?;
+
+    let rows = conn
+        .query(sql_manager.get_sql("get_transfer_lines")?.as_str(), &[&transfer_id])
+        .map_err(db_error)?;
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        transfer.LINES.push(TransferLine {
+            ITEM_ID: row.get("ITEM_ID").map_err(db_error)?,
+            QTY_REQUESTED: get_decimal(&row, "QTY_REQUESTED").unwrap_or(Decimal::ZERO),
+            QTY_APPROVED: get_decimal(&row, "QTY_APPROVED"),
+            QTY_SHIPPED: get_decimal(&row, "QTY_SHIPPED"),
+            QTY_RECEIVED: get_decimal(&row, "QTY_RECEIVED"),
+        });
+    }
+
+    let rows = conn
+        .query(sql_manager.get_sql("get_transfer_audit")?.as_str(), &[&transfer_id])
+        .map_err(db_error)?;
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        transfer.AUDIT.push(TransferAudit {
+            ACTION: row.get("ACTION").map_err(db_error)?,
+            USERNAME: row.get("USERNAME").map_err(db_error)?,
+            FROM_STATUS: row.get("FROM_STATUS").unwrap_or(None),
+            TO_STATUS: row.get("TO_STATUS").map_err(db_error)?,
+            NOTE: row.get("NOTE").unwrap_or(None),
+            CREATED_AT: row.get("CREATED_AT").unwrap_or(None),
+        });
+    }
+    Ok(transfer)
+}
+
+fn insert_audit(
+    conn: &Connection,
+    sql_manager: &SQLManager,
+    transfer_id: i64,
+    action: &str,
+    username: &str,
+    from_status: Option<TransferStatus>,
+    to_status: TransferStatus,
+    note: &Option<String>,
+) -> Result<(), APIErrors> {
+    conn.execute(
+        sql_manager.get_sql("insert_transfer_audit")?.as_str(),
+        &[
+            &transfer_id,
+            &action,
+            &username,
+            &from_status.map(|s| s.as_str()),
+            &to_status.as_str(),
+            note,
+        ],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+/// A user of the destination store asks the source store for stock
+pub async fn create_transfer(
+    params: &CreateTransferParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Transfer, APIErrors> {
+    let username = get_username(key)?;
+    validate_store(&params.p_from_store)?;
+    validate_store(&params.p_to_store)?;
+    if params.p_from_store == params.p_to_store || params.p_lines.is_empty() {
+        return Err(APIErrors::InvalidData);
+    }
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    if !store_ids.contains(&params.p_to_store) {
+        return Err(APIErrors::NoStoreAccess);
+    }
+
+    let mut quantities: Vec<(String, Decimal)> = Vec::new();
+    for line in params.p_lines.iter() {
+        if line.p_qty <= Decimal::ZERO || quantities.iter().any(|(item_id, _)| item_id == &line.p_item_id) {
+            error!("Invalid or duplicate line for {}", line.p_item_id);
+            return Err(APIErrors::InvalidData);
+        }
+        quantities.push((line.p_item_id.clone(), line.p_qty));
+    }
+    let item_ids: Vec<String> = quantities.iter().map(|(item_id, _)| item_id.clone()).collect();
+    let conn = pool.get().map_err(db_error)?;
+    check_stock(&quantities, &current_stock(&conn, &item_ids, &params.p_from_store)?)?;
+
+    let transfer_id = in_transaction(&conn, || {
+        let transfer_id: i64 = conn
+            .query_row_as(sql_manager.get_sql("next_transfer_id")?.as_str(), &[])
+            .map_err(db_error)?;
+        let status = TransferStatus::Requested;
+        conn.execute(
+            sql_manager.get_sql("insert_transfer")?.as_str(),
+            &[
+                &transfer_id,
+                &params.p_from_store,
+                &params.p_to_store,
+                &status.as_str(),
+                &username,
+                &params.p_note,
+            ],
+        )
+        .map_err(db_error)?;
+        let insert_line = sql_manager.get_sql("insert_transfer_line")?;
+        for (item_id, qty) in quantities.iter() {
+            conn.execute(&insert_line, &[&transfer_id, item_id, &(&qty.to_string(), &NUMBER)])
+                .map_err(db_error)?;
+        }
+        insert_audit(&conn, sql_manager, transfer_id, "REQUEST", &username, None, status, &params.p_note)?;
+        Ok(transfer_id)
+    })?;
+
+    info!("Transfer {} requested by {}", transfer_id, username);
+    read_transfer(&conn, sql_manager, transfer_id, false)
+}
+
+/// Transfers from or to the user's stores, newest first, without lines
+pub async fn get_transfers(
+    params: &TransferListParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Vec<Transfer>, APIErrors> {
+    let mut store_ids: Vec<String> = get_user_store_ids(key, pool, sql_manager).await?.into_iter().collect();
+    if let Some(store) = &params.store {
+        store_ids.retain(|store_id| store_id == store);
+    }
+    if store_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    store_ids.sort();
+    let status = match &params.status {
+        Some(status) => Some(TransferStatus::parse(&status.to_uppercase()).ok_or(APIErrors::InvalidData)?),
+        None => None,
+    };
+
+    let bind_names: Vec<String> = (0..store_ids.len()).map(|i| format!("store_{}", i)).collect();
+    let placeholders: Vec<String> = bind_names.iter().map(|name| format!(":{}", name)).collect();
+    let mut sql = sql_manager.get_sql("get_transfers")?;
+    sql.push_str(&format!(
+        " AND (FROM_STORE IN ({0}) OR TO_STORE IN ({0}))",
+        placeholders.join(", ")
+    ));
+    let mut binds: Vec<(&str, &dyn ToSql)> = Vec::new();
+    for (name, store_id) in bind_names.iter().zip(store_ids.iter()) {
+        binds.push((name.as_str(), store_id as &dyn ToSql));
+    }
+    let status_str = status.map(|s| s.as_str());
+    if let Some(status_str) = &status_str {
+        sql.push_str(" AND STATUS = :status");
+        binds.push(("status", status_str as &dyn ToSql));
+    }
+    sql.push_str(" ORDER BY TRANSFER_ID DESC FETCH FIRST 500 ROWS ONLY");
+
+    let conn = pool.get().map_err(db_error)?;
+    let mut stmt = conn.statement(&sql).build().map_err(db_error)?;
+    let rows = stmt.query_named(&binds).map_err(db_error)?;
+    let mut transfers: Vec<Transfer> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        transfers.push(transfer_from_row(&row)?);
+    }
+    Ok(transfers)
+}
+
+pub async fn get_transfer(
+    transfer_id: i64,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Transfer, APIErrors> {
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    let conn = pool.get().map_err(db_error)?;
+    let transfer = read_transfer(&conn, sql_manager, transfer_id, false)?;
+    if !store_ids.contains(&transfer.FROM_STORE) && !store_ids.contains(&transfer.TO_STORE) {
+        return Err(APIErrors::NoStoreAccess);
+    }
+    Ok(transfer)
+}
+
+/// Moves a transfer to its next state.
+/// Approve, reject and ship are done by the source store, receive by the destination
+/// store, cancel by the destination store or the requester before shipping.
+pub async fn apply_transfer_action(
+    transfer_id: i64,
+    action: TransferAction,
+    params: &TransferActionParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Transfer, APIErrors> {
+    let username = get_username(key)?;
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+
+    let conn = pool.get().map_err(db_error)?;
+    in_transaction(&conn, || {
+        let transfer = read_transfer(&conn, sql_manager, transfer_id, true)?;
+        let allowed = match action {
+            TransferAction::Approve | TransferAction::Reject | TransferAction::Ship => {
+                store_ids.contains(&transfer.FROM_STORE)
+            }
+            TransferAction::Receive => store_ids.contains(&transfer.TO_STORE),
+            TransferAction::Cancel => store_ids.contains(&transfer.TO_STORE) || transfer.REQUESTED_BY == username,
+        };
+        if !allowed {
+            return Err(APIErrors::NoStoreAccess);
+        }
+        if !transfer.STATUS.accepts(action) {
+            info!("Transfer {} is {}, can't {}", transfer_id, transfer.STATUS.as_str(), action.as_str());
+            return Err(APIErrors::InvalidState);
+        }
+
+        let lines = &transfer.LINES;
+        let item_ids: Vec<String> = lines.iter().map(|line| line.ITEM_ID.clone()).collect();
+        let mut updated: Vec<TransferLine> = lines.clone();
+        let next_status = match action {
+            TransferAction::Approve => {
+                let approved = resolve_quantities(lines, |line| line.QTY_REQUESTED, params.p_lines.as_ref())?;
+                if approved.iter().all(|(_, qty)| qty.is_zero()) {
+                    // Nothing approved is a rejection
+                    return Err(APIErrors::InvalidData);
+                }
+                check_stock(&approved, &current_stock(&conn, &item_ids, &transfer.FROM_STORE)?)?;
+                for (line, (_, qty)) in updated.iter_mut().zip(approved.iter()) {
+                    line.QTY_APPROVED = Some(*qty);
+                }
+                if updated.iter().all(|line| line.QTY_APPROVED == Some(line.QTY_REQUESTED)) {
+                    TransferStatus::Approved
+                } else {
+                    TransferStatus::PartiallyApproved
+                }
+            }
+            TransferAction::Ship => {
+                // Everything approved is shipped, stock is checked again as it may have moved
+                let shipped = resolve_quantities(lines, |line| line.QTY_APPROVED.unwrap_or(Decimal::ZERO), None)?;
+                check_stock(&shipped, &current_stock(&conn, &item_ids, &transfer.FROM_STORE)?)?;
+                for (line, (_, qty)) in updated.iter_mut().zip(shipped.iter()) {
+                    line.QTY_SHIPPED = Some(*qty);
+                }
+                TransferStatus::Shipped
+            }
+            TransferAction::Receive => {
+                let received = resolve_quantities(
+                    lines,
+                    |line| line.QTY_SHIPPED.unwrap_or(Decimal::ZERO),
+                    params.p_lines.as_ref(),
+                )?;
+                for (line, (_, qty)) in updated.iter_mut().zip(received.iter()) {
+                    line.QTY_RECEIVED = Some(*qty);
+                }
+                TransferStatus::Received
+            }
+            TransferAction::Reject => TransferStatus::Rejected,
+            TransferAction::Cancel => TransferStatus::Cancelled,
+        };
+
+        let update_line = sql_manager.get_sql("update_transfer_line")?;
+        for line in updated.iter().filter(|line| !lines.contains(line)) {
+            conn.execute(
+                &update_line,
+                &[
+                    &(&line.QTY_APPROVED.map(|q| q.to_string()), &NUMBER),
+                    &(&line.QTY_SHIPPED.map(|q| q.to_string()), &NUMBER),
+                    &(&line.QTY_RECEIVED.map(|q| q.to_string()), &NUMBER),
+                    &transfer_id,
+                    &line.ITEM_ID,
+                ],
+            )
+            .map_err(db_error)?;
+        }
+        conn.execute(
+            sql_manager.get_sql("update_transfer_status")?.as_str(),
+            &[&next_status.as_str(), &transfer_id],
+        )
+        .map_err(db_error)?;
+        insert_audit(
+            &conn,
+            sql_manager,
+            transfer_id,
+            action.as_str(),
+            &username,
+            Some(transfer.STATUS),
+            next_status,
+            &params.p_note,
+        )?;
+        info!("Transfer {} moved to {} by {}", transfer_id, next_status.as_str(), username);
+        Ok(())
+    })?;
+
+    read_transfer(&conn, sql_manager, transfer_id, false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn line(item_id: &str, requested: &str) -> TransferLine {
+        TransferLine {
+            ITEM_ID: item_id.to_string(),
+            QTY_REQUESTED: dec(requested),
+            QTY_APPROVED: None,
+            QTY_SHIPPED: None,
+            QTY_RECEIVED: None,
+        }
+    }
+
+    #[test]
+    fn test_transitions() {
+        assert!(TransferStatus::Requested.accepts(TransferAction::Approve));
+        assert!(TransferStatus::PartiallyApproved.accepts(TransferAction::Ship));
+        assert!(TransferStatus::Shipped.accepts(TransferAction::Receive));
+        assert!(!TransferStatus::Requested.accepts(TransferAction::Ship));
+        assert!(!TransferStatus::Shipped.accepts(TransferAction::Cancel));
+        assert!(!TransferStatus::Received.accepts(TransferAction::Receive));
+        assert_eq!(TransferStatus::parse("PARTIALLY_APPROVED"), Some(TransferStatus::PartiallyApproved));
+    }
+
+    #[test]
+    fn test_resolve_quantities() {
+        let lines = vec![line("1", "10"), line("2", "5")];
+        let overrides = vec![TransferLineParams {
+            p_item_id: "2".to_string(),
+            p_qty: dec("3"),
+        }];
+        let approved = resolve_quantities(&lines, |l| l.QTY_REQUESTED, Some(&overrides)).unwrap();
+        assert_eq!(approved, vec![("1".to_string(), dec("10")), ("2".to_string(), dec("3"))]);
+
+        // More than requested, or an item that isn't in the transfer
+        let too_many = vec![TransferLineParams {
+            p_item_id: "1".to_string(),
+            p_qty: dec("11"),
+        }];
+        assert!(resolve_quantities(&lines, |l| l.QTY_REQUESTED, Some(&too_many)).is_err());
+        let unknown = vec![TransferLineParams {
+            p_item_id: "3".to_string(),
+            p_qty: dec("1"),
+        }];
+        assert!(resolve_quantities(&lines, |l| l.QTY_REQUESTED, Some(&unknown)).is_err());
+
+        let stock = HashMap::from([("1".to_string(), dec("10")), ("2".to_string(), dec("2"))]);
+        assert!(check_stock(&approved, &stock).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+#![allow(non_snake_case)]
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransferStatus {
+    Requested,
+    Approved,
+    PartiallyApproved,
+    Rejected,
+    Shipped,
+    Received,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferAction {
+    Approve,
+    Reject,
+    Ship,
+    Receive,
+    Cancel,
+}
+
+impl TransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferStatus::Requested => "REQUESTED",
+            TransferStatus::Approved => "APPROVED",
+            TransferStatus::PartiallyApproved => "PARTIALLY_APPROVED",
+            TransferStatus::Rejected => "REJECTED",
+            TransferStatus::Shipped => "SHIPPED",
+            TransferStatus::Received => "RECEIVED",
+            TransferStatus::Cancelled => "CANCELLED",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<TransferStatus> {
+        match value {
+            "REQUESTED" => Some(TransferStatus::Requested),
+            "APPROVED" => Some(TransferStatus::Approved),
+            "PARTIALLY_APPROVED" => Some(TransferStatus::PartiallyApproved),
+            "REJECTED" => Some(TransferStatus::Rejected),
+            "SHIPPED" => Some(TransferStatus::Shipped),
+            "RECEIVED" => Some(TransferStatus::Received),
+            "CANCELLED" => Some(TransferStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// Whether `action` is allowed from this status. Approve resolves to
+    /// APPROVED or PARTIALLY_APPROVED depending on the approved quantities.
+    pub fn accepts(&self, action: TransferAction) -> bool {
+        matches!(
+            (self, action),
+            (TransferStatus::Requested, TransferAction::Approve)
+                | (TransferStatus::Requested, TransferAction::Reject)
+                | (TransferStatus::Requested, TransferAction::Cancel)
+                | (TransferStatus::Approved, TransferAction::Ship)
+                | (TransferStatus::Approved, TransferAction::Cancel)
+                | (TransferStatus::PartiallyApproved, TransferAction::Ship)
+                | (TransferStatus::PartiallyApproved, TransferAction::Cancel)
+                | (TransferStatus::Shipped, TransferAction::Receive)
+        )
+    }
+}
+
+impl TransferAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferAction::Approve => "APPROVE",
+            TransferAction::Reject => "REJECT",
+            TransferAction::Ship => "SHIP",
+            TransferAction::Receive => "RECEIVE",
+            TransferAction::Cancel => "CANCEL",
+        }
+    }
+}
+
+impl<'a> rocket::request::FromParam<'a> for TransferAction {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        match param {
+            "approve" => Ok(TransferAction::Approve),
+            "reject" => Ok(TransferAction::Reject),
+            "ship" => Ok(TransferAction::Ship),
+            "receive" => Ok(TransferAction::Receive),
+            "cancel" => Ok(TransferAction::Cancel),
+            _ => Err(param),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferLineParams {
+    pub p_item_id: String,
+    pub p_qty: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTransferParams {
+    pub p_from_store: String,
+    pub p_to_store: String,
+    pub p_note: Option<String>,
+    pub p_lines: Vec<TransferLineParams>,
+}
+
+/// Body of an action. `p_lines` overrides the approved or received quantity of
+/// the listed items, other items keep the quantity of the previous step.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransferActionParams {
+    pub p_note: Option<String>,
+    pub p_lines: Option<Vec<TransferLineParams>>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct TransferListParams {
+    pub store: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransferLine {
+    pub ITEM_ID: String,
+    pub QTY_REQUESTED: Decimal,
+    pub QTY_APPROVED: Option<Decimal>,
+    pub QTY_SHIPPED: Option<Decimal>,
+    pub QTY_RECEIVED: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferAudit {
+    pub ACTION: String,
+    pub USERNAME: String,
+    pub FROM_STATUS: Option<String>,
+    pub TO_STATUS: String,
+    pub NOTE: Option<String>,
+    pub CREATED_AT: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfer {
+    pub TRANSFER_ID: i64,
+    pub FROM_STORE: String,
+    pub TO_STORE: String,
+    pub STATUS: TransferStatus,
+    pub REQUESTED_BY: String,
+    pub NOTE: Option<String>,
+    pub CREATED_AT: Option<String>,
+    pub UPDATED_AT: Option<String>,
+    #[serde(default)]
+    pub LINES: Vec<TransferLine>,
+    #[serde(default)]
+    pub AUDIT: Vec<TransferAudit>,
+}
@@ -0,0 +1,427 @@
+pub mod notifier;
+pub mod structs;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use oracle::pool::Pool;
+use oracle::sql_type::OracleType;
+use oracle::{Connection, Row};
+use rust_decimal::Decimal;
+
+use crate::functions::authentication::get_username;
+use crate::functions::history::structs::{Change, PRICE_FIELDS, QTY_FIELD};
+use crate::functions::products::{get_products_by_ids, get_user_store_ids};
+use crate::functions::watchlist::notifier::Notifier;
+use crate::functions::watchlist::structs::{
+    Alert, AlertKind, Notification, Recipient, WatchParams, Watcher, WatchlistEntry,
+};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::db::{db_error, in_transaction};
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+fn entry_from_row(row: &Row) -> Result<WatchlistEntry, APIErrors> {
+    Ok(WatchlistEntry {
+        WATCH_ID: row.get("WATCH_ID").map_err(db_error)?,
+        ITEM_ID: row.get("ITEM_ID").map_err(db_error)?,
+        STORE_ID: row.get("STORE_ID").map_err(db_error)?,
+        ON_STOCKOUT: row.get::<_, i64>("ON_STOCKOUT").map_err(db_error)? != 0,
+        ON_PRICE: row.get::<_, i64>("ON_PRICE").map_err(db_error)? != 0,
+        CREATED_AT: row.get("CREATED_AT").unwrap_or(None),
+    })
+}
+
+fn read_watchlist(conn: &Connection, username: &str, sql_manager: &SQLManager) -> Result<Vec<WatchlistEntry>, APIErrors> {
+    let rows = conn
+        .query(sql_manager.get_sql("get_watchlist")?.as_str(), &[&username])
+        .map_err(db_error)?;
+    let mut entries: Vec<WatchlistEntry> = Vec::new();
+    for row_result in rows {
+        entries.push(entry_from_row(&row_result.map_err(db_error)?)?);
+    }
+    Ok(entries)
+}
+
+/// Store must be one the user can access, the item must exist
+async fn validate_watch(
+    params: &WatchParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<(), APIErrors> {
+    let store_ids = get_user_store_ids(key, pool, sql_manager).await?;
+    if let Some(store_id) = &params.p_store_id {
+        if !store_ids.contains(store_id) {
+            return Err(APIErrors::NoStoreAccess);
+        }
+    }
+    let item_id = params.p_item_id.trim();
+    if item_id.is_empty() || get_products_by_ids(&[item_id.to_string()], pool, &store_ids, false)?.is_empty() {
+        error!("Unknown item: {}", params.p_item_id);
+        return Err(APIErrors::InvalidData);
+    }
+    Ok(())
+}
+
+pub async fn get_watchlist(pool: &Pool, sql_manager: &SQLManager, key: &ApiKey<'_>) -> Result<Vec<WatchlistEntry>, APIErrors> {
+    let username = get_username(key)?;
+    let conn = pool.get().map_err(db_error)?;
+    read_watchlist(&conn, &username, sql_manager)
+}
+
+pub async fn add_watch(
+    params: &WatchParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<WatchlistEntry, APIErrors> {
+    let username = get_username(key)?;
+    validate_watch(params, pool, sql_manager, key).await?;
+    let item_id = params.p_item_id.trim().to_string();
+
+    let conn = pool.get().map_err(db_error)?;
+    let existing = read_watchlist(&conn, &username, sql_manager)?;
+    if existing.iter().any(|entry| entry.ITEM_ID == item_id && entry.STORE_ID == params.p_store_id) {
+        return Err(APIErrors::InvalidState);
+    }
+
+    let watch_id = in_transaction(&conn, || {
+        let mut stmt = conn
+            .statement(sql_manager.get_sql("insert_watch")?.as_str())
+            .build()
+            .map_err(db_error)?;
+        stmt.execute(&[
+            &username,
+            &item_id,
+            &params.p_store_id,
+            &(params.p_on_stockout.unwrap_or(true) as i32),
+            &(params.p_on_price.unwrap_or(true) as i32),
+            &OracleType::Number(0, 0),
+        ])
+        .map_err(db_error)?;
+        let ids: Vec<i64> = stmt.returned_values(6).map_err(db_error)?;
+        ids.first().copied().ok_or(APIErrors::DBError)
+    })?;
+
+    read_watchlist(&conn, &username, sql_manager)?
+        .into_iter()
+        .find(|entry| entry.WATCH_ID == watch_id)
+        .ok_or(APIErrors::NoData)
+}
+
+/// Changes the store and alert kinds of an entry, the item can't be changed
+pub async fn update_watch(
+    watch_id: i64,
+    params: &WatchParams,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<WatchlistEntry, APIErrors> {
+    let username = get_username(key)?;
+    // Before taking a connection, validating takes its own
+    validate_watch(params, pool, sql_manager, key).await?;
+
+    let conn = pool.get().map_err(db_error)?;
+    let existing = read_watchlist(&conn, &username, sql_manager)?;
+    let entry = existing.iter().find(|entry| entry.WATCH_ID == watch_id).ok_or(APIErrors::NoData)?;
+    if entry.ITEM_ID != params.p_item_id.trim() {
+        return Err(APIErrors::InvalidData);
+    }
+    if existing
+        .iter()
+        .any(|other| other.WATCH_ID != watch_id && other.ITEM_ID == entry.ITEM_ID && other.STORE_ID == params.p_store_id)
+    {
+        return Err(APIErrors::InvalidState);
+    }
+
+    in_transaction(&conn, || {
+        conn.execute(
+            sql_manager.get_sql("update_watch")?.as_str(),
+            &[
+                &params.p_store_id,
+                &(params.p_on_stockout.unwrap_or(entry.ON_STOCKOUT) as i32),
+                &(params.p_on_price.unwrap_or(entry.ON_PRICE) as i32),
+                &watch_id,
+                &username,
+            ],
+        )
+        .map_err(db_error)?;
+        Ok(())
+    })?;
+
+    read_watchlist(&conn, &username, sql_manager)?
+        .into_iter()
+        .find(|entry| entry.WATCH_ID == watch_id)
+        .ok_or(APIErrors::NoData)
+}
+
+pub async fn delete_watch(watch_id: i64, pool: &Pool, sql_manager: &SQLManager, key: &ApiKey<'_>) -> Result<(), APIErrors> {
+    let username = get_username(key)?;
+    let conn = pool.get().map_err(db_error)?;
+    in_transaction(&conn, || {
+        let stmt = conn
+            .execute(sql_manager.get_sql("delete_watch")?.as_str(), &[&watch_id, &username])
+            .map_err(db_error)?;
+        match stmt.row_count().map_err(db_error)? {
+            0 => Err(APIErrors::NoData),
+            _ => Ok(()),
+        }
+    })
+}
+
+pub async fn get_notifications(
+    unread: bool,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<Vec<Notification>, APIErrors> {
+    let username = get_username(key)?;
+    let conn = pool.get().map_err(db_error)?;
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("get_notifications")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    let rows = stmt
+        .query_named(&[("username", &username), ("unread", &(unread as i32))])
+        .map_err(db_error)?;
+    let mut notifications: Vec<Notification> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        notifications.push(Notification {
+            NOTIFICATION_ID: row.get("NOTIFICATION_ID").map_err(db_error)?,
+            ITEM_ID: row.get("ITEM_ID").map_err(db_error)?,
+            STORE_ID: row.get("STORE_ID").map_err(db_error)?,
+            KIND: row.get("KIND").map_err(db_error)?,
+            MESSAGE: row.get("MESSAGE").map_err(db_error)?,
+            CREATED_AT: row.get("CREATED_AT").unwrap_or(None),
+            READ_AT: row.get("READ_AT").unwrap_or(None),
+        });
+    }
+    Ok(notifications)
+}
+
+/// Marking a read notification again keeps its first `READ_AT`. `NoData` for unknown
+/// ids and other users' notifications.
+pub async fn mark_notification_read(
+    notification_id: i64,
+    pool: &Pool,
+    sql_manager: &SQLManager,
+    key: &ApiKey<'_>,
+) -> Result<(), APIErrors> {
+    let username = get_username(key)?;
+    let conn = pool.get().map_err(db_error)?;
+    in_transaction(&conn, || {
+        let stmt = conn
+            .execute(
+                sql_manager.get_sql("mark_notification_read")?.as_str(),
+                &[&notification_id, &username],
+            )
+            .map_err(db_error)?;
+        match stmt.row_count().map_err(db_error)? {
+            0 => Err(APIErrors::NoData),
+            _ => Ok(()),
+        }
+    })
+}
+
+/// The alert a change raises for a watcher, if any. Stock alerts fire when the
+/// quantity drops from above zero to zero or below.
+fn alert_for(change: &Change, watcher: &Watcher) -> Option<AlertKind> {
+    if change.FIELD == QTY_FIELD {
+        let was_in_stock = change.OLD_VALUE.map(|qty| qty > Decimal::ZERO).unwrap_or(false);
+        let now_out = change.NEW_VALUE.map(|qty| qty <= Decimal::ZERO).unwrap_or(true);
+        (watcher.on_stockout && was_in_stock && now_out).then_some(AlertKind::Stockout)
+    } else if PRICE_FIELDS.contains(&change.FIELD.as_str()) {
+        watcher.on_price.then_some(AlertKind::PriceChange)
+    } else {
+        None
+    }
+}
+
+/// Alerts per user, limited to each user's accessible stores. Overlapping
+/// entries (one store and all stores) raise a single alert.
+fn match_alerts(
+    changes: &[Change],
+    watchers: &[Watcher],
+    stores_by_user: &HashMap<String, HashSet<String>>,
+) -> Vec<(Recipient, Vec<Alert>)> {
+    let mut by_item: HashMap<&str, Vec<&Watcher>> = HashMap::new();
+    for watcher in watchers {
+        by_item.entry(watcher.item_id.as_str()).or_default().push(watcher);
+    }
+
+    let mut by_user: BTreeMap<&str, (Recipient, Vec<Alert>)> = BTreeMap::new();
+    for change in changes {
+        for watcher in by_item.get(change.ITEM_ID.as_str()).into_iter().flatten() {
+            if watcher.store_id.as_ref().map(|store_id| store_id != &change.STORE_ID).unwrap_or(false) {
+                continue;
+            }
+            let has_access = stores_by_user
+                .get(&watcher.username)
+                .map(|stores| stores.contains(&change.STORE_ID))
+                .unwrap_or(false);
+            if !has_access {
+                continue;
+            }
+            let kind = match alert_for(change, watcher) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let alert = Alert {
+                KIND: kind,
+                ITEM_ID: change.ITEM_ID.clone(),
+                STORE_ID: change.STORE_ID.clone(),
+                FIELD: change.FIELD.clone(),
+                OLD_VALUE: change.OLD_VALUE,
+                NEW_VALUE: change.NEW_VALUE,
+                CHANGED_AT: change.CHANGED_AT.clone(),
+            };
+            let (_, alerts) = by_user.entry(watcher.username.as_str()).or_insert_with(|| {
+                (
+                    Recipient {
+                        USERNAME: watcher.username.clone(),
+                        EMAIL: watcher.email.clone(),
+                    },
+                    Vec::new(),
+                )
+            });
+            if !alerts.contains(&alert) {
+                alerts.push(alert);
+            }
+        }
+    }
+    by_user.into_values().collect()
+}
+
+fn read_watchers(conn: &Connection, sql_manager: &SQLManager) -> Result<Vec<Watcher>, APIErrors> {
+    let rows = conn
+        .query(sql_manager.get_sql("get_all_watchers")?.as_str(), &[])
+        .map_err(db_error)?;
+    let mut watchers: Vec<Watcher> = Vec::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        watchers.push(Watcher {
+            username: row.get("USERNAME").map_err(db_error)?,
+            email: row.get("EMAIL").unwrap_or(None),
+            item_id: row.get("ITEM_ID").map_err(db_error)?,
+            store_id: row.get("STORE_ID").map_err(db_error)?,
+            on_stockout: row.get::<_, i64>("ON_STOCKOUT").map_err(db_error)? != 0,
+            on_price: row.get::<_, i64>("ON_PRICE").map_err(db_error)? != 0,
+        });
+    }
+    Ok(watchers)
+}
+
+fn read_user_stores(conn: &Connection, username: &str, sql_manager: &SQLManager) -> Result<HashSet<String>, APIErrors> {
+    let mut stmt = conn
+        .statement(sql_manager.get_sql("get_user_stores")?.as_str())
+        .build()
+        .map_err(db_error)?;
+    let rows = stmt.query_named(&[("USER_ID", &username)]).map_err(db_error)?;
+    let mut store_ids: HashSet<String> = HashSet::new();
+    for row_result in rows {
+        let row = row_result.map_err(db_error)?;
+        store_ids.insert(row.get("STORE_ID").map_err(db_error)?);
+    }
+    Ok(store_ids)
+}
+
+/// Send the alerts raised by `changes` through every notifier. A failing
+/// notifier is logged and doesn't stop the others.
+pub fn notify_watchers(
+    changes: &[Change],
+    notifiers: &[Box<dyn Notifier>],
+    pool: &Pool,
+    sql_manager: &SQLManager,
+) -> Result<(), APIErrors> {
+    if changes.is_empty() || notifiers.is_empty() {
+        return Ok(());
+    }
+    let conn = pool.get().map_err(db_error)?;
+    let changed_items: HashSet<&str> = changes.iter().map(|change| change.ITEM_ID.as_str()).collect();
+    let watchers: Vec<Watcher> = read_watchers(&conn, sql_manager)?
+        .into_iter()
+        .filter(|watcher| changed_items.contains(watcher.item_id.as_str()))
+        .collect();
+
+    let mut stores_by_user: HashMap<String, HashSet<String>> = HashMap::new();
+    for watcher in watchers.iter() {
+        if !stores_by_user.contains_key(&watcher.username) {
+            let store_ids = read_user_stores(&conn, &watcher.username, sql_manager)?;
+            stores_by_user.insert(watcher.username.clone(), store_ids);
+        }
+    }
+
+    for (recipient, alerts) in match_alerts(changes, &watchers, &stores_by_user) {
+        for notifier in notifiers {
+            if let Err(e) = notifier.notify(&recipient, &alerts) {
+                error!("Notifier {} failed for {}: {}", notifier.name(), recipient.USERNAME, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn change(item_id: &str, store_id: &str, field: &str, old: &str, new: &str) -> Change {
+        Change {
+            ITEM_ID: item_id.to_string(),
+            STORE_ID: store_id.to_string(),
+            FIELD: field.to_string(),
+            OLD_VALUE: Some(Decimal::from_str(old).unwrap()),
+            NEW_VALUE: Some(Decimal::from_str(new).unwrap()),
+            CHANGED_AT: None,
+        }
+    }
+
+    fn watcher(username: &str, item_id: &str, store_id: Option<&str>, on_stockout: bool, on_price: bool) -> Watcher {
+        Watcher {
+            username: username.to_string(),
+            email: None,
+            item_id: item_id.to_string(),
+            store_id: store_id.map(|s| s.to_string()),
+            on_stockout,
+            on_price,
+        }
+    }
+
+    #[test]
+    fn test_match_alerts() {
+        let changes = vec![
+            change("1", "05", "QTY", "3", "0"),
+            change("1", "01", "QTY", "3", "2"),
+            change("1", "01", "SALE_PRICE_NOTAX", "1.5", "1.75"),
+            change("2", "05", "QTY", "0", "-1"),
+        ];
+        let watchers = vec![
+            watcher("ana", "1", None, true, true),
+            watcher("ana", "1", Some("05"), true, false),
+            watcher("ben", "1", Some("01"), false, true),
+            watcher("ben", "2", None, true, true),
+            watcher("carl", "1", None, true, true),
+        ];
+        let stores_by_user = HashMap::from([
+            ("ana".to_string(), HashSet::from(["05".to_string()])),
+            ("ben".to_string(), HashSet::from(["01".to_string(), "05".to_string()])),
+        ]);
+
+        let result = match_alerts(&changes, &watchers, &stores_by_user);
+        assert_eq!(result.len(), 2);
+
+        let (ana, ana_alerts) = &result[0];
+        assert_eq!(ana.USERNAME, "ana");
+        assert_eq!(ana_alerts.len(), 1);
+        assert_eq!(ana_alerts[0].KIND, AlertKind::Stockout);
+        assert_eq!(ana_alerts[0].STORE_ID, "05");
+
+        let (ben, ben_alerts) = &result[1];
+        assert_eq!(ben.USERNAME, "ben");
+        assert_eq!(ben_alerts.len(), 1);
+        assert_eq!(ben_alerts[0].KIND, AlertKind::PriceChange);
+        assert_eq!(ben_alerts[0].message(), "Item 1 in store 01: SALE_PRICE_NOTAX changed from 1.5 to 1.75");
+    }
+}
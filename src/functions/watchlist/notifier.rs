@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use oracle::pool::Pool;
+
+use crate::functions::watchlist::structs::{Alert, Recipient};
+use crate::utils::db::{db_error, in_transaction};
+use crate::utils::env::{env_number, env_value};
+use crate::utils::sql::SQLManager;
+use crate::utils::structs::APIErrors;
+
+/// Delivers the alerts of one job run to one user
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn notify(&self, recipient: &Recipient, alerts: &[Alert]) -> Result<(), APIErrors>;
+}
+
+/// Stores alerts in NOTIFICATIONS_JHC, read through `/api/me/notifications`
+pub struct InboxNotifier {
+    pool: Pool,
+    sql: String,
+}
+
+impl Notifier for InboxNotifier {
+    fn name(&self) -> &'static str {
+        "inbox"
+    }
+
+    fn notify(&self, recipient: &Recipient, alerts: &[Alert]) -> Result<(), APIErrors> {
+        let conn = self.pool.get().map_err(db_error)?;
+        in_transaction(&conn, || {
+            let mut stmt = conn.statement(&self.sql).build().map_err(db_error)?;
+            for alert in alerts {
+                stmt.execute(&[
+                    &recipient.USERNAME,
+                    &alert.ITEM_ID,
+                    &alert.STORE_ID,
+                    &alert.KIND.as_str(),
+                    &alert.message(),
+                ])
+                .map_err(db_error)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// POSTs `{"recipient": ..., "alerts": [...]}` as JSON to a fixed URL
+pub struct WebhookNotifier {
+    url: String,
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn notify(&self, recipient: &Recipient, alerts: &[Alert]) -> Result<(), APIErrors> {
+        let body = serde_json::json!({ "recipient": recipient, "alerts": alerts });
+        ureq::post(&self.url)
+            .timeout(Duration::from_secs(10))
+            .send_json(body)
+            .map_err(|e| {
+                error!("Webhook Error: {}", e);
+                APIErrors::InternalServerError
+            })?;
+        Ok(())
+    }
+}
+
+/// Sends one email per job run to the user's address, users without one are skipped
+pub struct SmtpNotifier {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    fn notify(&self, recipient: &Recipient, alerts: &[Alert]) -> Result<(), APIErrors> {
+        let to: Mailbox = match recipient.EMAIL.as_ref().and_then(|email| email.parse().ok()) {
+            Some(to) => to,
+            None => {
+                info!("No valid email for {}, skipping", recipient.USERNAME);
+                return Ok(());
+            }
+        };
+        let body: Vec<String> = alerts.iter().map(|alert| alert.message()).collect();
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(format!("{} watchlist alert(s)", alerts.len()))
+            .body(body.join("\n"))
+            .map_err(|e| {
+                error!("Email Error: {}", e);
+                APIErrors::InternalServerError
+            })?;
+        self.transport.send(&email).map_err(|e| {
+            error!("SMTP Error: {}", e);
+            APIErrors::InternalServerError
+        })?;
+        Ok(())
+    }
+}
+
+fn smtp_from_env() -> Option<SmtpNotifier> {
+    let host = env_value("SMTP_HOST")?;
+    let from: Mailbox = env_value("SMTP_FROM")?.parse().ok()?;
+    let port = env_number("SMTP_PORT", 587u16);
+    let mut builder = SmtpTransport::starttls_relay(&host).ok()?.port(port);
+    if let (Some(username), Some(password)) = (env_value("SMTP_USERNAME"), env_value("SMTP_PASSWORD")) {
+        builder = builder.credentials(Credentials::new(username, password));
+    }
+    Some(SmtpNotifier {
+        transport: builder.build(),
+        from,
+    })
+}
+
+/// Notifiers listed in `NOTIFIERS` (comma separated: inbox, webhook, smtp), inbox by default.
+/// Entries that are unknown or missing their settings are logged and left out.
+pub fn notifiers_from_env(pool: &Pool, sql_manager: &SQLManager) -> Vec<Box<dyn Notifier>> {
+    let names = std::env::var("NOTIFIERS").unwrap_or("inbox".to_string());
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    for name in names.split(',').map(|name| name.trim().to_lowercase()) {
+        let notifier: Option<Box<dyn Notifier>> = match name.as_str() {
+            "" => continue,
+            "inbox" => sql_manager.get_sql("insert_notification").ok().map(|sql| {
+                Box::new(InboxNotifier {
+                    pool: pool.clone(),
+                    sql,
+                }) as Box<dyn Notifier>
+            }),
+            "webhook" => env_value("NOTIFIER_WEBHOOK_URL").map(|url| Box::new(WebhookNotifier { url }) as Box<dyn Notifier>),
+            "smtp" => smtp_from_env().map(|smtp| Box::new(smtp) as Box<dyn Notifier>),
+            _ => None,
+        };
+        match notifier {
+            Some(notifier) => notifiers.push(notifier),
+            None => error!("Notifier {} is unknown or not configured", name),
+        }
+    }
+    notifiers
+}
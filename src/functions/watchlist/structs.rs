@@ -0,0 +1,101 @@
+#![allow(non_snake_case)]
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WatchlistEntry {
+    pub WATCH_ID: i64,
+    pub ITEM_ID: String,
+    pub STORE_ID: Option<String>,
+    pub ON_STOCKOUT: bool,
+    pub ON_PRICE: bool,
+    pub CREATED_AT: Option<String>,
+}
+
+/// Body of a create or update. A missing `p_store_id` watches every accessible store,
+/// both alert kinds are on unless turned off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchParams {
+    pub p_item_id: String,
+    pub p_store_id: Option<String>,
+    pub p_on_stockout: Option<bool>,
+    pub p_on_price: Option<bool>,
+}
+
+/// Watchlist row used by the change-detection job
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    pub username: String,
+    pub email: Option<String>,
+    pub item_id: String,
+    pub store_id: Option<String>,
+    pub on_stockout: bool,
+    pub on_price: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlertKind {
+    Stockout,
+    PriceChange,
+}
+
+impl AlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertKind::Stockout => "STOCKOUT",
+            AlertKind::PriceChange => "PRICE_CHANGE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Alert {
+    pub KIND: AlertKind,
+    pub ITEM_ID: String,
+    pub STORE_ID: String,
+    pub FIELD: String,
+    pub OLD_VALUE: Option<Decimal>,
+    pub NEW_VALUE: Option<Decimal>,
+    pub CHANGED_AT: Option<String>,
+}
+
+impl Alert {
+    pub fn message(&self) -> String {
+        let show = |value: Option<Decimal>| value.map(|v| v.normalize().to_string()).unwrap_or("-".to_string());
+        match self.KIND {
+            AlertKind::Stockout => format!(
+                "Item {} is out of stock in store {} (was {})",
+                self.ITEM_ID,
+                self.STORE_ID,
+                show(self.OLD_VALUE)
+            ),
+            AlertKind::PriceChange => format!(
+                "Item {} in store {}: {} changed from {} to {}",
+                self.ITEM_ID,
+                self.STORE_ID,
+                self.FIELD,
+                show(self.OLD_VALUE),
+                show(self.NEW_VALUE)
+            ),
+        }
+    }
+}
+
+/// User the alerts of one job run are sent to
+#[derive(Debug, Clone, Serialize)]
+pub struct Recipient {
+    pub USERNAME: String,
+    pub EMAIL: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub NOTIFICATION_ID: i64,
+    pub ITEM_ID: String,
+    pub STORE_ID: String,
+    pub KIND: String,
+    pub MESSAGE: String,
+    pub CREATED_AT: Option<String>,
+    pub READ_AT: Option<String>,
+}
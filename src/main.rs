@@ -9,14 +9,22 @@ mod utils;
 use dotenv::dotenv;
 
 use routes::authentication::*;
+use routes::counts::*;
 use routes::files::*;
 use routes::health_check;
+use routes::history::*;
+use routes::jobs::*;
+use routes::labels::*;
 use routes::logs::*;
 use routes::permissions::*;
 use routes::products::*;
+use routes::reference::*;
+use routes::reports::*;
 use routes::stores::*;
+use routes::transfers::*;
 use routes::users::*;
 use routes::versions::*;
+use routes::watchlist::*;
 
 use server::JHApiServer;
 
@@ -37,6 +45,38 @@ async fn main() {
 
     let routes = routes![
         get_products,
+        get_product_suggestions,
+        get_product_by_id,
+        check_barcode,
+        create_labels,
+        get_label_templates,
+        get_product_index_status,
+        refresh_product_index,
+        get_category_tree,
+        get_supplier_list,
+        get_country_list,
+        get_nature_list,
+        get_trade_list,
+        get_low_stock,
+        get_valuation,
+        create_transfer_route,
+        get_transfer_list,
+        get_transfer_by_id,
+        transfer_action_route,
+        create_count_route,
+        get_count_list,
+        get_count_by_id,
+        add_count_lines_route,
+        get_count_variance_route,
+        close_count_route,
+        get_price_history,
+        get_change_feed,
+        get_watchlist_route,
+        add_watch_route,
+        update_watch_route,
+        delete_watch_route,
+        get_notifications_route,
+        read_notification_route,
         get_store_list,
         update_store_list,
         sign,
@@ -49,6 +89,17 @@ async fn main() {
         delete_user_route,
         get_image,
         upload,
+        get_product_images,
+        get_product_image,
+        add_product_image,
+        reorder_product_images,
+        set_primary_product_image,
+        delete_product_image,
+        delete_primary_product_image,
+        get_image_revisions,
+        restore_image_revision,
+        import_product_images,
+        get_job_route,
         cors_preflight_handler,
         get_store_list_for_user,
         get_user_logs,
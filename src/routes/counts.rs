@@ -0,0 +1,166 @@
+use crate::server::JHApiServerState;
+use rocket::http::Status;
+use rocket::log::private::info;
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+
+use crate::functions::counts::structs::{
+    AddCountLinesParams, CountLinesResult, CountListParams, CountSession, CreateCountParams, VarianceRow,
+};
+use crate::functions::counts::{
+    add_count_lines, close_count_session, create_count_session, get_count_session, get_count_sessions,
+    get_count_variance,
+};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::server::responders::{Export, ExportFormat};
+use crate::utils::permissions::is_stock_perm;
+use crate::utils::structs::APIErrors;
+
+fn error_status(err: APIErrors) -> Status {
+    match err {
+        APIErrors::InvalidData => Status::BadRequest,
+        APIErrors::InvalidState => Status::Conflict,
+        APIErrors::NoStoreAccess => Status::Unauthorized,
+        APIErrors::InvalidToken => Status::Unauthorized,
+        APIErrors::NoData => Status::NotFound,
+        APIErrors::UserNotFound => Status::NotFound,
+        _ => Status::InternalServerError,
+    }
+}
+
+#[post("/counts", data = "<params>")]
+pub async fn create_count_route(
+    params: Json<CreateCountParams>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<CountSession>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Count Session Request: {:?}", params);
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match create_count_session(&params, pool, sql_manager, &key).await {
+        Ok(session) => Ok(Json(session)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/counts?<params..>")]
+pub async fn get_count_list(
+    params: CountListParams,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Vec<CountSession>>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match get_count_sessions(&params, pool, sql_manager, &key).await {
+        Ok(sessions) => Ok(Json(sessions)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/counts/<session_id>")]
+pub async fn get_count_by_id(
+    session_id: i64,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<CountSession>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match get_count_session(session_id, pool, sql_manager, &key).await {
+        Ok(session) => Ok(Json(session)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[post("/counts/<session_id>/lines", data = "<params>")]
+pub async fn add_count_lines_route(
+    session_id: i64,
+    params: Json<AddCountLinesParams>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<CountLinesResult>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Count Lines: session {}, {} lines", session_id, params.p_lines.len());
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match add_count_lines(session_id, &params, pool, sql_manager, &key).await {
+        Ok(result) => Ok(Json(result)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/counts/<session_id>/variance?<format>")]
+pub async fn get_count_variance_route(
+    session_id: i64,
+    format: Option<ExportFormat>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Export<Vec<VarianceRow>>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match get_count_variance(session_id, pool, sql_manager, &key).await {
+        Ok(rows) => Ok(Export {
+            data: rows,
+            format: format.unwrap_or(ExportFormat::Json),
+            filename: "count_variance",
+        }),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[post("/counts/<session_id>/close")]
+pub async fn close_count_route(
+    session_id: i64,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<CountSession>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Close Count Session: {}", session_id);
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match close_count_session(session_id, pool, sql_manager, &key).await {
+        Ok(session) => Ok(Json(session)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_count_list() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_count_list]).await;
+        let response = client
+            .get("/api/counts?status=open")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+    }
+}
@@ -3,25 +3,111 @@ use crate::utils::permissions::is_images_perm;
 use crate::utils::permissions::has_query_perm;
 use crate::server::request_guard::api_key::ApiKey;
 
+use crate::server::responders::CachedFile;
 use crate::server::JHApiServerState;
-use rocket::fs::NamedFile;
 use rocket::http::Status;
-use rocket::{get, State};
+use rocket::response::status::Accepted;
+use rocket::serde::json::{json, Json};
+use rocket::{delete, get, put, State};
 
+use crate::functions::authentication::get_username;
+use crate::functions::files::cache::CachedImage;
 use crate::functions::files::download_file;
-use crate::functions::files::upload_file;
+use crate::functions::files::images::{
+    check_item, delete_image, delete_primary_image, list_images, reorder_images, set_primary_image,
+};
+use crate::functions::files::revisions::{list_revisions, restore_revision};
+use crate::functions::files::import::IMPORT_JOB;
+use crate::functions::files::structs::{ImageOrder, ImageRevision, ProductImage};
+use crate::functions::files::tasks::{ADD_JOB, UPLOAD_JOB};
+use crate::functions::files::validation::UploadError;
+use crate::functions::files::variants::ImageVariant;
+use crate::functions::jobs::structs::Job;
 
 use crate::utils::structs::APIErrors;
 
 
 use std::path::*;
+use rocket::tokio::io::AsyncReadExt;
 
-#[get("/images/<file..>")]
+async fn can_view_images(key: &ApiKey<'_>, state: &JHApiServerState) -> bool {
+    has_query_perm(key, &state.pool, &state.sql_manager).await || has_admin_perm(key, &state.pool, &state.sql_manager).await
+}
+
+async fn can_edit_images(key: &ApiKey<'_>, state: &JHApiServerState) -> bool {
+    is_images_perm(key, &state.pool, &state.sql_manager).await || has_admin_perm(key, &state.pool, &state.sql_manager).await
+}
+
+fn error_status(err: APIErrors) -> Status {
+    match err {
+        APIErrors::InvalidData => Status::BadRequest,
+        APIErrors::InvalidState => Status::Conflict,
+        APIErrors::InvalidToken => Status::Unauthorized,
+        APIErrors::FileNotFound => Status::NotFound,
+        APIErrors::NoData => Status::NotFound,
+        APIErrors::QueueFull | APIErrors::StoreUnavailable => Status::ServiceUnavailable,
+        _ => Status::InternalServerError,
+    }
+}
+
+fn image_response(image: CachedImage, variant: ImageVariant, state: &JHApiServerState) -> CachedFile {
+    CachedFile {
+        data: image.data,
+        content_type: variant.format.content_type(),
+        etag: image.etag,
+        last_modified: image.last_modified,
+        max_age: state.image_cache.max_age,
+    }
+}
+
+/// Local file header, or the end of central directory of an empty archive
+fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06")
+}
+
+fn upload_error_status(err: UploadError) -> Status {
+    match err {
+        UploadError::Empty => Status::BadRequest,
+        UploadError::UnsupportedFormat => Status::UnsupportedMediaType,
+        UploadError::Corrupt | UploadError::TooSmall { .. } => Status::UnprocessableEntity,
+        UploadError::TooLarge { .. } => Status::PayloadTooLarge,
+    }
+}
+
+/// Read a multipart file into memory. The client's file name is never used,
+/// so it can't point anywhere on disk.
+async fn read_file(file: &TempFile<'_>) -> Result<Vec<u8>, Status> {
+    let mut data: Vec<u8> = Vec::with_capacity(file.len() as usize);
+    let mut reader = file.open().await.map_err(|e| {
+        error!("Error opening upload: {}", e);
+        Status::InternalServerError
+    })?;
+    reader.read_to_end(&mut data).await.map_err(|e| {
+        error!("Error reading upload: {}", e);
+        Status::InternalServerError
+    })?;
+    Ok(data)
+}
+
+/// Read a multipart image and check it against the upload limits
+async fn read_upload(file: &TempFile<'_>, state: &JHApiServerState) -> Result<Vec<u8>, Status> {
+    let data = read_file(file).await?;
+    if let Err(e) = state.upload_limits.check(&data) {
+        info!("Upload Refused: {}", e);
+        return Err(upload_error_status(e));
+    }
+    Ok(data)
+}
+
+// `w` must be one of the allowed widths (IMAGE_VARIANT_WIDTHS or 640), `fmt` is jpg, png or webp
+#[get("/images/<file..>?<w>&<fmt>")]
 pub async fn get_image(
     file: PathBuf,
+    w: Option<u32>,
+    fmt: Option<String>,
     _key: ApiKey<'_>,
     state: &State<JHApiServerState>,
-) -> Result<Option<NamedFile>, Status> {
+) -> Result<CachedFile, Status> {
     let pool = &state.pool;
     let sql_manager = &state.sql_manager;
     if !has_query_perm(&_key, pool, &sql_manager).await && !has_admin_perm(&_key, pool, &sql_manager).await {
@@ -29,24 +115,30 @@ pub async fn get_image(
     }
     info!("Image Request: {:?}", file);
 
-    let filename = file.to_str().unwrap().to_string();
+    let filename = file.to_str().ok_or(Status::BadRequest)?.to_string();
 
     if filename == "" {
         return Err(Status::NotFound);
     }
 
-    match download_file(&filename).await {
-        Ok(()) => info!("File Downloaded"),
+    let variant = match state.image_variants.variant(w, fmt.as_deref()) {
+        Ok(variant) => variant,
+        Err(_) => return Err(Status::BadRequest),
+    };
+
+    // Always the primary image
+    match download_file(&state.image_store, &state.image_cache, &filename, None, variant).await {
+        Ok(image) => Ok(image_response(image, variant, state)),
         Err(e) => {
             info!("File Not Found");
             match e {
-                APIErrors::SFTPError => return Err(Status::InternalServerError),
-                APIErrors::FileNotFound => return Err(Status::NotFound),
-                _ => return Err(Status::InternalServerError),
+                APIErrors::SFTPError => Err(Status::InternalServerError),
+                APIErrors::InvalidData => Err(Status::BadRequest),
+                APIErrors::FileNotFound => Err(Status::NotFound),
+                _ => Err(Status::InternalServerError),
             }
         }
-    };
-    Ok(NamedFile::open(Path::new("tmp/tmpdownload.jpg")).await.ok())
+    }
 }
 
 use rocket::form::Form;
@@ -57,14 +149,13 @@ pub struct ImageUpload<'f> {
     pub file: TempFile<'f>,
     pub item_code: String,
 }
-// TODO: Add Error Handling
 #[post("/upload", data = "<params>")]
 pub async fn upload(
-    mut params: Form<ImageUpload<'_>>,
+    params: Form<ImageUpload<'_>>,
     #[allow(non_snake_case)] // Keeps giving warnings about _key not being snake_case
     _key: ApiKey<'_>,
     state: &State<JHApiServerState>,
-) -> Result<String, Status> {
+) -> Result<Accepted<Json<Job>>, Status> {
     let pool = &state.pool;
     let sql_manager = &state.sql_manager;
     if !is_images_perm(&_key, pool, &sql_manager).await && !has_admin_perm(&_key, pool, &sql_manager).await {
@@ -73,28 +164,290 @@ pub async fn upload(
 
     info!("Image Upload Request: {:?}", params.item_code);
 
-    // Save file temporarily
+    let username = get_username(&_key).map_err(|_| Status::Unauthorized)?;
 
-    if params.file.name().is_none() || params.item_code == "" {
+    let item_code = params.item_code.trim();
+    if item_code.is_empty() {
         return Err(Status::BadRequest);
     }
-    let filename = "tmp/".to_string() + params.file.name().unwrap();
-    params.file.persist_to(&filename).await.unwrap();
+    let data = read_upload(&params.file, state).await?;
+    check_item(item_code, pool, sql_manager).await.map_err(error_status)?;
 
-    // Upload file to server
-    match upload_file(&params.item_code, &filename).await {
-        Ok(()) => info!("File Uploaded"),
+    // Replaces the primary image once a worker gets to it
+    let payload = json!({ "ITEM_ID": item_code });
+    match state.job_queue.enqueue(UPLOAD_JOB, payload, data, &username, pool, sql_manager).await {
+        Ok(job) => Ok(Accepted(Json(job))),
         Err(e) => {
-            info!("File Not Uploaded");
-            match e {
-                APIErrors::SFTPError => return Err(Status::InternalServerError),
-                APIErrors::FileNotFound => return Err(Status::NotFound),
-                _ => return Err(Status::InternalServerError),
-            }
+            info!("File Not Uploaded: {}", e);
+            Err(error_status(e))
         }
     }
+}
+
+#[get("/products/<item_id>/images")]
+pub async fn get_product_images(
+    item_id: String,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Json<Vec<ProductImage>>, Status> {
+    if !can_view_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    match list_images(&item_id, &state.pool, &state.sql_manager, &state.image_store).await {
+        Ok(images) => Ok(Json(images)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/products/<item_id>/images/<image_id>?<w>&<fmt>")]
+pub async fn get_product_image(
+    item_id: String,
+    image_id: i64,
+    w: Option<u32>,
+    fmt: Option<String>,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<CachedFile, Status> {
+    if !can_view_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let variant = state.image_variants.variant(w, fmt.as_deref()).map_err(error_status)?;
+    match download_file(&state.image_store, &state.image_cache, &item_id, Some(image_id), variant).await {
+        Ok(image) => Ok(image_response(image, variant, state)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[derive(FromForm)]
+pub struct ProductImageUpload<'f> {
+    pub file: TempFile<'f>,
+    /// 0 based, the end by default
+    pub position: Option<i64>,
+    pub primary: Option<bool>,
+}
+
+#[post("/products/<item_id>/images", data = "<params>")]
+pub async fn add_product_image(
+    item_id: String,
+    params: Form<ProductImageUpload<'_>>,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Accepted<Json<Job>>, Status> {
+    if !can_edit_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let username = get_username(&key).map_err(|_| Status::Unauthorized)?;
+    info!("Product Image Upload Request: {}", item_id);
+
+    let data = read_upload(&params.file, state).await?;
+    check_item(&item_id, &state.pool, &state.sql_manager).await.map_err(error_status)?;
+    let payload = json!({
+        "ITEM_ID": item_id,
+        "POSITION": params.position,
+        "PRIMARY": params.primary.unwrap_or(false),
+    });
+    match state
+        .job_queue
+        .enqueue(ADD_JOB, payload, data, &username, &state.pool, &state.sql_manager)
+        .await
+    {
+        Ok(job) => Ok(Accepted(Json(job))),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[put("/products/<item_id>/images/order", data = "<params>")]
+pub async fn reorder_product_images(
+    item_id: String,
+    params: Json<ImageOrder>,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Json<Vec<ProductImage>>, Status> {
+    if !can_edit_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    info!("Product Image Order Request: {} {:?}", item_id, params.p_image_ids);
+    match reorder_images(&item_id, &params.p_image_ids, &state.pool, &state.sql_manager, &state.image_store).await {
+        Ok(images) => Ok(Json(images)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[post("/products/<item_id>/images/<image_id>/primary")]
+pub async fn set_primary_product_image(
+    item_id: String,
+    image_id: i64,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Json<Vec<ProductImage>>, Status> {
+    if !can_edit_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    info!("Primary Product Image Request: {} {}", item_id, image_id);
+    match set_primary_image(
+        &item_id,
+        image_id,
+        &state.pool,
+        &state.sql_manager,
+        &state.image_store,
+        &state.image_cache,
+    )
+    .await
+    {
+        Ok(images) => Ok(Json(images)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[delete("/products/<item_id>/images/<image_id>")]
+pub async fn delete_product_image(
+    item_id: String,
+    image_id: i64,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Status, Status> {
+    if !can_edit_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let username = get_username(&key).map_err(|_| Status::Unauthorized)?;
+    info!("Delete Product Image Request: {} {}", item_id, image_id);
+    match delete_image(
+        &item_id,
+        image_id,
+        &username,
+        &state.pool,
+        &state.sql_manager,
+        &state.image_store,
+        &state.image_cache,
+    )
+    .await
+    {
+        Ok(()) => Ok(Status::NoContent),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+/// Removes the primary image, the counterpart of `/api/upload`
+#[delete("/images/<item_id>")]
+pub async fn delete_primary_product_image(
+    item_id: String,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Status, Status> {
+    if !can_edit_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let username = get_username(&key).map_err(|_| Status::Unauthorized)?;
+    info!("Delete Image Request: {}", item_id);
+    match delete_primary_image(
+        &item_id,
+        &username,
+        &state.pool,
+        &state.sql_manager,
+        &state.image_store,
+        &state.image_cache,
+    )
+    .await
+    {
+        Ok(()) => Ok(Status::NoContent),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/products/<item_id>/images/revisions")]
+pub async fn get_image_revisions(
+    item_id: String,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Json<Vec<ImageRevision>>, Status> {
+    if !can_view_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    match list_revisions(&item_id, &state.pool, &state.sql_manager).await {
+        Ok(revisions) => Ok(Json(revisions)),
+        Err(err) => Err(error_status(err)),
+    }
+}
 
-    // Delete temporary file
-    std::fs::remove_file(filename).unwrap();
-    Ok("File Uploaded".to_string())
+// 409 once the revision's file is past the retention period
+#[post("/products/<item_id>/images/revisions/<revision_id>/restore")]
+pub async fn restore_image_revision(
+    item_id: String,
+    revision_id: i64,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Json<ProductImage>, Status> {
+    if !can_edit_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let username = get_username(&key).map_err(|_| Status::Unauthorized)?;
+    info!("Restore Image Revision Request: {} {}", item_id, revision_id);
+    match restore_revision(
+        &item_id,
+        revision_id,
+        &username,
+        &state.pool,
+        &state.sql_manager,
+        &state.image_store,
+        &state.image_cache,
+    )
+    .await
+    {
+        Ok(image) => Ok(Json(image)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[derive(FromForm)]
+pub struct ImageImport<'f> {
+    /// ZIP of images named by item code or barcode
+    pub file: TempFile<'f>,
+}
+
+/// Each entry replaces the primary image of its item. The per-file report is the
+/// result of the returned job, see `/api/jobs/<id>`.
+#[post("/images/import", data = "<params>")]
+pub async fn import_product_images(
+    params: Form<ImageImport<'_>>,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Accepted<Json<Job>>, Status> {
+    if !can_edit_images(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let username = get_username(&key).map_err(|_| Status::Unauthorized)?;
+    let archive = read_file(&params.file).await?;
+    info!("Image Import Request: {} bytes", archive.len());
+    if !is_zip(&archive) {
+        return Err(Status::UnsupportedMediaType);
+    }
+
+    match state
+        .job_queue
+        .enqueue(IMPORT_JOB, json!({}), archive, &username, &state.pool, &state.sql_manager)
+        .await
+    {
+        Ok(job) => Ok(Accepted(Json(job))),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_product_images_invalid_item() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_product_images]).await;
+        let response = client
+            .get("/api/products/bad@code/images")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::BadRequest);
+    }
 }
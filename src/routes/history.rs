@@ -0,0 +1,75 @@
+use crate::server::JHApiServerState;
+use rocket::http::Status;
+use rocket::log::private::info;
+use rocket::serde::json::Json;
+use rocket::{get, State};
+
+use crate::functions::history::get_changes;
+use crate::functions::history::structs::{Change, ChangeFeedParams};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::structs::APIErrors;
+
+fn error_status(err: APIErrors) -> Status {
+    match err {
+        APIErrors::InvalidData => Status::BadRequest,
+        APIErrors::InvalidToken => Status::Unauthorized,
+        APIErrors::UserNotFound => Status::NotFound,
+        _ => Status::InternalServerError,
+    }
+}
+
+#[get("/products/<item_id>/price-history?<store>&<from>&<to>")]
+pub async fn get_price_history(
+    item_id: String,
+    store: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Vec<Change>>, Status> {
+    let params = ChangeFeedParams {
+        store,
+        item: Some(item_id),
+        kind: Some("price".to_string()),
+        from,
+        to,
+    };
+    info!("Price History Request: {:?}", params);
+    match get_changes(&params, &state.pool, &state.sql_manager, &key).await {
+        Ok(changes) => Ok(Json(changes)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/changes?<params..>")]
+pub async fn get_change_feed(
+    params: ChangeFeedParams,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Vec<Change>>, Status> {
+    info!("Change Feed Request: {:?}", params);
+    match get_changes(&params, &state.pool, &state.sql_manager, &key).await {
+        Ok(changes) => Ok(Json(changes)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_change_feed_invalid_kind() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_change_feed]).await;
+        let response = client
+            .get("/api/changes?store=05&kind=other")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::BadRequest);
+    }
+}
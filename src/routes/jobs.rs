@@ -0,0 +1,34 @@
+use crate::server::JHApiServerState;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{get, State};
+
+use crate::functions::authentication::get_username;
+use crate::functions::jobs::get_job;
+use crate::functions::jobs::structs::Job;
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::permissions::has_admin_perm;
+use crate::utils::structs::APIErrors;
+
+fn error_status(err: APIErrors) -> Status {
+    match err {
+        APIErrors::InvalidToken => Status::Unauthorized,
+        APIErrors::NoData => Status::NotFound,
+        _ => Status::InternalServerError,
+    }
+}
+
+/// Status of a job and, once it has finished, its result
+#[get("/jobs/<job_id>")]
+pub async fn get_job_route(
+    job_id: i64,
+    key: ApiKey<'_>,
+    state: &State<JHApiServerState>,
+) -> Result<Json<Job>, Status> {
+    let username = get_username(&key).map_err(|_| Status::Unauthorized)?;
+    let admin = has_admin_perm(&key, &state.pool, &state.sql_manager).await;
+    match get_job(job_id, &username, admin, &state.pool, &state.sql_manager).await {
+        Ok(job) => Ok(Json(job)),
+        Err(err) => Err(error_status(err)),
+    }
+}
@@ -0,0 +1,71 @@
+use crate::server::JHApiServerState;
+use rocket::http::{ContentType, Status};
+use rocket::log::private::info;
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+
+use crate::functions::labels::render_labels;
+use crate::functions::labels::structs::{LabelFormat, LabelParams, LabelTemplate};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::structs::APIErrors;
+
+#[post("/labels", data = "<params>")]
+pub async fn create_labels(
+    params: Json<LabelParams>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    info!("Labels Request: {:?}", params.0);
+    match render_labels(
+        &params,
+        &state.label_templates,
+        &state.pool,
+        &state.sql_manager,
+        &state.pricing,
+        &key,
+    )
+    .await
+    {
+        Ok((LabelFormat::Png, body)) => Ok((ContentType::PNG, body)),
+        Ok((LabelFormat::Pdf, body)) => Ok((ContentType::PDF, body)),
+        Err(err) => {
+            match err {
+                APIErrors::InvalidData => Err(Status::BadRequest),
+                APIErrors::NoStoreAccess => Err(Status::Unauthorized),
+                APIErrors::InvalidToken => Err(Status::Unauthorized),
+                APIErrors::UserNotFound => Err(Status::NotFound),
+                _ => Err(Status::InternalServerError),
+            }
+        }
+    }
+}
+
+#[get("/labels/templates")]
+pub async fn get_label_templates(state: &State<JHApiServerState>, _key: ApiKey<'_>) -> Json<Vec<LabelTemplate>> {
+    Json(state.label_templates.list().to_vec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_create_labels_invalid_format() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![create_labels]).await;
+        let response = client
+            .post("/api/labels")
+            .header(rocket::http::Header::new("Authorization", token))
+            .json(&serde_json::json!({
+                "p_store_id": "01",
+                "p_item_ids": ["1"],
+                "p_format": "gif"
+            }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::BadRequest);
+    }
+}
@@ -1,11 +1,19 @@
 pub mod authentication;
+pub mod counts;
 pub mod files;
+pub mod labels;
+pub mod history;
+pub mod jobs;
 pub mod logs;
 pub mod permissions;
 pub mod products;
+pub mod reference;
+pub mod reports;
 pub mod stores;
+pub mod transfers;
 pub mod users;
 pub mod versions;
+pub mod watchlist;
 
 #[get("/health_check")]
 pub async fn health_check() -> &'static str {
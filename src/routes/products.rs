@@ -1,32 +1,148 @@
 #![allow(non_snake_case)]
 use crate::server::JHApiServerState;
 
+use rocket::http::Status;
 use rocket::log::private::info;
 use rocket::serde::json::Json;
 use rocket::{post, State};
 
+use crate::functions::products::index::IndexStatus;
+use crate::utils::permissions::has_admin_perm;
+
+use crate::functions::products::barcode;
 use crate::functions::products::get_product;
+use crate::functions::products::get_product_detail;
+use crate::functions::products::suggest_products;
 use crate::server::request_guard::api_key::ApiKey;
+use crate::server::request_guard::api_version::ApiVersion;
 
+use crate::functions::products::structs::BarcodeCheck;
 use crate::functions::products::structs::FetchParams;
-use crate::functions::products::structs::Product;
+use crate::functions::products::structs::ProductDetail;
+use crate::functions::products::structs::ProductList;
+use crate::functions::products::structs::ProductSuggestion;
+use crate::utils::structs::APIErrors;
 
 #[post("/products", data = "<params>")]
 pub async fn get_products(
     params: Json<FetchParams>,
     state: &State<JHApiServerState>,
     key: ApiKey<'_>,
-) -> Json<Vec<Product>> {
+    version: ApiVersion,
+) -> Json<ProductList> {
     let pool = &state.pool;
     let sql_manager = &state.sql_manager;
     info!("GetProductData Request: {:?}", params);
-    match get_product(params, &pool, &sql_manager, &key).await {
+    match get_product(params, &pool, &sql_manager, &state.pricing, &state.product_index, &key).await {
         Ok(products) => {
-            Json(products)
+            Json(ProductList::new(products, version))
         }
         Err(_err) => {
             error!("Error");
-            Json(vec![])
+            Json(ProductList::new(vec![], version))
+        }
+    }
+}
+
+// Type-ahead for the POS, at most `limit` entries (default 10, max 50)
+#[get("/products/suggest?<q>&<limit>")]
+pub async fn get_product_suggestions(
+    q: String,
+    limit: Option<usize>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Vec<ProductSuggestion>>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    let limit = limit.unwrap_or(10).clamp(1, 50);
+    match suggest_products(&q, limit, pool, sql_manager, &state.product_index, &key).await {
+        Ok(suggestions) => Ok(Json(suggestions)),
+        Err(err) => {
+            match err {
+                APIErrors::InvalidToken => Err(Status::Unauthorized),
+                APIErrors::UserNotFound => Err(Status::NotFound),
+                _ => Err(Status::InternalServerError),
+            }
+        }
+    }
+}
+
+#[get("/products/<item_id>?<with_tax>")]
+pub async fn get_product_by_id(
+    item_id: String,
+    with_tax: Option<bool>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<ProductDetail>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Product Detail Request: {:?}", item_id);
+    match get_product_detail(
+        &item_id,
+        with_tax.unwrap_or(false),
+        pool,
+        sql_manager,
+        &state.pricing,
+        &state.image_store,
+        &key,
+    )
+    .await {
+        Ok(product) => Ok(Json(product)),
+        Err(err) => {
+            match err {
+                APIErrors::InvalidToken => Err(Status::Unauthorized),
+                APIErrors::UserNotFound => Err(Status::NotFound),
+                APIErrors::NoData => Err(Status::NotFound),
+                _ => Err(Status::InternalServerError),
+            }
+        }
+    }
+}
+
+// Check digit validation of a scanned code and the forms a product search tries
+#[get("/barcodes/<code>")]
+pub async fn check_barcode(code: String, _key: ApiKey<'_>) -> Result<Json<BarcodeCheck>, Status> {
+    let check = barcode::check(&code);
+    if check.CODE.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    Ok(Json(check))
+}
+
+#[get("/products/index")]
+pub async fn get_product_index_status(
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<IndexStatus>, Status> {
+    if !has_admin_perm(&key, &state.pool, &state.sql_manager).await {
+        return Err(Status::Unauthorized);
+    }
+    Ok(Json(state.product_index.status()))
+}
+
+// Rebuild the in-memory product index now instead of waiting for the schedule
+#[post("/products/index")]
+pub async fn refresh_product_index(
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<IndexStatus>, Status> {
+    if !has_admin_perm(&key, &state.pool, &state.sql_manager).await {
+        return Err(Status::Unauthorized);
+    }
+    if !state.product_index.enabled {
+        return Err(Status::NotFound);
+    }
+    let sql = state.sql_manager.get_sql("get_product_index").map_err(|_| Status::InternalServerError)?;
+    let pool = state.pool.clone();
+    let index = state.product_index.clone();
+    match tokio::task::spawn_blocking(move || index.rebuild(&pool, &sql)).await {
+        Ok(Ok(count)) => {
+            info!("Product index refreshed: {} items", count);
+            Ok(Json(state.product_index.status()))
+        }
+        _ => {
+            error!("Error refreshing product index");
+            Err(Status::InternalServerError)
         }
     }
 }
@@ -98,3 +214,31 @@ pub async fn get_products_pi(
     }
 }
 */
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_product_suggestions() {
+        dotenv().ok();
+        let token = get_valid_user_token().await;
+        let client = get_client(routes![get_product_suggestions]).await;
+        let response = client
+            .get("/api/products/suggest?q=1&limit=5")
+            .header(rocket::http::Header::new(
+                "Authorization",
+                format!("{}", token.unwrap()),
+            ))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+        let res = response
+            .into_json::<Vec<ProductSuggestion>>()
+            .await
+            .unwrap();
+        assert!(res.len() <= 5);
+    }
+}
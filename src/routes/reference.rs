@@ -0,0 +1,140 @@
+use crate::server::JHApiServerState;
+use rocket::http::Status;
+use rocket::log::private::info;
+use rocket::{get, State};
+
+use crate::functions::reference::structs::{Category, ReferenceItem};
+use crate::functions::reference::{get_categories, get_reference_items, ReferenceKind};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::server::responders::Cached;
+use crate::utils::permissions::{has_admin_perm, has_query_perm};
+
+async fn can_read_reference(key: &ApiKey<'_>, state: &JHApiServerState) -> bool {
+    has_query_perm(key, &state.pool, &state.sql_manager).await || has_admin_perm(key, &state.pool, &state.sql_manager).await
+}
+
+async fn reference_items(
+    kind: ReferenceKind,
+    q: Option<String>,
+    state: &State<JHApiServerState>,
+    key: &ApiKey<'_>,
+) -> Result<Cached<Vec<ReferenceItem>>, Status> {
+    if !can_read_reference(key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    let cache = &state.reference_cache;
+    info!("Reference Request: {:?} {:?}", kind, q);
+    match get_reference_items(kind, q.as_deref(), pool, sql_manager, cache).await {
+        Ok(items) => Ok(Cached::new(items, cache.max_age)),
+        Err(err) => {
+            error!("Error fetching reference data: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[get("/reference/categories?<q>")]
+pub async fn get_category_tree(
+    q: Option<String>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Cached<Vec<Category>>, Status> {
+    if !can_read_reference(&key, state).await {
+        return Err(Status::Unauthorized);
+    }
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    let cache = &state.reference_cache;
+    info!("Categories Request: {:?}", q);
+    match get_categories(q.as_deref(), pool, sql_manager, cache).await {
+        Ok(categories) => Ok(Cached::new(categories, cache.max_age)),
+        Err(err) => {
+            error!("Error fetching categories: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[get("/reference/suppliers?<q>")]
+pub async fn get_supplier_list(
+    q: Option<String>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Cached<Vec<ReferenceItem>>, Status> {
+    reference_items(ReferenceKind::Suppliers, q, state, &key).await
+}
+
+#[get("/reference/countries?<q>")]
+pub async fn get_country_list(
+    q: Option<String>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Cached<Vec<ReferenceItem>>, Status> {
+    reference_items(ReferenceKind::Countries, q, state, &key).await
+}
+
+#[get("/reference/natures?<q>")]
+pub async fn get_nature_list(
+    q: Option<String>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Cached<Vec<ReferenceItem>>, Status> {
+    reference_items(ReferenceKind::Natures, q, state, &key).await
+}
+
+#[get("/reference/trades?<q>")]
+pub async fn get_trade_list(
+    q: Option<String>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Cached<Vec<ReferenceItem>>, Status> {
+    reference_items(ReferenceKind::Trades, q, state, &key).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_category_tree() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_category_tree]).await;
+        let response = client
+            .get("/api/reference/categories")
+            .header(rocket::http::Header::new("Authorization", token.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+        assert!(response.headers().get_one("Cache-Control").is_some());
+
+        // Same data, the client's copy is still valid
+        let response = client
+            .get("/api/reference/categories")
+            .header(rocket::http::Header::new("Authorization", token))
+            .header(rocket::http::Header::new("If-None-Match", etag))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::NotModified);
+    }
+
+    #[tokio::test]
+    pub async fn test_get_supplier_list() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_supplier_list]).await;
+        let response = client
+            .get("/api/reference/suppliers")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+        let suppliers = response.into_json::<Vec<ReferenceItem>>().await.unwrap();
+        assert!(!suppliers.is_empty());
+    }
+}
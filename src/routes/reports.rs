@@ -0,0 +1,112 @@
+use crate::server::JHApiServerState;
+use rocket::http::Status;
+use rocket::log::private::info;
+use rocket::{get, State};
+
+use crate::functions::reports::structs::{LowStockParams, LowStockRow, ValuationParams, ValuationReport};
+use crate::functions::reports::{get_low_stock_report, get_valuation_report};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::server::responders::{Export, ExportFormat};
+use crate::utils::permissions::{has_reports_perm, is_cost_perm};
+use crate::utils::structs::APIErrors;
+
+#[get("/reports/low-stock?<format>&<params..>")]
+pub async fn get_low_stock(
+    format: Option<ExportFormat>,
+    params: LowStockParams,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Export<Vec<LowStockRow>>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Low Stock Report Request: {:?}", params);
+    if !has_reports_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+
+    match get_low_stock_report(&params, pool, sql_manager, &key).await {
+        Ok(rows) => Ok(Export {
+            data: rows,
+            format: format.unwrap_or(ExportFormat::Json),
+            filename: "low_stock",
+        }),
+        Err(err) => {
+            match err {
+                APIErrors::InvalidData => Err(Status::BadRequest),
+                APIErrors::InvalidToken => Err(Status::Unauthorized),
+                APIErrors::UserNotFound => Err(Status::NotFound),
+                _ => Err(Status::InternalServerError),
+            }
+        }
+    }
+}
+
+#[get("/reports/valuation?<format>&<params..>")]
+pub async fn get_valuation(
+    format: Option<ExportFormat>,
+    params: ValuationParams,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Export<ValuationReport>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Valuation Report Request: {:?}", params);
+    if !has_reports_perm(&key, pool, sql_manager).await || !is_cost_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+
+    match get_valuation_report(&params, state.pricing.scale, pool, sql_manager, &key).await {
+        Ok(report) => Ok(Export {
+            data: report,
+            format: format.unwrap_or(ExportFormat::Json),
+            filename: "valuation",
+        }),
+        Err(err) => {
+            match err {
+                APIErrors::InvalidToken => Err(Status::Unauthorized),
+                APIErrors::UserNotFound => Err(Status::NotFound),
+                _ => Err(Status::InternalServerError),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_low_stock_csv() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_low_stock]).await;
+        let response = client
+            .get("/api/reports/low-stock?format=csv&threshold=5")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+        assert_eq!(response.content_type(), Some(rocket::http::ContentType::CSV));
+        let body = response.into_string().await.unwrap();
+        assert!(body.starts_with("STORE_ID,ITEM_ID"));
+    }
+
+    #[tokio::test]
+    pub async fn test_get_valuation() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_valuation]).await;
+        let response = client
+            .get("/api/reports/valuation?label=Month-end")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+        let report = response.into_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(report["LABEL"], "Month-end");
+    }
+}
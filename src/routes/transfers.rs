@@ -0,0 +1,136 @@
+use crate::server::JHApiServerState;
+use rocket::http::Status;
+use rocket::log::private::info;
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+
+use crate::functions::transfers::structs::{
+    CreateTransferParams, Transfer, TransferAction, TransferActionParams, TransferListParams,
+};
+use crate::functions::transfers::{apply_transfer_action, create_transfer, get_transfer, get_transfers};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::permissions::is_stock_perm;
+use crate::utils::structs::APIErrors;
+
+fn error_status(err: APIErrors) -> Status {
+    match err {
+        APIErrors::InvalidData => Status::BadRequest,
+        APIErrors::InvalidState => Status::Conflict,
+        APIErrors::NoStoreAccess => Status::Unauthorized,
+        APIErrors::InvalidToken => Status::Unauthorized,
+        APIErrors::NoData => Status::NotFound,
+        APIErrors::UserNotFound => Status::NotFound,
+        _ => Status::InternalServerError,
+    }
+}
+
+#[post("/transfers", data = "<params>")]
+pub async fn create_transfer_route(
+    params: Json<CreateTransferParams>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Transfer>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Transfer Request: {:?}", params);
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match create_transfer(&params, pool, sql_manager, &key).await {
+        Ok(transfer) => Ok(Json(transfer)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/transfers?<params..>")]
+pub async fn get_transfer_list(
+    params: TransferListParams,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Vec<Transfer>>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match get_transfers(&params, pool, sql_manager, &key).await {
+        Ok(transfers) => Ok(Json(transfers)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/transfers/<transfer_id>")]
+pub async fn get_transfer_by_id(
+    transfer_id: i64,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Transfer>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    match get_transfer(transfer_id, pool, sql_manager, &key).await {
+        Ok(transfer) => Ok(Json(transfer)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+/// `action` is one of approve, reject, ship, receive or cancel
+#[post("/transfers/<transfer_id>/<action>", data = "<params>")]
+pub async fn transfer_action_route(
+    transfer_id: i64,
+    action: TransferAction,
+    params: Option<Json<TransferActionParams>>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Transfer>, Status> {
+    let pool = &state.pool;
+    let sql_manager = &state.sql_manager;
+    info!("Transfer {} Action: {:?}", transfer_id, action);
+    if !is_stock_perm(&key, pool, sql_manager).await {
+        info!("User does not have permissions");
+        return Err(Status::Unauthorized);
+    }
+    let params = params.map(|p| p.into_inner()).unwrap_or_default();
+    match apply_transfer_action(transfer_id, action, &params, pool, sql_manager, &key).await {
+        Ok(transfer) => Ok(Json(transfer)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_transfer_list() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_transfer_list]).await;
+        let response = client
+            .get("/api/transfers?status=requested")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+    }
+
+    #[tokio::test]
+    pub async fn test_unknown_transfer_action() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![transfer_action_route]).await;
+        let response = client
+            .post("/api/transfers/1/teleport")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::NotFound);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::server::JHApiServerState;
+use rocket::http::Status;
+use rocket::log::private::info;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, put, State};
+
+use crate::functions::watchlist::structs::{Notification, WatchParams, WatchlistEntry};
+use crate::functions::watchlist::{
+    add_watch, delete_watch, get_notifications, get_watchlist, mark_notification_read, update_watch,
+};
+use crate::server::request_guard::api_key::ApiKey;
+use crate::utils::structs::APIErrors;
+
+fn error_status(err: APIErrors) -> Status {
+    match err {
+        APIErrors::InvalidData => Status::BadRequest,
+        APIErrors::InvalidState => Status::Conflict,
+        APIErrors::NoStoreAccess => Status::Unauthorized,
+        APIErrors::InvalidToken => Status::Unauthorized,
+        APIErrors::NoData => Status::NotFound,
+        APIErrors::UserNotFound => Status::NotFound,
+        _ => Status::InternalServerError,
+    }
+}
+
+#[get("/me/watchlist")]
+pub async fn get_watchlist_route(
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Vec<WatchlistEntry>>, Status> {
+    match get_watchlist(&state.pool, &state.sql_manager, &key).await {
+        Ok(entries) => Ok(Json(entries)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[post("/me/watchlist", data = "<params>")]
+pub async fn add_watch_route(
+    params: Json<WatchParams>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<WatchlistEntry>, Status> {
+    info!("Add Watch Request: {:?}", params.0);
+    match add_watch(&params, &state.pool, &state.sql_manager, &key).await {
+        Ok(entry) => Ok(Json(entry)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[put("/me/watchlist/<watch_id>", data = "<params>")]
+pub async fn update_watch_route(
+    watch_id: i64,
+    params: Json<WatchParams>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<WatchlistEntry>, Status> {
+    info!("Update Watch Request: {} {:?}", watch_id, params.0);
+    match update_watch(watch_id, &params, &state.pool, &state.sql_manager, &key).await {
+        Ok(entry) => Ok(Json(entry)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[delete("/me/watchlist/<watch_id>")]
+pub async fn delete_watch_route(
+    watch_id: i64,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Status, Status> {
+    info!("Delete Watch Request: {}", watch_id);
+    match delete_watch(watch_id, &state.pool, &state.sql_manager, &key).await {
+        Ok(()) => Ok(Status::NoContent),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[get("/me/notifications?<unread>")]
+pub async fn get_notifications_route(
+    unread: Option<bool>,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Json<Vec<Notification>>, Status> {
+    match get_notifications(unread.unwrap_or(false), &state.pool, &state.sql_manager, &key).await {
+        Ok(notifications) => Ok(Json(notifications)),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[post("/me/notifications/<notification_id>/read")]
+pub async fn read_notification_route(
+    notification_id: i64,
+    state: &State<JHApiServerState>,
+    key: ApiKey<'_>,
+) -> Result<Status, Status> {
+    match mark_notification_read(notification_id, &state.pool, &state.sql_manager, &key).await {
+        Ok(()) => Ok(Status::NoContent),
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::*;
+    use dotenv::dotenv;
+
+    #[tokio::test]
+    pub async fn test_get_watchlist() {
+        dotenv().ok();
+        let token = get_valid_user_token().await.unwrap();
+        let client = get_client(routes![get_watchlist_route]).await;
+        let response = client
+            .get("/api/me/watchlist")
+            .header(rocket::http::Header::new("Authorization", token))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+    }
+}
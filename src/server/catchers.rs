@@ -20,6 +20,16 @@ pub fn conflict(_req: &Request) -> String {
     format!("Data Conflict, please make sure you are not trying to insert duplicate data")
 }
 
+#[catch(413)]
+pub fn payload_too_large() -> &'static str {
+    "Payload Too Large, please send a smaller file or image"
+}
+
+#[catch(415)]
+pub fn unsupported_media_type() -> &'static str {
+    "Unsupported Media Type, images must be JPEG, PNG or WebP"
+}
+
 #[catch(422)]
 pub fn unprocessable_entity(_req: &Request) -> String {
     format!("The body data is invalid, please make sure you are following the correct structure")
@@ -29,3 +39,8 @@ pub fn unprocessable_entity(_req: &Request) -> String {
 pub fn internal_error() -> &'static str {
     "Whoops! Looks like we messed up."
 }
+
+#[catch(503)]
+pub fn service_unavailable() -> &'static str {
+    "Service Unavailable, too much work is queued, please try again later"
+}
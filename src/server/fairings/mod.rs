@@ -1,2 +1,3 @@
 pub mod log;
-pub mod cors;
\ No newline at end of file
+pub mod cors;
+pub mod scheduler;
\ No newline at end of file
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use rocket::{fairing::{Fairing, Info, Kind}, Orbit, Rocket};
+
+use crate::functions::files::revisions::purge_expired_revisions;
+use crate::functions::history::{snapshot, ChangeTracker};
+use crate::functions::jobs::queue::JobContext;
+use crate::functions::watchlist::notifier::notifiers_from_env;
+use crate::functions::watchlist::notify_watchers;
+use crate::server::JHApiServerState;
+use crate::utils::scheduler::spawn_periodic;
+
+/// Starts the periodic background jobs once the server is running
+pub struct Scheduler;
+
+#[rocket::async_trait]
+impl Fairing for Scheduler {
+    fn info(&self) -> Info {
+        Info {
+            name: "Background Job Scheduler",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let state = rocket.state::<JHApiServerState>().unwrap();
+
+        let tracker = ChangeTracker::from_env();
+        if tracker.enabled {
+            let pool = state.pool.clone();
+            let sql_manager = state.sql_manager.clone();
+            let notifiers = notifiers_from_env(&pool, &sql_manager);
+            let period = Duration::from_secs(tracker.refresh_minutes.max(1) * 60);
+            spawn_periodic("inventory_history", period, move || {
+                let changes = snapshot(&pool, &sql_manager)?;
+                notify_watchers(&changes, &notifiers, &pool, &sql_manager)
+            });
+        }
+
+        state.job_queue.start(JobContext {
+            pool: state.pool.clone(),
+            sql_manager: state.sql_manager.clone(),
+            image_store: state.image_store.clone(),
+            image_cache: state.image_cache.clone(),
+            upload_limits: state.upload_limits.clone(),
+        });
+
+        let pool = state.pool.clone();
+        let sql_manager = state.sql_manager.clone();
+        let store = state.image_store.clone();
+        spawn_periodic("image_revisions", Duration::from_secs(24 * 60 * 60), move || {
+            purge_expired_revisions(&pool, &sql_manager, store.as_ref())
+        });
+
+        if state.product_index.enabled {
+            let pool = state.pool.clone();
+            let index = state.product_index.clone();
+            let sql = match state.sql_manager.get_sql("get_product_index") {
+                Ok(sql) => sql,
+                Err(e) => {
+                    error!("Product index disabled, SQL not found: {}", e);
+                    return;
+                }
+            };
+            let period = Duration::from_secs(index.refresh_minutes.max(1) * 60);
+            spawn_periodic("product_index", period, move || {
+                index.rebuild(&pool, &sql).map(|_| ())
+            });
+        }
+    }
+}
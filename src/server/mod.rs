@@ -1,13 +1,26 @@
+use std::sync::Arc;
+
 use oracle::pool::{Pool, PoolBuilder};
 
 mod fairings;
 mod catchers;
 pub mod request_guard;
+pub mod responders;
 
 use fairings::log::Logger;
 use fairings::cors::CORS;
+use fairings::scheduler::Scheduler;
 use rocket::{Ignite, Rocket};
 
+use crate::functions::files::cache::ImageCache;
+use crate::functions::files::store::{image_store_from_env, ImageStore};
+use crate::functions::files::validation::UploadLimits;
+use crate::functions::files::variants::ImageVariants;
+use crate::functions::jobs::queue::JobQueue;
+use crate::functions::labels::LabelTemplates;
+use crate::functions::products::index::ProductIndex;
+use crate::functions::products::pricing::PricingConfig;
+use crate::functions::reference::ReferenceCache;
 use crate::utils::sql::SQLManager;
 use crate::utils::structs::APIErrors;
 
@@ -18,6 +31,15 @@ pub struct JHApiServer {
 pub struct JHApiServerState {
     pub pool: oracle::pool::Pool,
     pub sql_manager: SQLManager,
+    pub pricing: PricingConfig,
+    pub product_index: Arc<ProductIndex>,
+    pub reference_cache: ReferenceCache,
+    pub label_templates: LabelTemplates,
+    pub image_store: Arc<dyn ImageStore>,
+    pub image_cache: Arc<ImageCache>,
+    pub image_variants: ImageVariants,
+    pub upload_limits: UploadLimits,
+    pub job_queue: Arc<JobQueue>,
 }
 
 impl JHApiServer {
@@ -26,6 +48,7 @@ impl JHApiServer {
         let rocket = rocket::build()
         .attach(CORS)
         .attach(Logger)
+        .attach(Scheduler)
         .register(
             "/",
             Self::get_catchers()
@@ -63,8 +86,11 @@ impl JHApiServer {
             catchers::unauthorized,
             catchers::not_found,
             catchers::conflict,
+            catchers::payload_too_large,
+            catchers::unsupported_media_type,
             catchers::unprocessable_entity,
             catchers::internal_error,
+            catchers::service_unavailable,
         ];
         catchers
     }
@@ -77,9 +103,27 @@ impl JHApiServer {
     async fn get_state() -> JHApiServerState {
         let pool = JHApiServer::build_pool().expect("Failed to build db pool");
         let sql_manager = JHApiServer::get_sql_manager().await;
+        let pricing = PricingConfig::load();
+        let product_index = Arc::new(ProductIndex::from_env());
+        let reference_cache = ReferenceCache::from_env();
+        let label_templates = LabelTemplates::from_env();
+        let image_store = image_store_from_env();
+        let image_cache = Arc::new(ImageCache::from_env());
+        let image_variants = ImageVariants::from_env();
+        let upload_limits = UploadLimits::from_env();
+        let job_queue = Arc::new(JobQueue::from_env());
         JHApiServerState {
             pool,
             sql_manager,
+            pricing,
+            product_index,
+            reference_cache,
+            label_templates,
+            image_store,
+            image_cache,
+            image_variants,
+            upload_limits,
+            job_queue,
         }
     }
 
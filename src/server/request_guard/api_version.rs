@@ -0,0 +1,64 @@
+use rocket::{http::Status, request::{FromRequest, Outcome}, Request};
+
+/// Response format selected through the `X-API-Version` header.
+/// Version 1 (default) keeps the legacy all-strings product format, clients opt into typed fields
+/// with version 2.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub fn is_legacy(&self) -> bool {
+        *self == ApiVersion::V1
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiVersion {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("X-API-Version").map(|v| v.trim()) {
+            None | Some("") | Some("1") => Outcome::Success(ApiVersion::V1),
+            Some("2") => Outcome::Success(ApiVersion::V2),
+            Some(version) => {
+                error!("Unknown API version requested: {}", version);
+                Outcome::Error((
+                    Status::BadRequest,
+                    "Unsupported X-API-Version, expected 1 or 2".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    #[get("/version")]
+    fn version(version: ApiVersion) -> String {
+        format!("{:?}", version)
+    }
+
+    #[test]
+    fn test_api_version() {
+        let client = Client::tracked(rocket::build().mount("/", routes![version])).unwrap();
+        let requested = |header: Option<&str>| {
+            let mut request = client.get("/version");
+            if let Some(header) = header {
+                request = request.header(rocket::http::Header::new("X-API-Version", header.to_string()));
+            }
+            let response = request.dispatch();
+            (response.status(), response.into_string())
+        };
+        // Existing clients keep the legacy format until they ask for typed fields
+        assert_eq!(requested(None), (Status::Ok, Some("V1".to_string())));
+        assert_eq!(requested(Some("1")), (Status::Ok, Some("V1".to_string())));
+        assert_eq!(requested(Some(" 2 ")), (Status::Ok, Some("V2".to_string())));
+        assert_eq!(requested(Some("3")).0, Status::BadRequest);
+    }
+}
@@ -1 +1,2 @@
-pub mod api_key;
\ No newline at end of file
+pub mod api_key;
+pub mod api_version;
\ No newline at end of file
@@ -0,0 +1,189 @@
+use std::io::Cursor;
+
+use chrono::{DateTime, Utc};
+
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::Request;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::utils::export::CsvTable;
+
+/// JSON response sent with `ETag` and `Cache-Control` headers.
+/// Answers `304 Not Modified` with no body when the request's `If-None-Match` matches.
+pub struct Cached<T> {
+    pub value: T,
+    pub max_age: u64,
+}
+
+impl<T> Cached<T> {
+    pub fn new(value: T, max_age: u64) -> Cached<T> {
+        Cached { value, max_age }
+    }
+}
+
+/// Strong ETag over the serialized body
+pub fn etag_for(body: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(Sha256::digest(body)))
+}
+
+/// `If-None-Match` can hold `*` or a list of tags, weak tags compare equal to strong ones
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(|tag| tag.trim()).any(|tag| {
+        tag == "*" || tag.trim_start_matches("W/") == etag
+    })
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Cached<T> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::to_vec(&self.value).map_err(|e| {
+            error!("Error serializing response: {}", e);
+            Status::InternalServerError
+        })?;
+        let etag = etag_for(&body);
+        let cache_control = format!("private, max-age={}", self.max_age);
+
+        let not_modified = req
+            .headers()
+            .get_one("If-None-Match")
+            .map(|value| etag_matches(value, &etag))
+            .unwrap_or(false);
+        if not_modified {
+            return Response::build()
+                .status(Status::NotModified)
+                .raw_header("ETag", etag)
+                .raw_header("Cache-Control", cache_control)
+                .ok();
+        }
+
+        Response::build()
+            .header(ContentType::JSON)
+            .raw_header("ETag", etag)
+            .raw_header("Cache-Control", cache_control)
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+/// File sent with `ETag`, `Last-Modified` and `Cache-Control` headers.
+/// Answers `304 Not Modified` with no body when the request's `If-None-Match` matches.
+pub struct CachedFile {
+    pub data: Vec<u8>,
+    pub content_type: ContentType,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    pub max_age: u64,
+}
+
+impl<'r> Responder<'r, 'static> for CachedFile {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let last_modified = self.last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let cache_control = format!("private, max-age={}", self.max_age);
+
+        let not_modified = req
+            .headers()
+            .get_one("If-None-Match")
+            .map(|value| etag_matches(value, &self.etag))
+            .unwrap_or(false);
+        let mut response = Response::build();
+        response
+            .raw_header("ETag", self.etag)
+            .raw_header("Last-Modified", last_modified)
+            .raw_header("Cache-Control", cache_control);
+        if not_modified {
+            return response.status(Status::NotModified).ok();
+        }
+        response
+            .header(self.content_type)
+            .sized_body(self.data.len(), Cursor::new(self.data))
+            .ok()
+    }
+}
+
+/// Output format picked with the `format` query parameter
+#[derive(Debug, Clone, Copy, PartialEq, FromFormField)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// Report data sent as JSON, or as a CSV attachment named `<filename>.csv`
+pub struct Export<T> {
+    pub data: T,
+    pub format: ExportFormat,
+    pub filename: &'static str,
+}
+
+impl<'r, T: Serialize + CsvTable> Responder<'r, 'static> for Export<T> {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        match self.format {
+            ExportFormat::Json => {
+                let body = serde_json::to_vec(&self.data).map_err(|e| {
+                    error!("Error serializing response: {}", e);
+                    Status::InternalServerError
+                })?;
+                Response::build()
+                    .header(ContentType::JSON)
+                    .sized_body(body.len(), Cursor::new(body))
+                    .ok()
+            }
+            ExportFormat::Csv => {
+                let body = self.data.to_csv();
+                Response::build()
+                    .header(ContentType::CSV)
+                    .raw_header(
+                        "Content-Disposition",
+                        format!("attachment; filename=\"{}.csv\"", self.filename),
+                    )
+                    .sized_body(body.len(), Cursor::new(body))
+                    .ok()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = etag_for(b"[]");
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("\"other\", W/{}", etag), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"other\"", &etag));
+    }
+
+    #[get("/image")]
+    fn cached_image() -> CachedFile {
+        CachedFile {
+            data: b"image".to_vec(),
+            content_type: ContentType::JPEG,
+            etag: etag_for(b"image"),
+            last_modified: DateTime::from_timestamp(784111777, 0).unwrap(),
+            max_age: 60,
+        }
+    }
+
+    #[test]
+    fn test_cached_file() {
+        use rocket::local::blocking::Client;
+
+        let client = Client::tracked(rocket::build().mount("/", routes![cached_image])).unwrap();
+        let response = client.get("/image").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Last-Modified"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("private, max-age=60"));
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+        assert_eq!(response.into_bytes().unwrap(), b"image");
+
+        let response = client
+            .get("/image")
+            .header(rocket::http::Header::new("If-None-Match", etag))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+        assert!(response.into_bytes().is_none());
+    }
+}
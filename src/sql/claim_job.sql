@@ -0,0 +1 @@
+UPDATE ODBC_JHC.JOBS_JHC SET STATUS = 'RUNNING', ATTEMPTS = ATTEMPTS + 1, STARTED_AT = SYSDATE WHERE JOB_ID = :1 AND STATUS = 'QUEUED' AND RUN_AFTER <= SYSDATE
\ No newline at end of file
@@ -0,0 +1 @@
+UPDATE ODBC_JHC.COUNT_SESSIONS_JHC SET STATUS = 'CLOSED', CLOSED_BY = :1, CLOSED_AT = SYSDATE WHERE SESSION_ID = :2
\ No newline at end of file
@@ -0,0 +1 @@
+DELETE FROM ODBC_JHC.INV_STATE_JHC WHERE ITEM_ID = :item_id AND STORE_ID = :store_id
\ No newline at end of file
@@ -0,0 +1 @@
+DELETE FROM ODBC_JHC.PRODUCT_IMAGES_JHC WHERE IMAGE_ID = :1 AND ITEM_ID = :2
\ No newline at end of file
@@ -0,0 +1 @@
+DELETE FROM ODBC_JHC.WATCHLIST_JHC WHERE WATCH_ID = :1 AND USERNAME = :2
\ No newline at end of file
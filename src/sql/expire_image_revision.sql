@@ -0,0 +1 @@
+UPDATE ODBC_JHC.IMAGE_REVISIONS_JHC SET HAS_FILE = 0 WHERE REVISION_ID = :1
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT ITEM_ID, ITEM_CAT, ITEM_MAIN_BARCODE, BARCODE_LISTED FROM ODBC_JHC.JHC_INVDATA WHERE ITEM_ID = :code
\ No newline at end of file
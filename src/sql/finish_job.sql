@@ -0,0 +1 @@
+UPDATE ODBC_JHC.JOBS_JHC SET STATUS = :1, RESULT = :2, LAST_ERROR = :3, FINISHED_AT = SYSDATE WHERE JOB_ID = :4
\ No newline at end of file
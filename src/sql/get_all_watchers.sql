@@ -0,0 +1 @@
+SELECT W.USERNAME, W.ITEM_ID, W.STORE_ID, W.ON_STOCKOUT, W.ON_PRICE, U.EMAIL FROM ODBC_JHC.WATCHLIST_JHC W JOIN ODBC_JHC.AUTHENTICATION_JHC U ON U.USERNAME = W.USERNAME
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT ITEM_ID, ITEM_DESC, SYSTEM_QTY, COUNTED_QTY FROM ODBC_JHC.COUNT_RESULTS_JHC WHERE SESSION_ID = :1 ORDER BY ITEM_ID
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT SESSION_ID, STORE_ID, ITEM_CAT, STATUS, CREATED_BY, TO_CHAR(CREATED_AT, 'YYYY-MM-DD HH24:MI:SS') CREATED_AT, CLOSED_BY, TO_CHAR(CLOSED_AT, 'YYYY-MM-DD HH24:MI:SS') CLOSED_AT, NOTE FROM ODBC_JHC.COUNT_SESSIONS_JHC WHERE SESSION_ID = :1 FOR UPDATE
\ No newline at end of file
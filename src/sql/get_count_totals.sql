@@ -0,0 +1 @@
+SELECT ITEM_ID, SUM(QTY) QTY FROM ODBC_JHC.COUNT_LINES_JHC WHERE SESSION_ID = :1 GROUP BY ITEM_ID
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT JOB_ID FROM ODBC_JHC.JOBS_JHC WHERE STATUS = 'QUEUED' AND RUN_AFTER <= SYSDATE ORDER BY RUN_AFTER, JOB_ID FETCH FIRST :1 ROWS ONLY
\ No newline at end of file
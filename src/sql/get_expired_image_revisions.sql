@@ -0,0 +1 @@
+SELECT REVISION_ID, ITEM_ID, IMAGE_ID FROM ODBC_JHC.IMAGE_REVISIONS_JHC WHERE HAS_FILE = 1 AND CHANGED_AT < SYSDATE - :1
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT REVISION_ID, ITEM_ID, IMAGE_ID, ACTION, HAS_FILE, CHANGED_BY, TO_CHAR(CHANGED_AT, 'YYYY-MM-DD HH24:MI:SS') CHANGED_AT, CASE WHEN HAS_FILE = 1 THEN TO_CHAR(CHANGED_AT + :retention_days, 'YYYY-MM-DD HH24:MI:SS') END EXPIRES_AT FROM ODBC_JHC.IMAGE_REVISIONS_JHC WHERE ITEM_ID = :item_id ORDER BY REVISION_ID DESC
\ No newline at end of file
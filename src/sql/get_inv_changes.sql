@@ -0,0 +1 @@
+SELECT ITEM_ID, STORE_ID, FIELD, OLD_VALUE, NEW_VALUE, TO_CHAR(CHANGED_AT, 'YYYY-MM-DD HH24:MI:SS') CHANGED_AT FROM ODBC_JHC.INV_CHANGES_JHC WHERE CHANGED_AT >= TO_DATE(:date_from, 'YYYY-MM-DD') AND CHANGED_AT < TO_DATE(:date_to, 'YYYY-MM-DD') + 1
\ No newline at end of file
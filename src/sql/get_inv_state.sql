@@ -0,0 +1 @@
+SELECT ITEM_ID, STORE_ID, QTY, SALE_PRICE_NOTAX, FIRST_DISC_PER, SECOND_DISC_PER FROM ODBC_JHC.INV_STATE_JHC
\ No newline at end of file
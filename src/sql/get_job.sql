@@ -0,0 +1 @@
+SELECT JOB_ID, KIND, STATUS, CREATED_BY, TO_CHAR(CREATED_AT, 'YYYY-MM-DD HH24:MI:SS') CREATED_AT, TO_CHAR(FINISHED_AT, 'YYYY-MM-DD HH24:MI:SS') FINISHED_AT, ATTEMPTS, LAST_ERROR, PAYLOAD, RESULT FROM ODBC_JHC.JOBS_JHC WHERE JOB_ID = :1
\ No newline at end of file
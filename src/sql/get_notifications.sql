@@ -0,0 +1 @@
+SELECT NOTIFICATION_ID, ITEM_ID, STORE_ID, KIND, MESSAGE, TO_CHAR(CREATED_AT, 'YYYY-MM-DD HH24:MI:SS') CREATED_AT, TO_CHAR(READ_AT, 'YYYY-MM-DD HH24:MI:SS') READ_AT FROM ODBC_JHC.NOTIFICATIONS_JHC WHERE USERNAME = :username AND (:unread = 0 OR READ_AT IS NULL) ORDER BY CREATED_AT DESC, NOTIFICATION_ID DESC FETCH FIRST 500 ROWS ONLY
\ No newline at end of file
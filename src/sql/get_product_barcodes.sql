@@ -0,0 +1 @@
+SELECT ITEM_ID, BARCODE_LISTED FROM ODBC_JHC.JHC_INVDATA WHERE ITEM_ID = :1
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT IMAGE_ID, ITEM_ID, POSITION, IS_PRIMARY, UPLOADED_BY, TO_CHAR(UPLOADED_AT, 'YYYY-MM-DD HH24:MI:SS') UPLOADED_AT FROM ODBC_JHC.PRODUCT_IMAGES_JHC WHERE ITEM_ID = :1 ORDER BY POSITION, IMAGE_ID
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT ITEM_ID, ITEM_DESC, ITEM_DESC_S, FOREIGN_ITEM_CODE, ITEM_MAIN_BARCODE, BARCODE_LISTED, ITEM_CAT, ITEM_SUB_CAT FROM ODBC_JHC.JHC_INVDATA ORDER BY ITEM_ID
\ No newline at end of file
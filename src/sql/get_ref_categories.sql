@@ -0,0 +1 @@
+SELECT DISTINCT ITEM_CAT, ITEM_SUB_CAT FROM ODBC_JHC.JHC_INVDATA WHERE ITEM_CAT IS NOT NULL ORDER BY ITEM_CAT, ITEM_SUB_CAT
\ No newline at end of file
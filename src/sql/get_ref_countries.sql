@@ -0,0 +1 @@
+SELECT DISTINCT COUNTRY, COUNTRY_DESC FROM ODBC_JHC.JHC_INVDATA WHERE COUNTRY IS NOT NULL ORDER BY COUNTRY
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT DISTINCT NATURE_ID, NATURE_DESC FROM ODBC_JHC.JHC_INVDATA WHERE NATURE_ID IS NOT NULL ORDER BY NATURE_ID
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT DISTINCT SUPPLIER_ID, SUPPLIER_DESC FROM ODBC_JHC.JHC_INVDATA WHERE SUPPLIER_ID IS NOT NULL ORDER BY SUPPLIER_ID
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT DISTINCT TRADE_ID, TRADE_DESC FROM ODBC_JHC.JHC_INVDATA WHERE TRADE_ID IS NOT NULL ORDER BY TRADE_ID
\ No newline at end of file
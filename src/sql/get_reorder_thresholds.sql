@@ -0,0 +1 @@
+SELECT SCOPE_TYPE, SCOPE_VALUE, MIN_QTY FROM ODBC_JHC.REORDER_THRESHOLDS_JHC
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT * FROM ODBC_JHC.JHC_INVDATA WHERE 1 = 1
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT ACTION, USERNAME, FROM_STATUS, TO_STATUS, NOTE, TO_CHAR(CREATED_AT, 'YYYY-MM-DD HH24:MI:SS') CREATED_AT FROM ODBC_JHC.TRANSFER_AUDIT_JHC WHERE TRANSFER_ID = :1 ORDER BY AUDIT_ID
\ No newline at end of file
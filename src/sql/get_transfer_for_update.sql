@@ -0,0 +1 @@
+SELECT TRANSFER_ID, FROM_STORE, TO_STORE, STATUS, REQUESTED_BY, NOTE, TO_CHAR(CREATED_AT, 'YYYY-MM-DD HH24:MI:SS') CREATED_AT, TO_CHAR(UPDATED_AT, 'YYYY-MM-DD HH24:MI:SS') UPDATED_AT FROM ODBC_JHC.TRANSFERS_JHC WHERE TRANSFER_ID = :1 FOR UPDATE
\ No newline at end of file
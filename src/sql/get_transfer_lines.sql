@@ -0,0 +1 @@
+SELECT ITEM_ID, QTY_REQUESTED, QTY_APPROVED, QTY_SHIPPED, QTY_RECEIVED FROM ODBC_JHC.TRANSFER_LINES_JHC WHERE TRANSFER_ID = :1 ORDER BY ITEM_ID
\ No newline at end of file
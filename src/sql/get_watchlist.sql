@@ -0,0 +1 @@
+SELECT WATCH_ID, ITEM_ID, STORE_ID, ON_STOCKOUT, ON_PRICE, TO_CHAR(CREATED_AT, 'YYYY-MM-DD HH24:MI:SS') CREATED_AT FROM ODBC_JHC.WATCHLIST_JHC WHERE USERNAME = :1 ORDER BY ITEM_ID, STORE_ID
\ No newline at end of file
@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.COUNT_LINES_JHC (SESSION_ID, ITEM_ID, SCANNED_CODE, QTY, USERNAME, CREATED_AT) VALUES (:1, :2, :3, :4, :5, SYSDATE)
\ No newline at end of file
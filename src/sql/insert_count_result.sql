@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.COUNT_RESULTS_JHC (SESSION_ID, ITEM_ID, ITEM_DESC, SYSTEM_QTY, COUNTED_QTY) VALUES (:1, :2, :3, :4, :5)
\ No newline at end of file
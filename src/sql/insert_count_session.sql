@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.COUNT_SESSIONS_JHC (SESSION_ID, STORE_ID, ITEM_CAT, STATUS, CREATED_BY, NOTE, CREATED_AT) VALUES (:1, :2, :3, 'OPEN', :4, :5, SYSDATE)
\ No newline at end of file
@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.IMAGE_REVISIONS_JHC (ITEM_ID, IMAGE_ID, ACTION, HAS_FILE, CHANGED_BY) VALUES (:1, :2, :3, :4, :5) RETURNING REVISION_ID INTO :6
\ No newline at end of file
@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.INV_CHANGES_JHC (ITEM_ID, STORE_ID, FIELD, OLD_VALUE, NEW_VALUE, CHANGED_AT) VALUES (:1, :2, :3, :4, :5, SYSDATE)
\ No newline at end of file
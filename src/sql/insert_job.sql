@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.JOBS_JHC (KIND, STATUS, CREATED_BY, PAYLOAD) VALUES (:1, :2, :3, :4) RETURNING JOB_ID INTO :5
\ No newline at end of file
@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.NOTIFICATIONS_JHC (USERNAME, ITEM_ID, STORE_ID, KIND, MESSAGE) VALUES (:1, :2, :3, :4, :5)
\ No newline at end of file
@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.PRODUCT_IMAGES_JHC (ITEM_ID, POSITION, IS_PRIMARY, UPLOADED_BY) VALUES (:1, :2, :3, :4) RETURNING IMAGE_ID INTO :5
\ No newline at end of file
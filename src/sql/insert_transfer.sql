@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.TRANSFERS_JHC (TRANSFER_ID, FROM_STORE, TO_STORE, STATUS, REQUESTED_BY, NOTE, CREATED_AT, UPDATED_AT) VALUES (:1, :2, :3, :4, :5, :6, SYSDATE, SYSDATE)
\ No newline at end of file
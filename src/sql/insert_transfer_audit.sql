@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.TRANSFER_AUDIT_JHC (TRANSFER_ID, ACTION, USERNAME, FROM_STATUS, TO_STATUS, NOTE, CREATED_AT) VALUES (:1, :2, :3, :4, :5, :6, SYSDATE)
\ No newline at end of file
@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.TRANSFER_LINES_JHC (TRANSFER_ID, ITEM_ID, QTY_REQUESTED) VALUES (:1, :2, :3)
\ No newline at end of file
@@ -0,0 +1 @@
+INSERT INTO ODBC_JHC.WATCHLIST_JHC (USERNAME, ITEM_ID, STORE_ID, ON_STOCKOUT, ON_PRICE) VALUES (:1, :2, :3, :4, :5) RETURNING WATCH_ID INTO :6
\ No newline at end of file
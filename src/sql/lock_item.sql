@@ -0,0 +1 @@
+SELECT ITEM_ID FROM ODBC_JHC.JHC_INVDATA WHERE ITEM_ID = :1 FOR UPDATE
\ No newline at end of file
@@ -0,0 +1 @@
+UPDATE ODBC_JHC.NOTIFICATIONS_JHC SET READ_AT = NVL(READ_AT, SYSDATE) WHERE NOTIFICATION_ID = :1 AND USERNAME = :2
\ No newline at end of file
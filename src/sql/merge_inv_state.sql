@@ -0,0 +1 @@
+MERGE INTO ODBC_JHC.INV_STATE_JHC S USING DUAL ON (S.ITEM_ID = :item_id AND S.STORE_ID = :store_id) WHEN MATCHED THEN UPDATE SET S.QTY = :qty, S.SALE_PRICE_NOTAX = :price, S.FIRST_DISC_PER = :first_disc, S.SECOND_DISC_PER = :second_disc, S.CAPTURED_AT = SYSDATE WHEN NOT MATCHED THEN INSERT (ITEM_ID, STORE_ID, QTY, SALE_PRICE_NOTAX, FIRST_DISC_PER, SECOND_DISC_PER, CAPTURED_AT) VALUES (:item_id, :store_id, :qty, :price, :first_disc, :second_disc, SYSDATE)
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT ODBC_JHC.COUNT_SESSIONS_JHC_SEQ.NEXTVAL FROM DUAL
\ No newline at end of file
@@ -0,0 +1 @@
+UPDATE ODBC_JHC.JOBS_JHC SET STATUS = 'QUEUED', RUN_AFTER = SYSDATE WHERE STATUS = 'RUNNING'
\ No newline at end of file
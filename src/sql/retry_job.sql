@@ -0,0 +1 @@
+UPDATE ODBC_JHC.JOBS_JHC SET STATUS = 'QUEUED', RUN_AFTER = SYSDATE + :1 / 86400, LAST_ERROR = :2 WHERE JOB_ID = :3
\ No newline at end of file
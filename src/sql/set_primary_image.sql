@@ -0,0 +1 @@
+UPDATE ODBC_JHC.PRODUCT_IMAGES_JHC SET IS_PRIMARY = CASE WHEN IMAGE_ID = :image_id THEN 1 ELSE 0 END WHERE ITEM_ID = :item_id
\ No newline at end of file
@@ -0,0 +1,22 @@
+SELECT
+    ITEM_ID,
+    ITEM_DESC,
+    ITEM_MAIN_BARCODE
+FROM
+    ODBC_JHC.JHC_INVDATA
+WHERE
+    ITEM_ID LIKE :prefix ESCAPE '\'
+    OR FOREIGN_ITEM_CODE LIKE :prefix ESCAPE '\'
+    OR ITEM_MAIN_BARCODE LIKE :prefix ESCAPE '\'
+    OR UPPER(ITEM_DESC) LIKE :prefix ESCAPE '\'
+    OR UPPER(ITEM_DESC) LIKE :word_prefix ESCAPE '\'
+    OR UPPER(ITEM_DESC_S) LIKE :prefix ESCAPE '\'
+    OR UPPER(ITEM_DESC_S) LIKE :word_prefix ESCAPE '\'
+ORDER BY
+    CASE
+        WHEN ITEM_ID = :code OR FOREIGN_ITEM_CODE = :code OR ITEM_MAIN_BARCODE = :code THEN 0
+        WHEN ITEM_ID LIKE :prefix ESCAPE '\' OR FOREIGN_ITEM_CODE LIKE :prefix ESCAPE '\' OR ITEM_MAIN_BARCODE LIKE :prefix ESCAPE '\' THEN 1
+        ELSE 2
+    END,
+    ITEM_ID
+FETCH FIRST :limit ROWS ONLY
\ No newline at end of file
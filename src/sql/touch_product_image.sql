@@ -0,0 +1 @@
+UPDATE ODBC_JHC.PRODUCT_IMAGES_JHC SET UPLOADED_BY = :1, UPLOADED_AT = SYSDATE WHERE IMAGE_ID = :2
\ No newline at end of file
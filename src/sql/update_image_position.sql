@@ -0,0 +1 @@
+UPDATE ODBC_JHC.PRODUCT_IMAGES_JHC SET POSITION = :1 WHERE IMAGE_ID = :2 AND ITEM_ID = :3
\ No newline at end of file
@@ -0,0 +1 @@
+UPDATE ODBC_JHC.TRANSFER_LINES_JHC SET QTY_APPROVED = :1, QTY_SHIPPED = :2, QTY_RECEIVED = :3 WHERE TRANSFER_ID = :4 AND ITEM_ID = :5
\ No newline at end of file
@@ -0,0 +1 @@
+UPDATE ODBC_JHC.TRANSFERS_JHC SET STATUS = :1, UPDATED_AT = SYSDATE WHERE TRANSFER_ID = :2
\ No newline at end of file
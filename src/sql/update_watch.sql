@@ -0,0 +1 @@
+UPDATE ODBC_JHC.WATCHLIST_JHC SET STORE_ID = :1, ON_STOCKOUT = :2, ON_PRICE = :3 WHERE WATCH_ID = :4 AND USERNAME = :5
\ No newline at end of file
@@ -0,0 +1,31 @@
+use oracle::sql_type::OracleType;
+use oracle::Connection;
+
+use super::structs::APIErrors;
+
+/// Bind type for decimals, bound as `(&value.to_string(), &NUMBER)`. The client turns the
+/// text into a NUMBER with '.' as separator, a plain string bind would be converted by
+/// the server with the session's NLS_NUMERIC_CHARACTERS.
+pub const NUMBER: OracleType = OracleType::Number(0, 0);
+
+/// Log an oracle error and turn it into `APIErrors::DBError`
+pub fn db_error(e: oracle::Error) -> APIErrors {
+    error!("Database Error: {}", e);
+    APIErrors::DBError
+}
+
+/// Commit when `work` succeeds, roll back otherwise
+pub fn in_transaction<T>(conn: &Connection, work: impl FnOnce() -> Result<T, APIErrors>) -> Result<T, APIErrors> {
+    match work() {
+        Ok(value) => {
+            conn.commit().map_err(db_error)?;
+            Ok(value)
+        }
+        Err(e) => {
+            if let Err(rollback_error) = conn.rollback() {
+                error!("Rollback failed: {}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
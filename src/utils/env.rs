@@ -0,0 +1,26 @@
+use std::str::FromStr;
+
+/// Set and non-empty
+pub fn env_value(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// Numeric setting, `default` when unset or not a number
+pub fn env_number<T: FromStr>(name: &str, default: T) -> T {
+    env_value(name).and_then(|value| value.trim().parse::<T>().ok()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_env_number() {
+        std::env::set_var("JHAPI_TEST_ENV_NUMBER", " 42 ");
+        std::env::set_var("JHAPI_TEST_ENV_TEXT", "many");
+        assert_eq!(env_number("JHAPI_TEST_ENV_NUMBER", 7u64), 42);
+        assert_eq!(env_number("JHAPI_TEST_ENV_TEXT", 7u64), 7);
+        assert_eq!(env_number("JHAPI_TEST_ENV_UNSET", -1i64), -1);
+        assert_eq!(env_value("JHAPI_TEST_ENV_UNSET"), None);
+    }
+}
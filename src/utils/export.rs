@@ -0,0 +1,65 @@
+/// Rows that can be written as CSV, the column order is given by `headers`
+pub trait CsvRow {
+    fn headers() -> &'static [&'static str];
+    fn values(&self) -> Vec<String>;
+}
+
+/// Anything that can be exported as a whole CSV document
+pub trait CsvTable {
+    fn to_csv(&self) -> String;
+}
+
+impl<T: CsvRow> CsvTable for Vec<T> {
+    fn to_csv(&self) -> String {
+        to_csv(self)
+    }
+}
+
+/// Quote a field when it holds a separator, a quote or a line break
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Header line then one line per row, CRLF terminated
+pub fn csv_document(headers: &[&str], lines: &[Vec<String>]) -> String {
+    let mut csv = headers.join(",");
+    csv.push_str("\r\n");
+    for line in lines {
+        let values: Vec<String> = line.iter().map(|v| escape_field(v)).collect();
+        csv.push_str(&values.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+pub fn to_csv<T: CsvRow>(rows: &[T]) -> String {
+    let lines: Vec<Vec<String>> = rows.iter().map(|row| row.values()).collect();
+    csv_document(T::headers(), &lines)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Row(&'static str, &'static str);
+
+    impl CsvRow for Row {
+        fn headers() -> &'static [&'static str] {
+            &["ID", "DESC"]
+        }
+
+        fn values(&self) -> Vec<String> {
+            vec![self.0.to_string(), self.1.to_string()]
+        }
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let csv = to_csv(&[Row("1", "MILK, 1L"), Row("2", "12\" PAN")]);
+        assert_eq!(csv, "ID,DESC\r\n1,\"MILK, 1L\"\r\n2,\"12\"\" PAN\"\r\n");
+    }
+}
@@ -4,8 +4,12 @@ use oracle::pool::Pool;
 
 use self::{sql::SQLManager, structs::APIErrors};
 
+pub mod db;
+pub mod env;
+pub mod export;
 pub mod logging;
 pub mod permissions;
+pub mod scheduler;
 pub mod structs;
 pub mod sql;
 pub mod testing;
@@ -105,7 +105,6 @@ pub async fn has_query_perm(_key: &ApiKey<'_>, pool: &Pool, sql_manager: &SQLMan
 }
 
 // Check for Stock Permissions
-#[allow(dead_code)]
 pub async fn is_stock_perm(_key: &ApiKey<'_>, pool: &Pool, sql_manager: &SQLManager) -> bool {
     match decode_token_data(_key.0) {
         Some(x) => {
@@ -122,7 +121,6 @@ pub async fn is_stock_perm(_key: &ApiKey<'_>, pool: &Pool, sql_manager: &SQLMana
 }
 
 
-#[allow(dead_code)]
 /// Check for Reports Permissions
 pub async fn has_reports_perm(_key: &ApiKey<'_>, pool: &Pool, sql_manager: &SQLManager) -> bool {
     match decode_token_data(_key.0) {
@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use super::structs::APIErrors;
+
+/// Run a blocking job every `period`, the first run happens right away.
+/// Jobs run on the blocking thread pool so DB work doesn't stall request handlers.
+pub fn spawn_periodic<F>(name: &'static str, period: Duration, job: F)
+where
+    F: Fn() -> Result<(), APIErrors> + Send + Sync + 'static,
+{
+    let job = std::sync::Arc::new(job);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let job = job.clone();
+            match tokio::task::spawn_blocking(move || job()).await {
+                Ok(Ok(())) => info!("Scheduled job {} finished", name),
+                Ok(Err(e)) => error!("Scheduled job {} failed: {}", name, e),
+                Err(e) => error!("Scheduled job {} panicked: {:?}", name, e),
+            }
+        }
+    });
+}
@@ -43,6 +43,7 @@ pub async fn load_to_hashmap() -> Result<HashMap<String,String>,APIErrors> {
     Ok(table)
 }
 
+#[derive(Clone)]
 pub struct SQLManager{
     pub map: HashMap<String,String>
 }
@@ -10,7 +10,11 @@ pub enum APIErrors {
     FileNotFound,
     InvalidCredentials,
     NoData,
-    IOError
+    IOError,
+    InvalidState,
+    NoStoreAccess,
+    QueueFull,
+    StoreUnavailable,
 }
 
 use std::fmt;
@@ -30,6 +34,10 @@ impl fmt::Display for APIErrors {
             APIErrors::InvalidCredentials => write!(f, "Invalid Credentials"),
             APIErrors::NoData => write!(f, "No Data Found"),
             APIErrors::IOError => write!(f, "IO Error"),
+            APIErrors::InvalidState => write!(f, "Invalid State"),
+            APIErrors::NoStoreAccess => write!(f, "No Store Access"),
+            APIErrors::QueueFull => write!(f, "Job Queue Full"),
+            APIErrors::StoreUnavailable => write!(f, "Image Store Unavailable"),
         }
     }
 }
\ No newline at end of file